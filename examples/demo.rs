@@ -5,9 +5,9 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         // !!!!IMPORTANT!!!! Add the OverlayPlugin here
-        .add_plugins(OverlayPlugin { font_size: 23.0, ..default() })
+        .add_plugins(OverlayPlugin { font_size: 23.0, show_fps: true, ..default() })
         .add_systems(Startup, setup)
-        .add_systems(Update, (screen_print_text, show_fps, show_cursor_position))
+        .add_systems(Update, (screen_print_text, show_cursor_position))
         .run();
 }
 
@@ -69,30 +69,7 @@ fn screen_print_text(time: Res<Time>) {
         let col = Color::PINK;
         screen_print!(sec: 3.2123, col: col, "every 30: {mut_show:?}");
     }
-    if at_interval(0.13243) {
-        screen_print!("current time: {current_time:.2}")
-    }
-}
-
-fn show_fps(time: Res<Time>, mut deltas: Local<Vec<f32>>, mut ring_ptr: Local<usize>) {
-    let delta = time.delta_seconds_f64();
-    let current_time = time.elapsed_seconds_f64();
-    let at_interval = |t: f64| current_time % t < delta;
-    if *ring_ptr >= 4096 {
-        *ring_ptr = 0;
-    }
-    if deltas.len() <= *ring_ptr {
-        deltas.push(time.delta_seconds());
-    } else {
-        deltas.insert(*ring_ptr, time.delta_seconds());
-    }
-    *ring_ptr += 1;
-    if at_interval(2.0) {
-        let fps = deltas.len() as f32 / deltas.iter().sum::<f32>();
-        let last_fps = 1.0 / time.delta_seconds();
-        screen_print!(col: Color::GREEN, "fps: {fps:.0}");
-        screen_print!(col: Color::CYAN, "last: {last_fps:.0}");
-    }
+    screen_print!(every: 0.13243, "current time: {current_time:.2}");
 }
 
 fn show_cursor_position(
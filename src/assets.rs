@@ -0,0 +1,83 @@
+//! Loading status of assets, see [`OverlayAssetsPlugin`].
+use std::marker::PhantomData;
+
+use bevy::asset::{AssetLoadFailedEvent, LoadState};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::{screen_print, Level};
+
+/// Lists the `A` assets that are still loading or failed to load, with their
+/// path, and for failures the error.
+///
+/// Only assets of which a `Handle<A>` is a component of an entity are
+/// listed, such as the `Handle<Image>` of sprites. An asset stops being
+/// listed a short moment after it is loaded, or once its entities are
+/// despawned. Add the plugin once per asset type to watch.
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin) and bevy's
+/// `AssetPlugin`.
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{OverlayAssetsPlugin, OverlayPlugin};
+///
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     OverlayPlugin::default(),
+///     OverlayAssetsPlugin::<Image>::default(),
+///     OverlayAssetsPlugin::<Mesh>::default(),
+/// ));
+/// ```
+pub struct OverlayAssetsPlugin<A>(PhantomData<fn() -> A>);
+impl<A> Default for OverlayAssetsPlugin<A> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<A: Asset> Plugin for OverlayAssetsPlugin<A> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, show_asset_status::<A>);
+    }
+}
+
+/// Print the loading and failed `A` assets, `failures` holds the errors of
+/// assets that failed to load.
+fn show_asset_status<A: Asset>(
+    handles: Query<&Handle<A>>,
+    server: Option<Res<AssetServer>>,
+    mut failed: EventReader<AssetLoadFailedEvent<A>>,
+    mut failures: Local<HashMap<AssetId<A>, String>>,
+) {
+    let Some(server) = server else {
+        return;
+    };
+    for AssetLoadFailedEvent { id, error, .. } in failed.read() {
+        failures.insert(*id, error.to_string());
+    }
+    let mut seen = HashSet::new();
+    for handle in &handles {
+        let id = handle.id();
+        if !seen.insert(id) {
+            continue;
+        }
+        let Some(path) = server.get_path(id) else {
+            continue;
+        };
+        let key = format!("asset {path}");
+        match server.load_state(id) {
+            LoadState::Loading => {
+                screen_print!(sec: 0.5, key: key, "loading: {path}");
+            }
+            LoadState::Failed => {
+                let error = failures.get(&id).map_or("unknown error", String::as_str);
+                screen_print!(
+                    sec: 0.5, level: Level::Error, col: Color::RED, key: key,
+                    "failed: {path}: {error}"
+                );
+            }
+            LoadState::NotLoaded | LoadState::Loaded => {}
+        }
+    }
+    failures.retain(|id, _| server.load_state(*id) == LoadState::Failed);
+}
@@ -0,0 +1,30 @@
+//! Audio cue of messages printed with the `beep` option, see
+//! [`OverlayPlugin::beep_sound`].
+//!
+//! [`OverlayPlugin::beep_sound`]: crate::OverlayPlugin::beep_sound
+use std::time::Duration;
+
+use bevy::audio::{AudioBundle, AudioSource, Pitch, PitchBundle, PlaybackSettings};
+use bevy::prelude::{AssetServer, Assets, World};
+
+/// Play `sound`, or a short tone if it is `None`. Does nothing without
+/// bevy's `AudioPlugin`.
+pub(crate) fn beep(world: &mut World, sound: Option<&'static str>) {
+    let settings = PlaybackSettings::DESPAWN;
+    match sound {
+        Some(path) => {
+            let Some(assets) = world.get_resource::<AssetServer>() else {
+                return;
+            };
+            let source = assets.load::<AudioSource>(path);
+            world.spawn(AudioBundle { source, settings });
+        }
+        None => {
+            let Some(mut pitches) = world.get_resource_mut::<Assets<Pitch>>() else {
+                return;
+            };
+            let source = pitches.add(Pitch::new(880.0, Duration::from_millis(150)));
+            world.spawn(PitchBundle { source, settings });
+        }
+    }
+}
@@ -5,13 +5,20 @@
 //! A [`Block`] represents something take takes [`Block::size`] space or a gap
 //! in space.
 //!
-//! [`Blocks`] acts like a heap, where you can add and remove things.
+//! [`Blocks`] acts like a heap, where you can add and remove things, with
+//! optional spacing between them and slots reserved at the start of the line.
+//!
+//! [`Columns`] puts several [`Blocks`] side by side, when they have a maximum
+//! size.
+//!
+//! They are used by [`Layout::Stack`](crate::Layout::Stack), and can be used
+//! to implement an [`OverlayLayout`](crate::OverlayLayout).
 use std::iter::{once, Sum};
 use std::ops::{AddAssign, Sub};
 
 /// A quantity that can be added, substracted and has a ZERO. Generally known
 /// as a monoid.
-pub(crate) trait Summable:
+pub trait Summable:
     Sum + for<'a> AddAssign<&'a Self> + Sub<Output = Self> + PartialOrd + PartialEq + Copy
 {
     const ZERO: Self;
@@ -23,10 +30,11 @@ impl Summable for f32 {
 /// A `Block` represents something take takes [`Block::size`] space or a gap
 /// in space `S`. Each occupied `Block` is identified with `Id`.
 #[derive(Debug, Clone, Copy)]
-pub(crate) enum Block<Id, S> {
+enum Block<Id, S> {
     /// A void of size `S` left from something that was removed.
     Gap(S),
-    /// Something identified by `Id` that takes space `S`.
+    /// Something identified by `Id` that takes space `S`, including the
+    /// spacing after it.
     Full(Id, S),
 }
 impl<Id, S> Block<Id, S> {
@@ -46,13 +54,34 @@ impl<Id, S> Block<Id, S> {
         matches!(self, Block::Full(self_id, _) if self_id == id)
     }
 }
+
+/// A slot at the start of [`Blocks`], kept for `id`, see [`Blocks::reserve`].
+#[derive(Debug, Clone, Copy)]
+struct Slot<Id, S> {
+    id: Id,
+    /// Size of the slot, including the spacing after it.
+    size: S,
+    /// Whether `id` is inserted.
+    filled: bool,
+}
+
 /// `Blocks` manage resource allocation on a 1D line of generic type
 /// `S: `[`Summable`], and each allocation block is identified by `Id`.
 #[derive(Debug)]
-pub(crate) struct Blocks<Id, S>(Vec<Block<Id, S>>);
-impl<Id, S> Default for Blocks<Id, S> {
+pub struct Blocks<Id, S> {
+    blocks: Vec<Block<Id, S>>,
+    /// Slots before `blocks`, in order.
+    slots: Vec<Slot<Id, S>>,
+    /// Space left between consecutive blocks.
+    spacing: S,
+}
+impl<Id, S: Summable> Default for Blocks<Id, S> {
     fn default() -> Self {
-        Self(Vec::new())
+        Self {
+            blocks: Vec::new(),
+            slots: Vec::new(),
+            spacing: S::ZERO,
+        }
     }
 }
 struct Gap<S> {
@@ -64,9 +93,14 @@ where
     Id: PartialEq,
     S: Summable,
 {
+    /// Leave `spacing` between consecutive blocks, it is not part of their
+    /// size. Gaps left by removed blocks include the spacing after them.
+    pub fn with_spacing(self, spacing: S) -> Self {
+        Self { spacing, ..self }
+    }
     /// This assumes, `Self` is [cleaned up](Blocks::cleanup).
     fn first_gap_of_size(&self, size: S) -> Option<Gap<S>> {
-        self.0
+        self.blocks
             .iter()
             .enumerate()
             .find(|(_, block)| matches!(block, Block::Gap(gap) if gap >= &size))
@@ -77,32 +111,110 @@ where
         match gap {
             Some(Gap { index, gap_size }) if gap_size > &size => {
                 let gap = Block::Gap(*gap_size - size);
-                self.0.splice(index..=index, [to_insert, gap]);
+                self.blocks.splice(index..=index, [to_insert, gap]);
                 self.cleanup();
             }
-            Some(Gap { index, .. }) => self.0[*index] = to_insert,
-            None => self.0.push(to_insert),
+            Some(Gap { index, .. }) => self.blocks[*index] = to_insert,
+            None => self.blocks.push(to_insert),
         };
     }
-    pub(crate) fn insert_size(&mut self, id: Id, size: S) -> S {
+    fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.slots.is_empty()
+    }
+    /// Space taken by the slots, including the spacing after the last one.
+    fn slots_size(&self) -> S {
+        self.slots.iter().map(|slot| slot.size).sum()
+    }
+    /// `size` followed by the spacing.
+    fn spaced(&self, size: S) -> S {
+        let mut spaced = size;
+        spaced += &self.spacing;
+        spaced
+    }
+    /// Insert `id` in the first gap it fits in, or at the end, returning its
+    /// offset from the start of the line. If `id` has a [reserved](Self::reserve)
+    /// slot, it is placed in its slot, whatever its `size`.
+    pub fn insert_size(&mut self, id: Id, size: S) -> S {
+        let mut offset = S::ZERO;
+        for slot in &mut self.slots {
+            if slot.id == id {
+                slot.filled = true;
+                return offset;
+            }
+            offset += &slot.size;
+        }
+        let size = self.spaced(size);
         let gap_range = self.first_gap_of_size(size);
-        let old_len = self.0.len();
+        let old_len = self.blocks.len();
         self.replace_gap(gap_range.as_ref(), id, size);
         let start = gap_range.map_or(old_len, |Gap { index, .. }| index);
-        self.0.iter().take(start).map(Block::size).sum()
+        offset += &self.blocks.iter().take(start).map(Block::size).sum();
+        offset
+    }
+    /// Whether a block of `size` can be inserted without ending past `max`.
+    /// A block always fits when `self` is empty, even if larger than `max`.
+    fn fits(&self, size: S, max: S) -> bool {
+        let mut end = self.slots_size();
+        end += &self.blocks.iter().map(Block::size).sum();
+        end += &size;
+        self.is_empty() || end <= max || self.first_gap_of_size(self.spaced(size)).is_some()
     }
-    pub(crate) fn remove(&mut self, id: Id) {
-        if let Some(to_remove) = self.0.iter_mut().find(|block| block.has_id(&id)) {
+    pub fn remove(&mut self, id: Id) {
+        match self.slots.iter_mut().find(|slot| slot.id == id) {
+            Some(slot) => slot.filled = false,
+            None => self.remove_block(&id),
+        }
+    }
+    /// Replace the block of `id` by a gap, whether it has a slot or not.
+    fn remove_block(&mut self, id: &Id) {
+        if let Some(to_remove) = self.blocks.iter_mut().find(|block| block.has_id(id)) {
             *to_remove = Block::Gap(to_remove.size());
         }
         self.cleanup();
     }
+    /// Keep a slot of `size` for `id` at the start of the line, after the
+    /// previously reserved slots. `id` is placed in its slot when inserted,
+    /// and the slot stays empty otherwise. Blocks already inserted move
+    /// further by the size of the slot, so slots are best reserved first.
+    pub fn reserve(&mut self, id: Id, size: S) {
+        let mut slots = self.slots.iter();
+        let filled = slots.any(|slot| slot.id == id && slot.filled)
+            || self.blocks.iter().any(|block| block.has_id(&id));
+        self.unreserve(&id);
+        self.remove_block(&id);
+        let size = self.spaced(size);
+        self.slots.push(Slot { id, size, filled });
+    }
+    /// Remove the slot reserved for `id`, the blocks after it move back.
+    pub fn unreserve(&mut self, id: &Id) {
+        self.slots.retain(|slot| slot.id != *id);
+    }
+    /// Space taken by all blocks and slots, including gaps between them.
+    pub fn total_size(&self) -> S {
+        if self.is_empty() {
+            return S::ZERO;
+        }
+        let mut total = self.slots_size();
+        total += &self.blocks.iter().map(Block::size).sum();
+        total - self.spacing
+    }
+    /// Space taken by the block of `id`, if it is inserted.
+    pub fn size_of(&self, id: &Id) -> Option<S> {
+        let mut slots = self.slots.iter();
+        if let Some(slot) = slots.find(|slot| slot.id == *id) {
+            return slot.filled.then(|| slot.size - self.spacing);
+        }
+        self.blocks
+            .iter()
+            .find(|block| block.has_id(id))
+            .map(|block| block.size() - self.spacing)
+    }
     /// Remove [`Block::Gap`] at the end of `self` and merges adjacent gaps.
     fn cleanup(&mut self) {
         let mut cur_gap = S::ZERO;
         let mut gap_start = 0;
         let mut splice_commands = Vec::new();
-        for (i, block) in self.0.iter().enumerate() {
+        for (i, block) in self.blocks.iter().enumerate() {
             match block {
                 Block::Gap(gap) if cur_gap == S::ZERO => {
                     gap_start = i;
@@ -119,13 +231,76 @@ where
         }
         for (start, end, size) in splice_commands.into_iter() {
             let to_insert = Block::Gap(size);
-            self.0.splice(start..end, once(to_insert));
+            self.blocks.splice(start..end, once(to_insert));
         }
-        if matches!(self.0.last(), Some(Block::Gap(_))) {
-            self.0.pop().expect("We just tested Vec::last is Some");
+        while matches!(self.blocks.last(), Some(Block::Gap(_))) {
+            self.blocks.pop().expect("We just tested Vec::last is Some");
         }
     }
 }
+
+/// Multiple [`Blocks`] side by side, a new one is started when a block
+/// doesn't fit in the previous ones, if they have a maximum size.
+#[derive(Debug)]
+pub struct Columns<Id, S> {
+    max_size: Option<S>,
+    /// Spacing of the [`Blocks`] of each column.
+    spacing: S,
+    columns: Vec<Blocks<Id, S>>,
+}
+impl<Id, S> Columns<Id, S>
+where
+    Id: PartialEq,
+    S: Summable,
+{
+    /// Columns of at most `max_size`, `None` for a single column.
+    pub fn new(max_size: Option<S>) -> Self {
+        Self { max_size, spacing: S::ZERO, columns: Vec::new() }
+    }
+    /// Leave `spacing` between consecutive blocks of each column, see
+    /// [`Blocks::with_spacing`].
+    pub fn with_spacing(self, spacing: S) -> Self {
+        Self { spacing, ..self }
+    }
+    /// Insert `id` in the first column it fits in, returning the index of
+    /// the column and the offset in that column.
+    pub fn insert_size(&mut self, id: Id, size: S) -> (usize, S) {
+        let max_size = self.max_size;
+        let fits = |column: &Blocks<Id, S>| max_size.map_or(true, |max| column.fits(size, max));
+        let index = self.columns.iter().position(fits).unwrap_or_else(|| {
+            self.columns
+                .push(Blocks::default().with_spacing(self.spacing));
+            self.columns.len() - 1
+        });
+        (index, self.columns[index].insert_size(id, size))
+    }
+    pub fn remove(&mut self, id: Id)
+    where
+        Id: Copy,
+    {
+        for column in &mut self.columns {
+            column.remove(id);
+        }
+        while matches!(self.columns.last(), Some(column) if column.is_empty()) {
+            self.columns.pop();
+        }
+    }
+    /// Space taken by `id`, `None` if it is not in any column.
+    pub fn size_of(&self, id: Id) -> Option<S> {
+        self.columns.iter().find_map(|column| column.size_of(&id))
+    }
+    /// Number of columns, including empty columns before non-empty ones.
+    #[cfg(test)]
+    fn count(&self) -> usize {
+        self.columns.len()
+    }
+    /// Space taken by the largest column.
+    pub fn total_size(&self) -> S {
+        let sizes = self.columns.iter().map(Blocks::total_size);
+        sizes.fold(S::ZERO, |max, size| if size > max { size } else { max })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // TODO: very small deltas on S==f32 may cause issues down the line
@@ -185,4 +360,107 @@ mod tests {
         blocks.remove(3);
         assert_eq!(1., blocks.insert_size(4, 1.));
     }
+    #[test]
+    fn test_columns_overflow() {
+        let mut columns = Columns::new(Some(4.));
+        assert_eq!((0, 0.), columns.insert_size(1_u8, 3.));
+        assert_eq!((1, 0.), columns.insert_size(2, 2.));
+        assert_eq!((0, 3.), columns.insert_size(3, 1.));
+        assert_eq!((1, 2.), columns.insert_size(4, 2.));
+        assert_eq!((2, 0.), columns.insert_size(5, 8.));
+        assert_eq!(3, columns.count());
+        assert_eq!(8., columns.total_size());
+        columns.remove(5);
+        assert_eq!(2, columns.count());
+        assert_eq!(4., columns.total_size());
+    }
+    #[test]
+    fn test_columns_gap_reuse() {
+        let mut columns = Columns::new(Some(4.));
+        columns.insert_size(1_u8, 2.);
+        columns.insert_size(2, 2.);
+        columns.insert_size(3, 2.);
+        columns.remove(1);
+        assert_eq!((0, 0.), columns.insert_size(4, 2.));
+        assert_eq!((1, 2.), columns.insert_size(5, 2.));
+    }
+    #[test]
+    fn test_single_column() {
+        let mut columns = Columns::new(None);
+        columns.insert_size(1_u8, 3.);
+        assert_eq!((0, 3.), columns.insert_size(2, 30.));
+        assert_eq!(1, columns.count());
+    }
+    #[test]
+    fn test_columns_size_of() {
+        let mut columns = Columns::new(Some(10.0));
+        columns.insert_size(1, 6.0);
+        columns.insert_size(2, 8.0);
+        assert_eq!(columns.size_of(2), Some(8.0));
+        columns.remove(2);
+        assert_eq!(columns.size_of(2), None);
+        assert_eq!(columns.size_of(1), Some(6.0));
+    }
+    #[test]
+    fn test_spacing() {
+        let mut blocks = Blocks::default().with_spacing(1.);
+        assert_eq!(0., blocks.insert_size(1_u8, 3.));
+        assert_eq!(4., blocks.insert_size(2, 2.));
+        assert_eq!(7., blocks.insert_size(3, 2.));
+        assert_eq!(9., blocks.total_size());
+        assert_eq!(Some(2.), blocks.size_of(&2));
+        blocks.remove(2);
+        // The gap is 2 plus its spacing.
+        assert_eq!(10., blocks.insert_size(4, 3.));
+        assert_eq!(4., blocks.insert_size(5, 2.));
+    }
+    #[test]
+    fn test_columns_spacing() {
+        let mut columns = Columns::new(Some(6.)).with_spacing(1.);
+        assert_eq!((0, 0.), columns.insert_size(1_u8, 3.));
+        assert_eq!((1, 0.), columns.insert_size(2, 3.));
+        assert_eq!((0, 4.), columns.insert_size(3, 2.));
+    }
+    #[test]
+    fn test_reserved_slots() {
+        let mut blocks = Blocks::default();
+        blocks.reserve(1_u8, 2.);
+        blocks.reserve(2, 1.);
+        assert_eq!(3., blocks.total_size());
+        assert_eq!(3., blocks.insert_size(3, 4.));
+        assert_eq!(2., blocks.insert_size(2, 5.));
+        assert_eq!(Some(1.), blocks.size_of(&2));
+        assert_eq!(None, blocks.size_of(&1));
+        blocks.remove(2);
+        // Slots are not given to other blocks.
+        assert_eq!(7., blocks.insert_size(4, 1.));
+        blocks.unreserve(&1);
+        assert_eq!(Some(4.), blocks.size_of(&3));
+        assert_eq!(0., blocks.insert_size(2, 1.));
+        blocks.remove(3);
+        assert_eq!(1., blocks.insert_size(3, 4.));
+    }
+    #[test]
+    fn test_reserve_inserted_block() {
+        let mut blocks = Blocks::default().with_spacing(1.);
+        blocks.insert_size(1_u8, 2.);
+        blocks.insert_size(2, 2.);
+        blocks.reserve(2, 3.);
+        assert_eq!(Some(3.), blocks.size_of(&2));
+        blocks.remove(1);
+        assert_eq!(4., blocks.insert_size(1, 2.));
+        assert_eq!(6., blocks.total_size());
+    }
+    #[test]
+    fn test_total_size() {
+        let mut blocks = Blocks::default();
+        assert_eq!(0., blocks.total_size());
+        blocks.insert_size(1_u8, 1.);
+        blocks.insert_size(2, 2.);
+        blocks.insert_size(3, 3.);
+        blocks.remove(2);
+        assert_eq!(1. + 2. + 3., blocks.total_size());
+        blocks.remove(3);
+        assert_eq!(1., blocks.total_size());
+    }
 }
@@ -0,0 +1,12 @@
+//! Copy clicked messages to the system clipboard, see
+//! [`OverlayPlugin::click_to_copy`].
+//!
+//! [`OverlayPlugin::click_to_copy`]: crate::OverlayPlugin::click_to_copy
+use bevy::log::warn;
+
+pub(crate) fn copy(text: &str) {
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    if let Err(error) = copied {
+        warn!("Failed to copy debug message to the clipboard: {error}");
+    }
+}
@@ -0,0 +1,185 @@
+//! An in-game developer console, see [`ConsolePlugin`].
+use std::mem;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::ReceivedCharacter;
+
+use crate::{command_channels, InvocationSiteKey, OverlaySearch, PrintOptions};
+
+type Handler = Box<dyn Fn(&mut World, &[&str]) + Send + Sync>;
+
+/// Print `text` in the overlay as console output.
+fn output(text: String) {
+    let key = InvocationSiteKey::Named("console".into());
+    let options = PrintOptions::default().push(true).no_prefix(true);
+    command_channels().print(key, options, || text);
+}
+
+/// Commands that can be typed in the console, see [`ConsolePlugin`].
+///
+/// A command line is split on whitespaces, the first word is the name of the
+/// command, and the rest are passed as arguments to its handler. Handlers can
+/// print their output with [`screen_print!`].
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{screen_print, ConsoleCommands, ConsolePlugin, OverlayPlugin};
+///
+/// let mut app = App::new();
+/// app.add_plugins((OverlayPlugin::default(), ConsolePlugin::default()));
+/// let mut commands = app.world.resource_mut::<ConsoleCommands>();
+/// commands.register("entities", |world, _args| {
+///     screen_print!(push, "{} entities", world.entities().len());
+/// });
+/// ```
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    handlers: HashMap<String, Handler>,
+}
+impl ConsoleCommands {
+    /// Run `handler` when a line starting with `name` is entered, replacing
+    /// any previous handler for `name`.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&mut World, &[&str]) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.insert(name.into(), Box::new(handler));
+        self
+    }
+    fn run(&self, world: &mut World, line: &str) {
+        output(format!("> {line}"));
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return;
+        };
+        let args: Vec<_> = words.collect();
+        match self.handlers.get(name) {
+            Some(handler) => handler(world, &args),
+            None if name == "help" => {
+                let mut names: Vec<_> = self.handlers.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                output(format!("commands: help, {}", names.join(", ")));
+            }
+            None => output(format!("unknown command `{name}`, try `help`")),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ConsoleState {
+    toggle_key: KeyCode,
+    open: bool,
+    input: String,
+    submitted: Vec<String>,
+}
+
+fn console_input(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut state: ResMut<ConsoleState>,
+) {
+    let Some(keys) = keys else {
+        return;
+    };
+    if keys.just_pressed(state.toggle_key) {
+        state.open = !state.open;
+        // Do not type the toggle key character.
+        chars.clear();
+        return;
+    }
+    if !state.open {
+        chars.clear();
+        return;
+    }
+    for received in chars.read() {
+        let typed = received.char.chars().filter(|c| !c.is_control());
+        state.input.extend(typed);
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        state.input.pop();
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        let line = mem::take(&mut state.input);
+        state.submitted.push(line);
+    }
+    // Refreshed every frame, so it disappears shortly after closing.
+    let key = InvocationSiteKey::Named("console input".into());
+    let options = PrintOptions::default().no_prefix(true).sec(0.1);
+    command_channels().print(key, options, || format!("> {}_", state.input));
+}
+
+/// The `search` command, setting the [`OverlaySearch`] text.
+fn search(world: &mut World, args: &[&str]) {
+    if let Some(mut search) = world.get_resource_mut::<OverlaySearch>() {
+        search.text = args.join(" ");
+    }
+}
+
+fn run_console_commands(world: &mut World) {
+    let submitted = mem::take(&mut world.resource_mut::<ConsoleState>().submitted);
+    if submitted.is_empty() {
+        return;
+    }
+    world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+        for line in &submitted {
+            commands.run(world, line);
+        }
+    });
+}
+
+/// A console to type commands registered in [`ConsoleCommands`], displayed
+/// in the overlay.
+///
+/// The `search <text>` command only displays messages containing `text`, see
+/// [`OverlaySearch`], `search` without text displays all messages again.
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin).
+pub struct ConsolePlugin {
+    /// Key to press to open or close the console, by default it is
+    /// [`KeyCode::Backquote`].
+    pub toggle_key: KeyCode,
+}
+impl Default for ConsolePlugin {
+    fn default() -> Self {
+        Self { toggle_key: KeyCode::Backquote }
+    }
+}
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let state = ConsoleState {
+            toggle_key: self.toggle_key,
+            open: false,
+            input: String::new(),
+            submitted: Vec::new(),
+        };
+        app.init_resource::<ConsoleCommands>()
+            .insert_resource(state)
+            .add_systems(Update, (console_input, run_console_commands).chain());
+        let mut commands = app.world.resource_mut::<ConsoleCommands>();
+        commands.register("search", search);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource)]
+    struct Spawned(Vec<String>);
+
+    #[test]
+    fn test_run_passes_arguments() {
+        let mut world = World::new();
+        world.insert_resource(Spawned(Vec::new()));
+        let mut commands = ConsoleCommands::default();
+        commands.register("spawn", |world, args| {
+            let args = args.iter().map(|arg| arg.to_string());
+            world.resource_mut::<Spawned>().0.extend(args);
+        });
+        commands.run(&mut world, "  spawn  cube 3 ");
+        commands.run(&mut world, "despawn cube");
+        assert_eq!(world.resource::<Spawned>().0, ["cube", "3"]);
+    }
+}
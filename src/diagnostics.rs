@@ -0,0 +1,89 @@
+//! Built-in widgets printing bevy diagnostics, see [`OverlayPlugin::show_fps`]
+//! and [`OverlayPlugin::show_diagnostics`].
+//!
+//! [`OverlayPlugin::show_fps`]: crate::OverlayPlugin::show_fps
+//! [`OverlayPlugin::show_diagnostics`]: crate::OverlayPlugin::show_diagnostics
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+    FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+
+use crate::{screen_print, EcsStats};
+
+pub(crate) fn add_fps_diagnostics(app: &mut App) {
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+        app.add_plugins(FrameTimeDiagnosticsPlugin);
+    }
+    if !app.is_plugin_added::<EntityCountDiagnosticsPlugin>() {
+        app.add_plugins(EntityCountDiagnosticsPlugin);
+    }
+    app.add_systems(Update, show_fps);
+}
+
+/// The diagnostics selected with `OverlayPlugin::show_diagnostics`.
+#[derive(Resource)]
+struct ShownDiagnostics(Vec<DiagnosticPath>);
+
+pub(crate) fn add_diagnostics(app: &mut App, paths: Vec<DiagnosticPath>) {
+    app.insert_resource(ShownDiagnostics(paths))
+        .add_systems(Update, show_diagnostics);
+}
+
+fn show_diagnostics(diagnostics: Option<Res<DiagnosticsStore>>, shown: Res<ShownDiagnostics>) {
+    let Some(diagnostics) = diagnostics else {
+        return;
+    };
+    for path in &shown.0 {
+        let Some(diagnostic) = diagnostics.get(path) else {
+            continue;
+        };
+        let Some(value) = diagnostic.smoothed() else {
+            continue;
+        };
+        let suffix = &diagnostic.suffix;
+        let key = format!("diagnostic {path}");
+        screen_print!(sec: 0.5, col: Color::GREEN, key: key, "{path}: {value:.2}{suffix}");
+    }
+}
+
+/// The statistics selected with `OverlayPlugin::ecs_stats`.
+#[derive(Resource)]
+struct ShownEcsStats(EcsStats);
+
+pub(crate) fn add_ecs_stats(app: &mut App, stats: EcsStats) {
+    app.insert_resource(ShownEcsStats(stats))
+        .add_systems(Update, show_ecs_stats);
+}
+
+fn show_ecs_stats(world: &World) {
+    let entities = world.entities().len();
+    let archetypes = world.archetypes().len();
+    screen_print!(sec: 0.5, col: Color::GREEN, "entities: {entities}, archetypes: {archetypes}");
+    for (name, type_id) in &world.resource::<ShownEcsStats>().0.components {
+        let count: usize = match world.components().get_id(*type_id) {
+            Some(id) => world
+                .archetypes()
+                .iter()
+                .filter(|a| a.contains(id))
+                .map(|a| a.len())
+                .sum(),
+            None => 0,
+        };
+        let key = format!("ecs stats {name}");
+        screen_print!(sec: 0.5, col: Color::GREEN, key: key, "{name}: {count}");
+    }
+}
+
+fn show_fps(diagnostics: Res<DiagnosticsStore>) {
+    let smoothed = |path: &DiagnosticPath| diagnostics.get(path).and_then(Diagnostic::smoothed);
+    if let Some(fps) = smoothed(&FrameTimeDiagnosticsPlugin::FPS) {
+        screen_print!(sec: 0.5, col: Color::GREEN, "fps: {fps:.0}");
+    }
+    if let Some(frame_time) = smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME) {
+        screen_print!(sec: 0.5, col: Color::GREEN, "frame time: {frame_time:.2}ms");
+    }
+    if let Some(entities) = smoothed(&EntityCountDiagnosticsPlugin::ENTITY_COUNT) {
+        screen_print!(sec: 0.5, col: Color::GREEN, "entities: {entities:.0}");
+    }
+}
@@ -0,0 +1,121 @@
+//! Line-based diff of messages, see the `diff` option of
+//! [`crate::screen_print!`].
+use bevy::prelude::Color;
+
+use crate::markup::{self, Span};
+
+/// Beyond this many compared line pairs, texts are considered too different
+/// to be worth a diff.
+const MAX_COMPARISONS: usize = 1 << 20;
+
+/// A line of the new text, or a line of the old text that was removed.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Line<'a> {
+    Same(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// The lines of `new`, interleaved with the lines of `old` that are not in
+/// `new`, based on their longest common subsequence. `None` if the texts are
+/// too large to compare.
+pub(crate) fn lines<'a>(old: &'a str, new: &'a str) -> Option<Vec<Line<'a>>> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    if old.len().saturating_mul(new.len()) > MAX_COMPARISONS {
+        return None;
+    }
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..].
+    let mut common = vec![vec![0_u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut diff = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(Line::Same(new[j]));
+            (i, j) = (i + 1, j + 1);
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            diff.push(Line::Added(new[j]));
+            j += 1;
+        } else {
+            diff.push(Line::Removed(old[i]));
+            i += 1;
+        }
+    }
+    Some(diff)
+}
+
+/// Spans of the `new` message text, with the lines added since `old` in
+/// green and the removed lines in red. `None` if no line changed.
+pub(crate) fn spans(old: &str, new: &str) -> Option<Vec<Span>> {
+    let (old_plain, new_plain) = (markup::strip(old), markup::strip(new));
+    let diff = lines(&old_plain, &new_plain)?;
+    if diff.iter().all(|line| matches!(line, Line::Same(_))) {
+        return None;
+    }
+    let mut spans: Vec<Span> = Vec::new();
+    for line in diff {
+        let (line, color) = match line {
+            Line::Same(line) => (line, None),
+            Line::Added(line) => (line, Some(Color::GREEN)),
+            Line::Removed(line) => (line, Some(Color::RED)),
+        };
+        match spans.last_mut() {
+            Some((span, span_color)) if *span_color == color => span.push_str(line),
+            _ => spans.push((line.to_owned(), color)),
+        }
+        spans
+            .last_mut()
+            .expect("A line was just added")
+            .0
+            .push('\n');
+    }
+    if !new.ends_with('\n') {
+        spans.last_mut().expect("Some line changed").0.pop();
+    }
+    Some(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_changed_field() {
+        let old = "Player {\n    hp: 10,\n    speed: 2.0,\n}";
+        let new = "Player {\n    hp: 9,\n    speed: 2.0,\n}";
+        let expected = [
+            Line::Same("Player {"),
+            Line::Added("    hp: 9,"),
+            Line::Removed("    hp: 10,"),
+            Line::Same("    speed: 2.0,"),
+            Line::Same("}"),
+        ];
+        assert_eq!(lines(old, new).unwrap(), expected);
+    }
+    #[test]
+    fn test_diff_added_and_removed() {
+        let expected = [Line::Removed("a"), Line::Same("b"), Line::Added("c")];
+        assert_eq!(lines("a\nb", "b\nc").unwrap(), expected);
+        assert_eq!(lines("", "a").unwrap(), [Line::Added("a")]);
+    }
+    #[test]
+    fn test_diff_spans() {
+        let expected = [
+            ("a\n".to_owned(), None),
+            ("c\n".to_owned(), Some(Color::GREEN)),
+            ("b\n".to_owned(), Some(Color::RED)),
+        ];
+        assert_eq!(spans("a\nb\n", "a\n[red]c[/]\n").unwrap(), expected);
+        assert_eq!(spans("a\nb", "a\n[red]b[/]"), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Numeric series rendered as text, see [`crate::screen_graph!`],
+//! [`crate::screen_histogram!`] and the `stats` form of
+//! [`crate::screen_print!`].
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use bevy::utils::HashMap;
+
+use crate::InvocationSiteKey;
+
+/// How many samples are kept and displayed per graph.
+const GRAPH_LEN: usize = 32;
+
+/// How many samples are kept per histogram.
+const HISTOGRAM_LEN: usize = 256;
+
+/// How many samples the min, average and max of a series are computed over.
+const STATS_LEN: usize = 120;
+
+/// Characters for each height of the graph, from lowest to highest. The
+/// bevy default font only has ASCII characters.
+const LEVELS: &[char] = &['_', '.', '-', '~', '^', '\''];
+
+/// The last [`GRAPH_LEN`] values of a series.
+#[derive(Default)]
+pub(crate) struct Graph(VecDeque<f32>);
+impl Graph {
+    pub(crate) fn push(&mut self, value: f32) {
+        if self.0.len() == GRAPH_LEN {
+            self.0.pop_front();
+        }
+        self.0.push_back(value);
+    }
+    /// A sparkline of the series, scaled between its min and max values.
+    pub(crate) fn sparkline(&self) -> String {
+        let min = self.0.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.0.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let range = max - min;
+        let top = (LEVELS.len() - 1) as f32;
+        let mut line = String::with_capacity(self.0.len());
+        for value in &self.0 {
+            let height = if range > 0.0 {
+                (value - min) / range
+            } else {
+                0.5
+            };
+            let level = (height * top).round() as usize;
+            let _ = line.write_char(LEVELS[level.min(LEVELS.len() - 1)]);
+        }
+        line
+    }
+}
+
+/// The last [`HISTOGRAM_LEN`] values of a series.
+#[derive(Default)]
+pub(crate) struct Histogram(VecDeque<f32>);
+impl Histogram {
+    pub(crate) fn push(&mut self, value: f32) {
+        if self.0.len() == HISTOGRAM_LEN {
+            self.0.pop_front();
+        }
+        self.0.push_back(value);
+    }
+    /// How many values fall in each of `buckets` equal ranges between the
+    /// min and max values, drawn as a sparkline, followed by the min and max.
+    pub(crate) fn bars(&self, buckets: usize) -> (String, f32, f32) {
+        let min = self.0.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.0.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let range = max - min;
+        let buckets = buckets.max(1);
+        let mut counts = vec![0_usize; buckets];
+        for value in &self.0 {
+            let bucket = if range > 0.0 {
+                ((value - min) / range * buckets as f32) as usize
+            } else {
+                buckets / 2
+            };
+            counts[bucket.min(buckets - 1)] += 1;
+        }
+        let highest = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let top = (LEVELS.len() - 1) as f32;
+        let mut line = String::with_capacity(buckets);
+        for count in counts {
+            let level = (count as f32 / highest * top).round() as usize;
+            let _ = line.write_char(LEVELS[level]);
+        }
+        (line, min, max)
+    }
+}
+
+/// The last [`STATS_LEN`] values of a series.
+#[derive(Default)]
+pub(crate) struct Stats(VecDeque<f32>);
+impl Stats {
+    pub(crate) fn push(&mut self, value: f32) {
+        if self.0.len() == STATS_LEN {
+            self.0.pop_front();
+        }
+        self.0.push_back(value);
+    }
+    /// The min, average and max of the series.
+    pub(crate) fn summary(&self) -> (f32, f32, f32) {
+        let min = self.0.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.0.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let avg = self.0.iter().sum::<f32>() / self.0.len().max(1) as f32;
+        (min, avg, max)
+    }
+}
+
+/// How a series is displayed.
+#[derive(Clone, Copy)]
+pub(crate) enum Series {
+    /// A sparkline of the last values, see [`crate::screen_graph!`].
+    Graph,
+    /// A histogram with that many buckets, see [`crate::screen_histogram!`].
+    Histogram(u32),
+    /// The min, average and max of the last values, see the `stats` form of
+    /// [`crate::screen_print!`].
+    Stats,
+}
+
+/// Graphs, histograms and stats of each invocation site.
+#[derive(Default)]
+pub(crate) struct Samples {
+    graphs: HashMap<InvocationSiteKey, Graph>,
+    histograms: HashMap<InvocationSiteKey, Histogram>,
+    stats: HashMap<InvocationSiteKey, Stats>,
+}
+impl Samples {
+    /// Add `value` to the `series` of `key`, and return the text displaying
+    /// it.
+    pub(crate) fn push(
+        &mut self,
+        key: &InvocationSiteKey,
+        label: &str,
+        value: f32,
+        series: Series,
+    ) -> String {
+        match series {
+            Series::Graph => {
+                let graph = self.graphs.entry(key.clone()).or_default();
+                graph.push(value);
+                format!("{label} {} {value:.2}", graph.sparkline())
+            }
+            Series::Histogram(buckets) => {
+                let histogram = self.histograms.entry(key.clone()).or_default();
+                histogram.push(value);
+                let (bars, min, max) = histogram.bars(buckets as usize);
+                format!("{label} {min:.2} {bars} {max:.2}")
+            }
+            Series::Stats => {
+                let stats = self.stats.entry(key.clone()).or_default();
+                stats.push(value);
+                let (min, avg, max) = stats.summary();
+                format!("{label}: {value:.2} (min {min:.2}, avg {avg:.2}, max {max:.2})")
+            }
+        }
+    }
+    pub(crate) fn remove(&mut self, key: &InvocationSiteKey) {
+        self.graphs.remove(key);
+        self.histograms.remove(key);
+        self.stats.remove(key);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_summary() {
+        let mut stats = Stats::default();
+        for value in [4., 1., 7.] {
+            stats.push(value);
+        }
+        assert_eq!(stats.summary(), (1., 4., 7.));
+        for _ in 0..STATS_LEN {
+            stats.push(2.);
+        }
+        assert_eq!(stats.summary(), (2., 2., 2.));
+    }
+    #[test]
+    fn test_sparkline() {
+        let mut graph = Graph::default();
+        for value in [0., 1., 2., 3., 4., 5.] {
+            graph.push(value);
+        }
+        assert_eq!(graph.sparkline(), "_.-~^'");
+    }
+    #[test]
+    fn test_sparkline_flat() {
+        let mut graph = Graph::default();
+        graph.push(3.);
+        graph.push(3.);
+        assert_eq!(graph.sparkline(), "~~");
+    }
+    #[test]
+    fn test_graph_len() {
+        let mut graph = Graph::default();
+        for value in 0..GRAPH_LEN * 2 {
+            graph.push(value as f32);
+        }
+        assert_eq!(graph.sparkline().len(), GRAPH_LEN);
+        assert_eq!(graph.0.front(), Some(&(GRAPH_LEN as f32)));
+    }
+    #[test]
+    fn test_histogram_bars() {
+        let mut histogram = Histogram::default();
+        for value in [0., 1., 1., 1., 3., 4.] {
+            histogram.push(value);
+        }
+        assert_eq!(histogram.bars(4), ("-'_~".to_owned(), 0., 4.));
+        let mut flat = Histogram::default();
+        flat.push(2.);
+        assert_eq!(flat.bars(3), ("_'_".to_owned(), 2., 2.));
+    }
+}
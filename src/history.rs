@@ -0,0 +1,161 @@
+//! Record of messages displayed by the overlay, see [`OverlayHistory`].
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::{Color, Resource};
+
+use crate::InvocationSiteKey;
+
+/// A message recorded in the [`OverlayHistory`].
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    /// Game time in seconds when the message was displayed.
+    pub time: f64,
+    pub site: InvocationSiteKey,
+    pub text: String,
+    pub color: Color,
+}
+
+/// The most recent messages displayed by the overlay.
+///
+/// A message is recorded when it first shows up on screen and each time its
+/// text changes, `push` messages each time they are printed. Only the last
+/// `capacity` messages are kept, see `OverlayPlugin::history`.
+#[derive(Resource, Clone, Debug)]
+pub struct OverlayHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    /// Where to write the history when pressing
+    /// `OverlayPlugin::history_dump_key`, by default it is
+    /// `overlay_history.txt`.
+    pub path: PathBuf,
+}
+impl OverlayHistory {
+    /// An empty history keeping at most `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            path: PathBuf::from("overlay_history.txt"),
+        }
+    }
+    /// Recorded messages, oldest first.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+    /// Maximum number of recorded messages.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    #[cfg_attr(not(feature = "debug"), allow(dead_code))]
+    pub(crate) fn record(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+    /// Write the history to `writer`, one message per line.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        for HistoryEntry { time, site, text, .. } in &self.entries {
+            writeln!(writer, "[{time:.3}s] {site} {}", text.trim_end())?;
+        }
+        writer.flush()
+    }
+    /// Write the history to the file at `path`, replacing it if it exists.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Messages received by the overlay when `OverlayPlugin::headless` is set,
+/// rather than displayed.
+///
+/// Messages are kept in the order they are received, until [`Self::clear`]
+/// is called. Like on screen, a message replaces the previous one of its
+/// invocation site, unless it was printed with `push`. Only the last
+/// [`Self::CAPACITY`] messages are kept. This lets tests assert on debug
+/// output without a window.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlayMessageBuffer {
+    entries: VecDeque<HistoryEntry>,
+}
+impl OverlayMessageBuffer {
+    /// Maximum number of kept messages.
+    pub const CAPACITY: usize = 4096;
+
+    /// Received messages, oldest first.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+    /// Whether a received message contains `text`.
+    pub fn contains(&self, text: &str) -> bool {
+        self.entries.iter().any(|entry| entry.text.contains(text))
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    /// Add `entry`, removing the previous message of its site if `replace`.
+    #[cfg_attr(not(feature = "debug"), allow(dead_code))]
+    pub(crate) fn push(&mut self, entry: HistoryEntry, replace: bool) {
+        if replace {
+            self.entries.retain(|previous| previous.site != entry.site);
+        }
+        if self.entries.len() == Self::CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: f64, text: &str) -> HistoryEntry {
+        let site = InvocationSiteKey::Site { file: "src/main.rs", line: 3, column: 5 };
+        HistoryEntry {
+            time,
+            site,
+            text: text.to_owned(),
+            color: Color::WHITE,
+        }
+    }
+    #[test]
+    fn test_record_bounded() {
+        let mut history = OverlayHistory::new(2);
+        history.record(entry(1.0, "a"));
+        history.record(entry(2.0, "b"));
+        history.record(entry(3.0, "c"));
+        let texts: Vec<_> = history.entries().map(|entry| &entry.text[..]).collect();
+        assert_eq!(texts, ["b", "c"]);
+    }
+    #[test]
+    fn test_buffer_replaces_site() {
+        let mut buffer = OverlayMessageBuffer::default();
+        buffer.push(entry(1.0, "a"), true);
+        buffer.push(entry(2.0, "b"), false);
+        buffer.push(entry(3.0, "c"), false);
+        let texts: Vec<_> = buffer.entries().map(|entry| &entry.text[..]).collect();
+        assert_eq!(texts, ["a", "b", "c"]);
+        buffer.push(entry(4.0, "d"), true);
+        let texts: Vec<_> = buffer.entries().map(|entry| &entry.text[..]).collect();
+        assert_eq!(texts, ["d"]);
+    }
+    #[test]
+    fn test_write() {
+        let mut history = OverlayHistory::new(4);
+        history.record(entry(1.5, "hello\n"));
+        let mut output = Vec::new();
+        history.write(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "[1.500s] [src/main.rs:3:5] hello\n");
+    }
+}
@@ -0,0 +1,148 @@
+//! An egui window listing the overlay messages, see
+//! [`OverlayInspectorPlugin`].
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::overlay::ActiveMessages;
+use crate::{DebugOverlaySites, Level, OverlayFilter, OverlayHistory};
+
+const LEVELS: [Level; 5] = [
+    Level::Trace,
+    Level::Debug,
+    Level::Info,
+    Level::Warn,
+    Level::Error,
+];
+
+#[derive(Resource)]
+struct InspectorState {
+    toggle_key: KeyCode,
+    open: bool,
+    search: String,
+}
+
+fn toggle_inspector(keys: Option<Res<ButtonInput<KeyCode>>>, mut state: ResMut<InspectorState>) {
+    if keys.is_some_and(|keys| keys.just_pressed(state.toggle_key)) {
+        state.open = !state.open;
+    }
+}
+
+fn egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, a] = color.as_rgba_u8();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// A line with a copy button, it is not displayed if it doesn't contain
+/// `search`.
+fn message_line(ui: &mut egui::Ui, search: &str, text: &str, color: Color) {
+    if !text.contains(search) {
+        return;
+    }
+    ui.horizontal(|ui| {
+        if ui.small_button("copy").clicked() {
+            ui.output_mut(|output| output.copied_text = text.to_owned());
+        }
+        ui.label(egui::RichText::new(text.trim_end()).color(egui_color(color)));
+    });
+}
+
+fn inspector_window(
+    mut contexts: EguiContexts,
+    mut state: ResMut<InspectorState>,
+    active: ActiveMessages,
+    history: Option<Res<OverlayHistory>>,
+    mut sites: ResMut<DebugOverlaySites>,
+    mut filter: ResMut<OverlayFilter>,
+) {
+    if !state.open {
+        return;
+    }
+    let InspectorState { open, search, .. } = &mut *state;
+    // Resources are only modified when the user changes them, since the
+    // overlay resends all messages when they change.
+    let mut min_level = filter.min_level;
+    let mut toggled = Vec::new();
+    let window = egui::Window::new("Debug overlay").open(open);
+    window.show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("search");
+            ui.text_edit_singleline(search);
+        });
+        egui::ComboBox::from_label("minimum level")
+            .selected_text(format!("{min_level:?}"))
+            .show_ui(ui, |ui| {
+                for level in LEVELS {
+                    ui.selectable_value(&mut min_level, level, format!("{level:?}"));
+                }
+            });
+        ui.collapsing("Active", |ui| {
+            let scroll = egui::ScrollArea::vertical().id_source("active");
+            scroll.max_height(300.0).show(ui, |ui| {
+                for (site, text, color) in active.collect() {
+                    message_line(ui, search, &format!("{site} {text}"), color);
+                }
+            });
+        });
+        if let Some(history) = &history {
+            ui.collapsing("History", |ui| {
+                let scroll = egui::ScrollArea::vertical().id_source("history");
+                scroll.max_height(300.0).show(ui, |ui| {
+                    for entry in history.entries() {
+                        let text = format!("{:.2} {} {}", entry.time, entry.site, entry.text);
+                        message_line(ui, search, &text, entry.color);
+                    }
+                });
+            });
+        }
+        ui.collapsing("Sites", |ui| {
+            let scroll = egui::ScrollArea::vertical().id_source("sites");
+            scroll.max_height(300.0).show(ui, |ui| {
+                for (site, enabled) in sites.iter() {
+                    let mut enabled = enabled;
+                    if ui.checkbox(&mut enabled, site.to_string()).changed() {
+                        toggled.push(site.clone());
+                    }
+                }
+            });
+        });
+    });
+    if min_level != filter.min_level {
+        filter.min_level = min_level;
+    }
+    for site in toggled {
+        sites.toggle(site);
+    }
+}
+
+/// An egui window listing the active messages and the [`OverlayHistory`],
+/// with a search field, copy buttons, a [`Level`] filter, and checkboxes to
+/// mute the sites of [`DebugOverlaySites`].
+///
+/// This is an alternative frontend, the overlay is still displayed. It
+/// requires the [`OverlayPlugin`](crate::OverlayPlugin), and adds the
+/// [`EguiPlugin`] if it isn't already added. The history is only listed when
+/// [`OverlayPlugin::history`](crate::OverlayPlugin::history) is set.
+pub struct OverlayInspectorPlugin {
+    /// Key to press to open or close the window, by default it is
+    /// [`KeyCode::F1`].
+    pub toggle_key: KeyCode,
+}
+impl Default for OverlayInspectorPlugin {
+    fn default() -> Self {
+        Self { toggle_key: KeyCode::F1 }
+    }
+}
+impl Plugin for OverlayInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        let state = InspectorState {
+            toggle_key: self.toggle_key,
+            open: false,
+            search: String::new(),
+        };
+        app.insert_resource(state)
+            .add_systems(Update, (toggle_inspector, inspector_window).chain());
+    }
+}
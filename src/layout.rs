@@ -0,0 +1,172 @@
+//! The built-in [`Layout`]s, see [`OverlayLayout`].
+use bevy::prelude::{Entity, Vec2};
+use bevy::utils::HashMap;
+
+use crate::block::Columns;
+use crate::{Layout, LayoutArea, OverlayLayout};
+
+impl Layout {
+    /// The layout of a window or viewport with `area` available.
+    pub(crate) fn new_layout(&self, area: LayoutArea) -> Box<dyn OverlayLayout> {
+        match self {
+            Layout::Stack => Box::new(StackLayout::new(area)),
+            Layout::Grid(cell) => Box::new(GridLayout::new(*cell, area)),
+            Layout::Custom(new_layout) => new_layout(area),
+        }
+    }
+}
+
+/// See [`Layout::Stack`].
+struct StackLayout {
+    columns: Columns<Entity, f32>,
+    column_width: f32,
+    /// Column and width of each placed message.
+    widths: HashMap<Entity, (usize, f32)>,
+}
+impl StackLayout {
+    fn new(area: LayoutArea) -> Self {
+        // Columns only wrap when they have a width.
+        let max_height = area.column_width.and(area.size.map(|size| size.y));
+        Self {
+            columns: Columns::new(max_height).with_spacing(area.line_spacing),
+            column_width: area.column_width.unwrap_or(0.0),
+            widths: HashMap::new(),
+        }
+    }
+}
+impl OverlayLayout for StackLayout {
+    fn insert(&mut self, entity: Entity, size: Vec2) -> Vec2 {
+        let (column, offset) = self.columns.insert_size(entity, size.y);
+        self.widths.insert(entity, (column, size.x));
+        Vec2::new(column as f32 * self.column_width, offset)
+    }
+    fn remove(&mut self, entity: Entity) {
+        self.columns.remove(entity);
+        self.widths.remove(&entity);
+    }
+    fn size_of(&self, entity: Entity) -> Option<Vec2> {
+        let height = self.columns.size_of(entity)?;
+        let (_, width) = self.widths.get(&entity)?;
+        Some(Vec2::new(*width, height))
+    }
+    fn size(&self) -> Vec2 {
+        let ends = self.widths.values();
+        let ends = ends.map(|(column, width)| *column as f32 * self.column_width + width);
+        Vec2::new(ends.fold(0.0, f32::max), self.columns.total_size())
+    }
+    fn resize(&mut self, entity: Entity, size: Vec2) -> Option<Vec2> {
+        // Messages don't move when only their width changes.
+        if self.columns.size_of(entity) == Some(size.y) {
+            if let Some((_, width)) = self.widths.get_mut(&entity) {
+                *width = size.x;
+            }
+            return None;
+        }
+        self.remove(entity);
+        Some(self.insert(entity, size))
+    }
+}
+
+/// See [`Layout::Grid`].
+struct GridLayout {
+    cell: Vec2,
+    /// Cells per row.
+    row_len: usize,
+    /// Message in each cell, without trailing free cells.
+    cells: Vec<Option<Entity>>,
+    sizes: HashMap<Entity, Vec2>,
+}
+impl GridLayout {
+    fn new(cell: Vec2, area: LayoutArea) -> Self {
+        let row_len = match area.size {
+            Some(size) if cell.x > 0.0 => (size.x / cell.x) as usize,
+            _ => 1,
+        };
+        Self {
+            cell,
+            row_len: row_len.max(1),
+            cells: Vec::new(),
+            sizes: HashMap::new(),
+        }
+    }
+    fn position(&self, index: usize) -> Vec2 {
+        let (column, row) = (index % self.row_len, index / self.row_len);
+        Vec2::new(column as f32, row as f32) * self.cell
+    }
+}
+impl OverlayLayout for GridLayout {
+    fn insert(&mut self, entity: Entity, size: Vec2) -> Vec2 {
+        let index = self.cells.iter().position(Option::is_none);
+        let index = index.unwrap_or_else(|| {
+            self.cells.push(None);
+            self.cells.len() - 1
+        });
+        self.cells[index] = Some(entity);
+        self.sizes.insert(entity, size);
+        self.position(index)
+    }
+    fn remove(&mut self, entity: Entity) {
+        if let Some(cell) = self.cells.iter_mut().find(|cell| **cell == Some(entity)) {
+            *cell = None;
+        }
+        while matches!(self.cells.last(), Some(None)) {
+            self.cells.pop();
+        }
+        self.sizes.remove(&entity);
+    }
+    fn size_of(&self, entity: Entity) -> Option<Vec2> {
+        self.sizes.get(&entity).copied()
+    }
+    fn size(&self) -> Vec2 {
+        let placed = self.cells.iter().enumerate();
+        let placed = placed.filter_map(|(index, cell)| Some((index, self.sizes.get(&(*cell)?)?)));
+        let ends = placed.map(|(index, size)| self.position(index) + *size);
+        ends.fold(Vec2::ZERO, Vec2::max)
+    }
+    fn resize(&mut self, entity: Entity, size: Vec2) -> Option<Vec2> {
+        // Messages keep their cell whatever their size.
+        self.sizes.insert(entity, size);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(size: Vec2, column_width: Option<f32>) -> LayoutArea {
+        LayoutArea { size: Some(size), column_width, line_spacing: 0. }
+    }
+    #[test]
+    fn test_stack_keeps_place_when_width_changes() {
+        let mut stack = StackLayout::new(area(Vec2::splat(100.), None));
+        let [a, b] = [Entity::from_raw(0), Entity::from_raw(1)];
+        assert_eq!(stack.insert(a, Vec2::new(10., 5.)), Vec2::ZERO);
+        assert_eq!(stack.insert(b, Vec2::new(20., 5.)), Vec2::new(0., 5.));
+        assert_eq!(stack.resize(a, Vec2::new(30., 5.)), None);
+        assert_eq!(stack.size(), Vec2::new(30., 10.));
+        assert_eq!(
+            stack.resize(a, Vec2::new(30., 8.)),
+            Some(Vec2::new(0., 10.))
+        );
+    }
+    #[test]
+    fn test_stack_wraps_into_columns() {
+        let mut stack = StackLayout::new(area(Vec2::splat(10.), Some(50.)));
+        let [a, b] = [Entity::from_raw(0), Entity::from_raw(1)];
+        stack.insert(a, Vec2::new(10., 8.));
+        assert_eq!(stack.insert(b, Vec2::new(20., 8.)), Vec2::new(50., 0.));
+        assert_eq!(stack.size(), Vec2::new(70., 8.));
+    }
+    #[test]
+    fn test_grid_fills_rows() {
+        let mut grid = GridLayout::new(Vec2::new(40., 10.), area(Vec2::new(100., 100.), None));
+        let [a, b, c] = [0, 1, 2].map(Entity::from_raw);
+        assert_eq!(grid.insert(a, Vec2::new(30., 10.)), Vec2::ZERO);
+        assert_eq!(grid.insert(b, Vec2::new(30., 10.)), Vec2::new(40., 0.));
+        assert_eq!(grid.insert(c, Vec2::new(30., 10.)), Vec2::new(0., 10.));
+        grid.remove(b);
+        assert_eq!(grid.size(), Vec2::new(30., 20.));
+        assert_eq!(grid.insert(b, Vec2::new(30., 10.)), Vec2::new(40., 0.));
+    }
+}
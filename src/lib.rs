@@ -1,22 +1,940 @@
 #![doc = include_str!("../Readme.md")]
 
-use bevy::prelude::Resource;
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-#[cfg(feature = "debug")]
+use bevy::prelude::{Color, Component, Entity, Event, GamepadButtonType, Resource, Vec2};
+
+#[cfg(all(feature = "debug", feature = "assets"))]
+mod assets;
+#[cfg(all(feature = "debug", feature = "audio"))]
+mod audio;
 mod block;
+#[cfg(all(feature = "debug", feature = "clipboard"))]
+mod clipboard;
+#[cfg(all(feature = "debug", feature = "console"))]
+mod console;
+#[cfg(feature = "debug")]
+mod diagnostics;
+#[cfg(feature = "debug")]
+mod diff;
+#[cfg(feature = "debug")]
+mod graph;
+mod history;
+#[cfg(feature = "egui")]
+mod inspector;
+#[cfg(feature = "debug")]
+mod layout;
+#[cfg(all(feature = "debug", feature = "log"))]
+mod logger;
+#[cfg(feature = "debug")]
+mod markup;
 #[cfg(feature = "debug")]
 mod overlay;
+#[cfg(all(feature = "debug", feature = "remote"))]
+mod remote;
+#[cfg(feature = "debug")]
+mod replay;
+mod snapshot;
+#[cfg(feature = "debug")]
+mod state;
+#[cfg(feature = "debug")]
+#[doc(hidden)]
+pub mod testing;
+#[cfg(all(feature = "debug", feature = "timings"))]
+mod timings;
+#[cfg(all(feature = "debug", feature = "tracing"))]
+mod tracing_layer;
+#[cfg(feature = "debug")]
+pub use overlay::{
+    command_channels, CommandChannels, Counter, Icon, OverlayCommands, OverlayConfig,
+    OverlayPlugin, OverlaySender, OverlaySet, PrintCache, PrintOptions, ScreenGroup, ScreenTimer,
+    TimerStats, WorldPosition,
+};
+#[cfg(feature = "debug")]
+pub use replay::{MessageRecord, OverlayRecording, OverlayReplay, RecordedChange};
 #[cfg(feature = "debug")]
-pub use overlay::{command_channels, CommandChannels, InvocationSiteKey, OverlayPlugin};
+pub use state::ClearOnStateChange;
 
+pub use block::{Blocks, Columns, Summable};
+pub use history::{HistoryEntry, OverlayHistory, OverlayMessageBuffer};
+pub use snapshot::{OverlaySnapshot, SnapshotLine};
 #[cfg(not(feature = "debug"))]
 mod mocks;
+#[cfg(all(feature = "debug", feature = "assets"))]
+pub use assets::OverlayAssetsPlugin;
+#[cfg(all(feature = "debug", feature = "console"))]
+pub use console::{ConsoleCommands, ConsolePlugin};
+#[cfg(feature = "egui")]
+pub use inspector::OverlayInspectorPlugin;
+#[cfg(all(feature = "debug", feature = "log"))]
+pub use logger::OverlayLogger;
+#[cfg(all(feature = "debug", feature = "remote"))]
+pub use remote::{OverlayRemotePlugin, OverlayRemoteSink};
+#[cfg(all(feature = "debug", feature = "timings"))]
+pub use timings::{timing_subscriber, SystemTimingLayer, SystemTimingsPlugin};
+#[cfg(all(feature = "debug", feature = "tracing"))]
+pub use tracing_layer::{overlay_subscriber, OverlayLayer};
+
+#[cfg(all(not(feature = "debug"), feature = "tracing"))]
+pub use mocks::overlay_subscriber;
+#[cfg(all(not(feature = "debug"), feature = "assets"))]
+pub use mocks::OverlayAssetsPlugin;
+#[cfg(all(not(feature = "debug"), feature = "log"))]
+pub use mocks::OverlayLogger;
+#[cfg(all(not(feature = "debug"), feature = "timings"))]
+pub use mocks::{timing_subscriber, SystemTimingsPlugin};
 #[cfg(not(feature = "debug"))]
-pub use mocks::OverlayPlugin;
+pub use mocks::{
+    ClearOnStateChange, MessageRecord, OverlayCommands, OverlayConfig, OverlayPlugin,
+    OverlayRecording, OverlayReplay, OverlaySender, OverlaySet, RecordedChange, ScreenTimer,
+};
+#[cfg(all(not(feature = "debug"), feature = "console"))]
+pub use mocks::{ConsoleCommands, ConsolePlugin};
+#[cfg(all(not(feature = "debug"), feature = "remote"))]
+pub use mocks::{OverlayRemotePlugin, OverlayRemoteSink};
 
 /// Control position on screen of the debug overlay.
+///
+/// This can be changed at runtime by modifying the resource.
 #[derive(Resource, Default)]
 pub struct DebugOverlayLocation {
-    pub margin_vertical: f32,
-    pub margin_horizontal: f32,
+    /// Space between the top and bottom edges of the window and the overlay.
+    pub margin_vertical: Margin,
+    /// Space between the left and right edges of the window and the overlay.
+    pub margin_horizontal: Margin,
+    /// Where on screen to display messages, new messages are added further
+    /// away from the anchor.
+    pub anchor: Anchor,
+    /// Horizontal alignment of messages, overridden by the `align` option
+    /// of [`screen_print!`].
+    pub alignment: Alignment,
+}
+
+/// Marks a camera rendering to a viewport, for example one player's half of
+/// the screen in split screen, to display messages printed with the
+/// `target: <name>` option of [`screen_print!`] in that viewport.
+///
+/// Each viewport stacks its messages separately, at the
+/// [`DebugOverlayLocation`] relative to the viewport. Messages printed from
+/// the same place with different targets are displayed in each viewport.
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{screen_print, OverlayViewport};
+///
+/// #[derive(Component)]
+/// struct Player { viewport: &'static str, health: u32 }
+///
+/// fn spawn_camera(mut cmds: Commands) {
+///     cmds.spawn((Camera2dBundle::default(), OverlayViewport("player 1".into())));
+/// }
+/// fn show_health(players: Query<&Player>) {
+///     for player in &players {
+///         screen_print!(target: player.viewport, "health: {}", player.health);
+///     }
+/// }
+/// ```
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct OverlayViewport(pub Cow<'static, str>);
+
+/// Space between the edges of the window and the overlay, see
+/// [`DebugOverlayLocation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Margin {
+    /// Size in logical pixels.
+    Px(f32),
+    /// Percentage of the window size, its width for horizontal margins and
+    /// its height for vertical margins.
+    Percent(f32),
+}
+impl Default for Margin {
+    fn default() -> Self {
+        Margin::Px(0.0)
+    }
+}
+
+/// Horizontal alignment of messages relative to each other, see
+/// [`DebugOverlayLocation::alignment`].
+///
+/// Messages are aligned within the widest message, or within
+/// [`OverlayPlugin::column_width`] when set. Ignored in
+/// [`OverlayPlugin::single_text`] mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Alignment {
+    /// Messages touch the side of the [`Anchor`], lines of multiline
+    /// messages are aligned left.
+    #[default]
+    Anchor,
+    Left,
+    Center,
+    /// Useful for numeric dashboards at right anchors.
+    Right,
+}
+
+/// Screen location of the debug overlay, see [`DebugOverlayLocation`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    /// Messages are stacked downward from the top left corner.
+    #[default]
+    TopLeft,
+    /// Messages are stacked downward from the top right corner.
+    TopRight,
+    /// Messages are stacked upward from the bottom left corner.
+    BottomLeft,
+    /// Messages are stacked upward from the bottom right corner.
+    BottomRight,
+    /// Messages are stacked downward from the middle of the left edge.
+    CenterLeft,
+    /// Messages are stacked downward from the middle of the right edge.
+    CenterRight,
+}
+
+/// What to do when more than 4096 messages are printed in a single frame.
+///
+/// This can be changed at runtime by modifying the resource.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the messages that do not fit and log an error counting them,
+    /// once per frame.
+    #[default]
+    DropNewest,
+    /// Discard the oldest queued messages to make room for new ones and log
+    /// an error counting them, once per frame.
+    DropOldest,
+    /// Discard the messages that do not fit, and display a single
+    /// "N messages dropped" line instead.
+    Collapse,
+}
+
+/// Order of messages on screen, see `OverlayPlugin::ordering`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageOrdering {
+    /// Messages take the first free spot, they stay in place until they
+    /// expire, but related messages may end up far apart.
+    #[default]
+    Stable,
+    /// Messages are sorted by invocation site, then by when they showed up.
+    CallSite,
+    /// Messages are sorted by when they showed up, oldest first.
+    Chronological,
+    /// Messages are sorted by their displayed text, including the prefix.
+    Alphabetical,
+}
+
+/// How messages are placed on screen, see `OverlayPlugin::layout`.
+///
+/// Each window and viewport has its own layout. Ignored in
+/// `OverlayPlugin::single_text` mode.
+#[derive(Clone, Default)]
+pub enum Layout {
+    /// Messages are stacked away from the [`Anchor`], each taking the first
+    /// gap left by expired messages it fits in. They wrap into additional
+    /// columns when `OverlayPlugin::column_width` is set.
+    #[default]
+    Stack,
+    /// Messages are placed in a grid of cells of this size, filled row by
+    /// row away from the [`Anchor`], each taking the first free cell.
+    /// Messages larger than a cell overlap the next ones.
+    Grid(Vec2),
+    /// Use the provided function to create the layout of each window and
+    /// viewport, it is called again when their [`LayoutArea`] changes.
+    Custom(Arc<dyn Fn(LayoutArea) -> Box<dyn OverlayLayout> + Send + Sync>),
+}
+
+/// Space available to the messages of a window or viewport, see
+/// [`OverlayLayout`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutArea {
+    /// Space from the [`Anchor`] corner to the opposite edges, inside the
+    /// margins of the [`DebugOverlayLocation`] and the background padding.
+    /// `None` while the size of the window is unknown.
+    pub size: Option<Vec2>,
+    /// `OverlayPlugin::column_width`.
+    pub column_width: Option<f32>,
+    /// `OverlayPlugin::line_spacing`.
+    pub line_spacing: f32,
+}
+
+/// Places the messages of a window or viewport, see [`Layout::Custom`].
+///
+/// Positions are relative to the [`Anchor`] corner, inside the background
+/// padding: `x` increases away from the side of the anchor, `y` away from
+/// its edge. Messages only move when [`Self::insert`] or [`Self::resize`]
+/// return a new position. With an `OverlayPlugin::ordering` other than
+/// [`MessageOrdering::Stable`], all messages are removed and inserted again in
+/// order when one of them moves.
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{Layout, OverlayLayout, OverlayPlugin};
+///
+/// /// Messages side by side, in the order they show up.
+/// #[derive(Default)]
+/// struct Row(Vec<(Entity, f32, Vec2)>);
+/// impl OverlayLayout for Row {
+///     fn insert(&mut self, entity: Entity, size: Vec2) -> Vec2 {
+///         let x = self.size().x;
+///         self.0.push((entity, x, size));
+///         Vec2::new(x, 0.0)
+///     }
+///     fn remove(&mut self, entity: Entity) {
+///         self.0.retain(|(placed, ..)| *placed != entity);
+///     }
+///     fn size_of(&self, entity: Entity) -> Option<Vec2> {
+///         let mut placed = self.0.iter();
+///         placed.find(|(placed, ..)| *placed == entity).map(|(.., size)| *size)
+///     }
+///     fn size(&self) -> Vec2 {
+///         let ends = self.0.iter().map(|(_, x, size)| Vec2::new(x + size.x, size.y));
+///         ends.fold(Vec2::ZERO, Vec2::max)
+///     }
+/// }
+///
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     OverlayPlugin {
+///         layout: Layout::Custom(Arc::new(|_| Box::<Row>::default())),
+///         ..default()
+///     },
+/// ));
+/// ```
+pub trait OverlayLayout: Send + Sync + 'static {
+    /// Place the message `entity` of `size`, returning its position.
+    fn insert(&mut self, entity: Entity, size: Vec2) -> Vec2;
+    /// Free the space taken by `entity`, if it is placed.
+    fn remove(&mut self, entity: Entity);
+    /// The size `entity` was placed with, `None` if it isn't placed.
+    fn size_of(&self, entity: Entity) -> Option<Vec2>;
+    /// Size of the area covered by the placed messages, from the anchor
+    /// corner. The background panel covers this area.
+    fn size(&self) -> Vec2;
+    /// `entity` changed `size`, return its new position if it moved. By
+    /// default it is removed and inserted again.
+    fn resize(&mut self, entity: Entity, size: Vec2) -> Option<Vec2> {
+        self.remove(entity);
+        Some(self.insert(entity, size))
+    }
+}
+
+/// Severity of a [`screen_print!`] message, see [`OverlayFilter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// A message of the overlay, on the entity displaying it, so that systems can
+/// query and react to what the overlay displays.
+///
+/// Times are in seconds of the overlay clock, which counts in
+/// `OverlayPlugin::time_source` and stops while the overlay is paused. The
+/// entities of expired messages are kept to be displayed again, with
+/// [`Self::expired`] set. There are no message entities in
+/// `OverlayPlugin::headless` mode.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{Level, OverlayMessage};
+///
+/// fn report_errors(messages: Query<&OverlayMessage, Changed<OverlayMessage>>) {
+///     for message in &messages {
+///         if message.level == Level::Error && !message.expired {
+///             warn!("overlay error at {:?}: {}", message.site, message.text);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct OverlayMessage {
+    /// The message text as displayed, with its prefix, without color markup.
+    pub text: String,
+    pub site: InvocationSiteKey,
+    pub level: Level,
+    /// The `cat` option of [`screen_print!`].
+    pub category: Option<Cow<'static, str>>,
+    /// When the message showed up, after being expired if it was.
+    pub created: f64,
+    /// When the message starts fading out, unless it is pinned.
+    pub expiration: f64,
+    /// Whether the message faded out, it is then hidden.
+    pub expired: bool,
+}
+
+/// Sent when a message shows up, including `push` messages and messages
+/// displayed again after they expired, see [`OverlayMessage`].
+///
+/// It is not sent in `OverlayPlugin::headless` mode.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{Level, OverlayMessageShown};
+///
+/// fn log_errors(mut shown: EventReader<OverlayMessageShown>) {
+///     for message in shown.read().filter(|message| message.level == Level::Error) {
+///         error!("on screen: {}", message.text);
+///     }
+/// }
+/// ```
+#[derive(Event, Clone, Debug)]
+pub struct OverlayMessageShown {
+    /// The entity displaying the message.
+    pub entity: Entity,
+    pub site: InvocationSiteKey,
+    /// The message text as displayed, see [`OverlayMessage::text`].
+    pub text: String,
+    pub level: Level,
+}
+
+/// Sent when a message completely faded out, see [`OverlayMessage`].
+///
+/// It is not sent for pinned messages, nor for the messages removed with
+/// `screen_clear!` before they expired, nor in `OverlayPlugin::headless`
+/// mode.
+#[derive(Event, Clone, Debug)]
+pub struct OverlayMessageExpired {
+    /// The entity that displayed the message.
+    pub entity: Entity,
+    pub site: InvocationSiteKey,
+    /// The message text as displayed, see [`OverlayMessage::text`].
+    pub text: String,
+    pub level: Level,
+}
+
+/// Runtime filter for messages displayed by the overlay.
+///
+/// Filtered out messages are dropped as soon as they are printed, without
+/// formatting their text, unless an `OverlayRecording` is in progress. It can
+/// be set with the `OverlayPlugin::env_var` environment variable.
+#[derive(Resource, Clone, Debug)]
+pub struct OverlayFilter {
+    /// Messages with a [`Level`] lower than this are not displayed, by default
+    /// all messages are displayed.
+    pub min_level: Level,
+    /// Minimum [`Level`] of the messages of given categories, overriding
+    /// [`Self::min_level`], see the `cat:` option of [`screen_print!`].
+    pub categories: BTreeMap<Cow<'static, str>, Level>,
+}
+impl Default for OverlayFilter {
+    fn default() -> Self {
+        Self {
+            min_level: Level::Trace,
+            categories: BTreeMap::new(),
+        }
+    }
+}
+impl OverlayFilter {
+    /// Whether messages of `level` in the `cat` category are displayed.
+    pub fn allows(&self, level: Level, cat: Option<&str>) -> bool {
+        let category_level = cat.and_then(|cat| self.categories.get(cat));
+        level >= *category_level.unwrap_or(&self.min_level)
+    }
+}
+
+/// Runtime search of the messages displayed by the overlay.
+///
+/// While `text` is not empty, only the messages containing it, ignoring case,
+/// are displayed. The other ones are hidden like messages scrolled out of
+/// view, they show up again when the search changes. With the `console`
+/// feature, it is set by the `search` console command.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlaySearch {
+    pub text: String,
+}
+impl OverlaySearch {
+    /// Whether a message with the given `text` is displayed.
+    pub fn matches(&self, text: &str) -> bool {
+        self.text.is_empty() || text.to_lowercase().contains(&self.text.to_lowercase())
+    }
+}
+
+/// Runtime toggles for categories of messages, see the `cat:` option of
+/// [`screen_print!`].
+///
+/// Messages of disabled categories are dropped as soon as they are printed,
+/// like messages filtered out by [`OverlayFilter`]. Messages without category
+/// are always displayed.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DebugOverlayCategories {
+    disabled: HashSet<Cow<'static, str>>,
+    /// Index in `hotkeys` of the only category displayed by [`Self::cycle`].
+    solo: Option<usize>,
+    /// Categories toggled by the `1` to `9` digit keys, in order. By default
+    /// no keys are bound.
+    pub hotkeys: Vec<Cow<'static, str>>,
+}
+impl DebugOverlayCategories {
+    /// Whether messages of `category` are displayed.
+    pub fn is_enabled(&self, category: &str) -> bool {
+        !self.disabled.contains(category)
+    }
+    /// Display messages of `category`.
+    pub fn enable(&mut self, category: &str) {
+        self.disabled.remove(category);
+    }
+    /// Do not display messages of `category`.
+    pub fn disable(&mut self, category: impl Into<Cow<'static, str>>) {
+        self.disabled.insert(category.into());
+    }
+    /// Enable `category` if it is disabled, disable it otherwise.
+    pub fn toggle(&mut self, category: impl Into<Cow<'static, str>>) {
+        let category = category.into();
+        if !self.disabled.remove(&category) {
+            self.disabled.insert(category);
+        }
+    }
+    /// Display only the next category of [`Self::hotkeys`], or all of them
+    /// after the last one.
+    pub fn cycle(&mut self) {
+        let next = self.solo.map_or(0, |solo| solo + 1);
+        self.solo = (next < self.hotkeys.len()).then_some(next);
+        for (i, category) in self.hotkeys.iter().enumerate() {
+            if self.solo.map_or(true, |solo| solo == i) {
+                self.disabled.remove(category);
+            } else {
+                self.disabled.insert(category.clone());
+            }
+        }
+    }
+}
+
+/// Colors of the messages printed without a `col` option, by [`Level`] and
+/// category, see the `level:` and `cat:` options of [`screen_print!`].
+///
+/// The color of a category takes precedence over the color of a level,
+/// messages with neither use the `OverlayConfig::fallback_color`. By default
+/// no colors are set, presets are provided for common backgrounds and needs.
+/// Changes only apply to the messages printed afterward.
+///
+/// ```rust
+/// use bevy::prelude::Color;
+/// use bevy_debug_text_overlay::OverlayTheme;
+///
+/// let mut theme = OverlayTheme::colorblind();
+/// theme.categories.insert("physics".into(), Color::CYAN);
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlayTheme {
+    pub levels: BTreeMap<Level, Color>,
+    pub categories: BTreeMap<Cow<'static, str>, Color>,
+}
+impl OverlayTheme {
+    fn from_levels(colors: [Color; 5]) -> Self {
+        let levels = [
+            Level::Trace,
+            Level::Debug,
+            Level::Info,
+            Level::Warn,
+            Level::Error,
+        ];
+        let levels = levels.into_iter().zip(colors).collect();
+        Self { levels, categories: BTreeMap::new() }
+    }
+    /// Muted colors for dark backgrounds, the default bevy clear color.
+    pub fn dark() -> Self {
+        Self::from_levels([
+            Color::rgb(0.5, 0.5, 0.5),
+            Color::rgb(0.55, 0.75, 1.0),
+            Color::rgb(0.9, 0.9, 0.9),
+            Color::rgb(1.0, 0.8, 0.3),
+            Color::rgb(1.0, 0.4, 0.4),
+        ])
+    }
+    /// Dark colors for light backgrounds.
+    pub fn light() -> Self {
+        Self::from_levels([
+            Color::rgb(0.45, 0.45, 0.45),
+            Color::rgb(0.1, 0.3, 0.7),
+            Color::rgb(0.1, 0.1, 0.1),
+            Color::rgb(0.7, 0.45, 0.0),
+            Color::rgb(0.75, 0.1, 0.1),
+        ])
+    }
+    /// Saturated colors, readable over busy scenes.
+    pub fn high_contrast() -> Self {
+        Self::from_levels([
+            Color::SILVER,
+            Color::CYAN,
+            Color::WHITE,
+            Color::YELLOW,
+            Color::RED,
+        ])
+    }
+    /// Colors told apart with any color vision deficiency, from the
+    /// Okabe-Ito palette.
+    pub fn colorblind() -> Self {
+        Self::from_levels([
+            Color::rgb_u8(153, 153, 153),
+            Color::rgb_u8(86, 180, 233),
+            Color::rgb_u8(240, 240, 240),
+            Color::rgb_u8(230, 159, 0),
+            Color::rgb_u8(213, 94, 0),
+        ])
+    }
+    /// Color of messages of `level` in the `cat` category, `None` for the
+    /// fallback color.
+    pub fn color(&self, level: Level, cat: Option<&str>) -> Option<Color> {
+        let category_color = cat.and_then(|cat| self.categories.get(cat));
+        category_color.or_else(|| self.levels.get(&level)).copied()
+    }
+}
+
+/// Registry of the invocation sites of the messages displayed so far, with a
+/// runtime toggle per site, for example to mute noisy messages from a debug
+/// console.
+///
+/// Messages of disabled sites are still consumed, so that they do not
+/// accumulate. Disabling a site removes its messages from the screen.
+///
+/// Once [`Self::CAPACITY`] sites are registered, the enabled ones are
+/// forgotten, they are registered again when their messages show up next.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DebugOverlaySites {
+    sites: BTreeMap<InvocationSiteKey, bool>,
+}
+impl DebugOverlaySites {
+    /// Number of sites above which enabled sites are forgotten.
+    pub const CAPACITY: usize = 4096;
+
+    /// Whether messages from `site` are displayed, `true` for unknown sites.
+    pub fn is_enabled(&self, site: &InvocationSiteKey) -> bool {
+        self.sites.get(site).copied().unwrap_or(true)
+    }
+    /// Display messages from `site` if `enabled`, mute them otherwise.
+    pub fn set_enabled(&mut self, site: InvocationSiteKey, enabled: bool) {
+        self.sites.insert(site, enabled);
+    }
+    /// Enable `site` if it is disabled, disable it otherwise.
+    pub fn toggle(&mut self, site: InvocationSiteKey) {
+        let enabled = self.sites.entry(site).or_insert(true);
+        *enabled = !*enabled;
+    }
+    /// All the sites seen so far, sorted, and whether they are enabled.
+    pub fn iter(&self) -> impl Iterator<Item = (&InvocationSiteKey, bool)> {
+        self.sites.iter().map(|(site, enabled)| (site, *enabled))
+    }
+    /// Add `site` to the registry, enabled, if it is not already in it.
+    #[cfg(feature = "debug")]
+    fn register(&mut self, site: &InvocationSiteKey) {
+        if self.sites.contains_key(site) {
+            return;
+        }
+        if self.sites.len() >= Self::CAPACITY {
+            // Unknown sites are enabled anyway, only disabled ones matter.
+            self.sites.retain(|_, enabled| !*enabled);
+        }
+        self.sites.insert(site.clone(), true);
+    }
+}
+
+/// Gamepad buttons controlling the overlay, see [`OverlayPlugin::gamepad`].
+///
+/// Buttons of any connected gamepad are accepted. Set a binding to `None` to
+/// leave that button to the game.
+#[derive(Clone, Copy, Debug)]
+pub struct GamepadBindings {
+    /// Hide or show the overlay, like [`OverlayPlugin::toggle_key`]. By
+    /// default it is `Select`.
+    pub toggle: Option<GamepadButtonType>,
+    /// Scroll toward older messages, like the `PageUp` key. By default it is
+    /// `DPadUp`.
+    pub page_up: Option<GamepadButtonType>,
+    /// Scroll toward newer messages, like the `PageDown` key. By default it is
+    /// `DPadDown`.
+    pub page_down: Option<GamepadButtonType>,
+    /// Display only the next category, see [`DebugOverlayCategories::cycle`].
+    /// By default it is `DPadRight`.
+    pub cycle_category: Option<GamepadButtonType>,
+}
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self {
+            toggle: Some(GamepadButtonType::Select),
+            page_up: Some(GamepadButtonType::DPadUp),
+            page_down: Some(GamepadButtonType::DPadDown),
+            cycle_category: Some(GamepadButtonType::DPadRight),
+        }
+    }
+}
+
+/// Whether the overlay is displayed.
+///
+/// Messages are still consumed while the overlay is hidden, so that they do
+/// not accumulate. See also [`OverlayPlugin::toggle_key`].
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct OverlayVisibility {
+    pub visible: bool,
+}
+impl Default for OverlayVisibility {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+/// Whether the overlay is paused.
+///
+/// While paused, messages do not expire and are not updated, messages printed
+/// in the meantime are displayed once resumed. Only the last message of each
+/// invocation site is kept, except that all values added to graphs and
+/// histograms are, up to 4096 of them. See also [`OverlayPlugin::pause_key`].
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct DebugOverlayPaused {
+    pub paused: bool,
+}
+
+/// Multiplies the font size of all messages, for example to keep them
+/// readable on high resolution screens.
+///
+/// This can be changed at runtime, messages already displayed are resized.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct DebugOverlayScale {
+    pub scale: f32,
+}
+impl Default for DebugOverlayScale {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+/// Multiplies the opacity of all messages and of the background panel, for
+/// example to dim the overlay while keeping it readable.
+///
+/// This can be changed at runtime, messages already displayed are updated.
+/// See also the `alpha` option of [`screen_print!`].
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct DebugOverlayOpacity {
+    pub opacity: f32,
+}
+impl Default for DebugOverlayOpacity {
+    fn default() -> Self {
+        Self { opacity: 1.0 }
+    }
+}
+
+/// How many of the most recent messages are scrolled out of view, when
+/// [`OverlayPlugin::max_visible_lines`] is set.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct OverlayScroll {
+    pub offset: usize,
+}
+
+/// Specific call site of [`screen_print!`], or user-provided key.
+///
+/// Used to identify where a message is coming from and replacing it on screen
+/// when updated.
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum InvocationSiteKey {
+    Site {
+        file: &'static str,
+        line: u32,
+        column: u32,
+    },
+    /// Set with the `key:` option of [`screen_print!`].
+    Named(Cow<'static, str>),
+}
+impl fmt::Display for InvocationSiteKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Site { file, line, column } => write!(f, "[{file}:{line}:{column}]"),
+            Self::Named(name) => write!(f, "[{name}]"),
+        }
+    }
+}
+
+/// What to display before each message, see `OverlayPlugin::prefix`.
+#[derive(Clone, Default)]
+pub enum MessagePrefix {
+    /// `[file:line:column]` of the [`screen_print!`] invocation, or `[key]`
+    /// if it has a `key:` option.
+    #[default]
+    Full,
+    /// `[file:line]` of the [`screen_print!`] invocation, or `[key]` if it
+    /// has a `key:` option.
+    Short,
+    /// `[name]` of the function calling [`screen_print!`], usually a bevy
+    /// system. Messages printed otherwise use the `Full` prefix.
+    System,
+    /// Do not display a prefix.
+    Hidden,
+    /// Use the provided function to compute the prefix.
+    Custom(Arc<dyn Fn(&InvocationSiteKey) -> String + Send + Sync>),
+}
+impl MessagePrefix {
+    /// The prefix to display for messages identified by `key`.
+    pub fn format(&self, key: &InvocationSiteKey) -> Option<String> {
+        match (self, key) {
+            (Self::Full | Self::System, key) => Some(key.to_string()),
+            (Self::Short, InvocationSiteKey::Site { file, line, .. }) => {
+                Some(format!("[{file}:{line}]"))
+            }
+            (Self::Short, key) => Some(key.to_string()),
+            (Self::Hidden, _) => None,
+            (Self::Custom(format), key) => Some(format(key)),
+        }
+    }
+}
+
+/// The clock message timeouts count in, see `OverlayPlugin::time_source`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimeSource {
+    /// Game time, `Time<Virtual>`, messages stay on screen while the game is
+    /// paused and for longer in slow motion.
+    #[default]
+    Virtual,
+    /// Wall-clock time, `Time<Real>`, messages expire at the same pace
+    /// whatever the game speed.
+    Real,
+}
+
+/// ECS statistics to display, see `OverlayPlugin::ecs_stats`.
+///
+/// The entity and archetype counts are always displayed, add components with
+/// [`EcsStats::component`] to also display how many entities have them.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::EcsStats;
+///
+/// let stats = EcsStats::default()
+///     .component::<Transform>()
+///     .component::<Handle<Mesh>>();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EcsStats {
+    components: Vec<(String, TypeId)>,
+}
+impl EcsStats {
+    /// Also display the number of entities with a `T` component.
+    pub fn component<T: Component>(mut self) -> Self {
+        let name = bevy::utils::get_short_name(std::any::type_name::<T>());
+        self.components.push((name, TypeId::of::<T>()));
+        self
+    }
+}
+
+/// Time displayed before each message, see `OverlayPlugin::timestamp`.
+///
+/// It is updated each time the message is refreshed.
+#[derive(Clone, Default)]
+pub enum Timestamp {
+    /// Do not display a timestamp.
+    #[default]
+    Hidden,
+    /// Game time elapsed since startup, as `[12.345s]`.
+    Elapsed,
+    /// UTC wall-clock time of day, as `[13:37:00.123]`.
+    WallClock,
+    /// Use the provided function to compute the timestamp, it is called with
+    /// the elapsed game time and the current wall-clock time.
+    Custom(Arc<dyn Fn(Duration, SystemTime) -> String + Send + Sync>),
+}
+impl Timestamp {
+    /// The timestamp to display at `elapsed` game time and `now` wall-clock time.
+    pub fn format(&self, elapsed: Duration, now: SystemTime) -> Option<String> {
+        match self {
+            Self::Hidden => None,
+            Self::Elapsed => Some(format!("[{:.3}s]", elapsed.as_secs_f64())),
+            Self::WallClock => {
+                let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let secs = since_epoch.as_secs();
+                let (hours, minutes, seconds) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+                let millis = since_epoch.subsec_millis();
+                Some(format!(
+                    "[{hours:02}:{minutes:02}:{seconds:02}.{millis:03}]"
+                ))
+            }
+            Self::Custom(format) => Some(format(elapsed, now)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn test_sites_bounded() {
+        let site = |i: usize| InvocationSiteKey::Named(format!("site {i}").into());
+        let mut sites = DebugOverlaySites::default();
+        sites.set_enabled(site(0), false);
+        for i in 1..DebugOverlaySites::CAPACITY {
+            sites.register(&site(i));
+        }
+        assert_eq!(sites.iter().count(), DebugOverlaySites::CAPACITY);
+        sites.register(&site(DebugOverlaySites::CAPACITY));
+        let kept: Vec<_> = sites.iter().map(|(site, _)| site.clone()).collect();
+        assert_eq!(kept, [site(0), site(DebugOverlaySites::CAPACITY)]);
+        assert!(!sites.is_enabled(&site(0)));
+    }
+    #[test]
+    fn test_search_matches() {
+        let mut search = OverlaySearch::default();
+        assert!(search.matches("hp: 3"));
+        search.text = "Stamina".to_owned();
+        assert!(search.matches("player stamina: 20"));
+        assert!(!search.matches("hp: 3"));
+    }
+    #[test]
+    fn test_categories_toggle() {
+        let mut categories = DebugOverlayCategories::default();
+        assert!(categories.is_enabled("physics"));
+        categories.toggle("physics");
+        assert!(!categories.is_enabled("physics"));
+        assert!(categories.is_enabled("ai"));
+        categories.toggle("physics");
+        assert!(categories.is_enabled("physics"));
+        categories.disable("ai");
+        categories.enable("ai");
+        assert!(categories.is_enabled("ai"));
+    }
+    #[test]
+    fn test_categories_cycle() {
+        let mut categories = DebugOverlayCategories {
+            hotkeys: vec!["physics".into(), "ai".into()],
+            ..Default::default()
+        };
+        let enabled = |categories: &mut DebugOverlayCategories| {
+            categories.cycle();
+            ["physics", "ai"].map(|category| categories.is_enabled(category))
+        };
+        assert_eq!(enabled(&mut categories), [true, false]);
+        assert_eq!(enabled(&mut categories), [false, true]);
+        assert_eq!(enabled(&mut categories), [true, true]);
+        assert_eq!(enabled(&mut categories), [true, false]);
+    }
+    #[test]
+    fn test_theme_color() {
+        assert_eq!(OverlayTheme::default().color(Level::Error, None), None);
+        let mut theme = OverlayTheme::high_contrast();
+        theme.categories.insert("ai".into(), Color::GREEN);
+        assert_eq!(theme.color(Level::Error, None), Some(Color::RED));
+        assert_eq!(theme.color(Level::Error, Some("ai")), Some(Color::GREEN));
+        assert_eq!(
+            theme.color(Level::Info, Some("physics")),
+            Some(Color::WHITE)
+        );
+    }
+    #[test]
+    fn test_timestamp_format() {
+        let elapsed = Duration::from_millis(12_345);
+        let now = UNIX_EPOCH + Duration::from_millis(86_400_000 * 3 + 49_020_123);
+        assert_eq!(Timestamp::Hidden.format(elapsed, now), None);
+        let elapsed_text = Timestamp::Elapsed.format(elapsed, now);
+        assert_eq!(elapsed_text.as_deref(), Some("[12.345s]"));
+        let wall_clock = Timestamp::WallClock.format(elapsed, now);
+        assert_eq!(wall_clock.as_deref(), Some("[13:37:00.123]"));
+    }
 }
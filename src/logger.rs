@@ -0,0 +1,84 @@
+//! A [`log::Log`] implementation displaying records in the overlay.
+//!
+//! Note that bevy's `LogPlugin` already sets a global logger, you need to
+//! disable it to use [`OverlayLogger`].
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::overlay::guard_logging;
+use crate::{command_channels, InvocationSiteKey, Level, PrintOptions};
+
+/// Forward `log` records to the overlay as `push` messages, colored by level.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::OverlayLogger;
+/// use log::LevelFilter;
+///
+/// OverlayLogger::new(LevelFilter::Info)
+///     .allow_target("my_game::physics")
+///     .init()
+///     .unwrap();
+/// log::info!(target: "my_game::physics", "this is shown on screen");
+/// ```
+pub struct OverlayLogger {
+    level: LevelFilter,
+    targets: Vec<String>,
+}
+impl OverlayLogger {
+    /// Display records of `level` or more severe.
+    pub fn new(level: LevelFilter) -> Self {
+        Self { level, targets: Vec::new() }
+    }
+    /// Only display records which target starts with `target`. Can be called
+    /// several times to allow several targets. By default, all targets are
+    /// displayed.
+    pub fn allow_target(mut self, target: impl Into<String>) -> Self {
+        self.targets.push(target.into());
+        self
+    }
+    /// Set this as the global logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+fn level_options(level: log::Level) -> PrintOptions {
+    let level = match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warn,
+        log::Level::Info => Level::Info,
+        log::Level::Debug => Level::Debug,
+        log::Level::Trace => Level::Trace,
+    };
+    PrintOptions::default()
+        .push(true)
+        .level(level)
+        .col(level.color())
+        .logged(true)
+}
+impl Log for OverlayLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let allowed = |target: &String| metadata.target().starts_with(target.as_str());
+        metadata.level() <= self.level
+            && (self.targets.is_empty() || self.targets.iter().any(allowed))
+    }
+    fn log(&self, record: &Record) {
+        // Errors of the overlay itself are not displayed, it has its own way
+        // to report them.
+        if !self.enabled(record.metadata()) || record.target().starts_with(env!("CARGO_CRATE_NAME"))
+        {
+            return;
+        }
+        let key = InvocationSiteKey::Site {
+            file: record.file_static().unwrap_or("<unknown>"),
+            line: record.line().unwrap_or(0),
+            column: 0,
+        };
+        let options = level_options(record.level());
+        // Records mirrored by the overlay, or logged while formatting this
+        // one, would cause infinite recursion.
+        guard_logging(|| {
+            command_channels().print(key, options, || record.args().to_string());
+        });
+    }
+    fn flush(&self) {}
+}
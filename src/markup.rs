@@ -0,0 +1,145 @@
+//! Inline color markup of messages, see [`crate::screen_print!`].
+use bevy::prelude::Color;
+
+/// A part of a message and its color, `None` for the color of the message.
+pub(crate) type Span = (String, Option<Color>);
+
+/// The color named by `tag`, either one of the basic color names or an
+/// hexadecimal `#rrggbb` color.
+fn tag_color(tag: &str) -> Option<Color> {
+    let color = match tag {
+        "red" => Color::RED,
+        "green" => Color::GREEN,
+        "blue" => Color::BLUE,
+        "yellow" => Color::YELLOW,
+        "orange" => Color::ORANGE,
+        "pink" => Color::PINK,
+        "purple" => Color::PURPLE,
+        "cyan" => Color::CYAN,
+        "white" => Color::WHITE,
+        "gray" | "grey" => Color::GRAY,
+        "black" => Color::BLACK,
+        _ => return Color::hex(tag.strip_prefix('#')?).ok(),
+    };
+    Some(color)
+}
+
+/// Split `text` into spans of a single color.
+///
+/// `[<color>]` starts a span of that color, `[/]` ends it, going back to the
+/// color of the enclosing span. Brackets that are not part of a tag are kept
+/// as is, so that printing `Debug` output of slices works as expected.
+pub(crate) fn parse(text: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = vec![(String::new(), None)];
+    let mut colors = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        let after = &rest[start + 1..];
+        let tag = after.find(']').map(|end| &after[..end]);
+        let color = match tag {
+            Some("/") if !colors.is_empty() => {
+                colors.pop();
+                Some(colors.last().copied())
+            }
+            Some(tag) => tag_color(tag).map(|color| {
+                colors.push(color);
+                Some(color)
+            }),
+            None => None,
+        };
+        let (span, color_span) = spans.last_mut().expect("There is at least one span");
+        let (Some(color), Some(tag)) = (color, tag) else {
+            span.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+        span.push_str(&rest[..start]);
+        rest = &rest[start + tag.len() + 2..];
+        if span.is_empty() {
+            *color_span = color;
+        } else {
+            spans.push((String::new(), color));
+        }
+    }
+    let (span, _) = spans.last_mut().expect("There is at least one span");
+    span.push_str(rest);
+    if spans.len() > 1 {
+        spans.retain(|(span, _)| !span.is_empty());
+    }
+    spans
+}
+
+/// `text` without its color markup.
+pub(crate) fn strip(text: &str) -> String {
+    if !text.contains('[') {
+        return text.to_owned();
+    }
+    parse(text).into_iter().map(|(span, _)| span).collect()
+}
+
+/// The first `lines` lines of `spans`, followed by a line counting the
+/// hidden ones if there are more.
+pub(crate) fn truncate(mut spans: Vec<Span>, lines: usize) -> Vec<Span> {
+    let mut newlines = spans
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (span, _))| span.match_indices('\n').map(move |(at, _)| (i, at + 1)));
+    let Some((i, at)) = newlines.nth(lines.max(1) - 1) else {
+        return spans;
+    };
+    let mut rest = spans[i].0[at..].to_owned();
+    rest.extend(spans[i + 1..].iter().map(|(span, _)| span.as_str()));
+    let hidden = rest.lines().count();
+    if hidden == 0 {
+        return spans;
+    }
+    spans.truncate(i + 1);
+    spans[i].0.truncate(at);
+    spans.push((format!("\u{2026} {hidden} more lines\n"), None));
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spans() {
+        let spans = parse("hp: [red]10[/] / 20");
+        let expected = [
+            ("hp: ".to_owned(), None),
+            ("10".to_owned(), Some(Color::RED)),
+            (" / 20".to_owned(), None),
+        ];
+        assert_eq!(spans, expected);
+    }
+    #[test]
+    fn test_parse_nested() {
+        let spans = parse("[blue]a[#ff0000]b[/]c[/]");
+        let red = Color::hex("ff0000").unwrap();
+        let expected = [
+            ("a".to_owned(), Some(Color::BLUE)),
+            ("b".to_owned(), Some(red)),
+            ("c".to_owned(), Some(Color::BLUE)),
+        ];
+        assert_eq!(spans, expected);
+    }
+    #[test]
+    fn test_parse_literal_brackets() {
+        let text = "[src/main.rs:3:5] [1, 2] [/] [red";
+        assert_eq!(parse(text), [(text.to_owned(), None)]);
+        assert_eq!(strip("[green]ok[/] [1]"), "ok [1]");
+    }
+    #[test]
+    fn test_truncate_lines() {
+        let spans = parse("a\n[red]b\nc[/]\nd\n");
+        let expected = [
+            ("a\n".to_owned(), None),
+            ("b\n".to_owned(), Some(Color::RED)),
+            ("\u{2026} 2 more lines\n".to_owned(), None),
+        ];
+        assert_eq!(truncate(spans.clone(), 2), expected);
+        assert_eq!(truncate(spans.clone(), 4), spans);
+        assert_eq!(truncate(spans.clone(), 10), spans);
+    }
+}
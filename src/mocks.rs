@@ -1,43 +1,575 @@
 //! Mocks for [`OverlayPlugin`] and [`screen_print`]
 //!
 //! `screen_print` "uses" the provided variables to avoid warnings when
-//! disabling debug mode.
+//! disabling debug mode. They are only referenced in a closure that is never
+//! called, so that arguments are neither evaluated nor formatted, and nothing
+//! is allocated, see `tests/release.rs`.
 #[derive(Default)]
 pub struct OverlayPlugin {
     pub fallback_color: bevy::prelude::Color,
     pub font_size: f32,
+    pub font: Option<&'static str>,
+    pub overflow_policy: crate::OverflowPolicy,
+    pub toggle_key: Option<bevy::prelude::KeyCode>,
+    pub background_color: Option<bevy::prelude::Color>,
+    pub background_padding: f32,
+    pub pool_size: usize,
+    pub pool_grace: f64,
+    pub single_text: bool,
+    pub show_fps: bool,
+    pub show_diagnostics: Vec<bevy::diagnostic::DiagnosticPath>,
+    pub ecs_stats: Option<crate::EcsStats>,
+    pub max_visible_lines: Option<usize>,
+    pub max_height: bevy::ui::Val,
+    pub max_width: bevy::ui::Val,
+    pub dedicated_camera: bool,
+    pub default_window: bevy::window::WindowRef,
+    pub prefix: crate::MessagePrefix,
+    pub timestamp: crate::Timestamp,
+    pub fade_duration: f64,
+    pub fade_background: bool,
+    pub history: Option<usize>,
+    pub history_dump_key: Option<bevy::input::keyboard::KeyCode>,
+    pub snapshot_key: Option<bevy::input::keyboard::KeyCode>,
+    pub snapshot_path: &'static str,
+    pub column_width: Option<f32>,
+    pub layout: crate::Layout,
+    pub line_spacing: f32,
+    pub indent_width: f32,
+    pub table_font: Option<&'static str>,
+    pub click_to_copy: bool,
+    pub pause_key: Option<bevy::input::keyboard::KeyCode>,
+    pub show_countdown: bool,
+    pub mirror_to_log: bool,
+    pub headless: bool,
+    pub ordering: crate::MessageOrdering,
+    pub group_by_file: bool,
+    pub collapse_key: Option<bevy::prelude::KeyCode>,
+    pub expand_key: Option<bevy::prelude::KeyCode>,
+    pub coalesce: bool,
+    pub beep_sound: Option<&'static str>,
+    pub gamepad: Option<crate::GamepadBindings>,
+    pub time_source: crate::TimeSource,
+    pub env_var: Option<&'static str>,
+    pub follow_stepping: bool,
 }
 impl bevy::prelude::Plugin for OverlayPlugin {
     fn build(&self, _app: &mut bevy::prelude::App) {}
 }
 
+#[derive(bevy::prelude::SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OverlaySet {
+    Update,
+    Layout,
+}
+
+pub struct ClearOnStateChange<S>(std::marker::PhantomData<fn() -> S>);
+impl<S> Default for ClearOnStateChange<S> {
+    fn default() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+impl<S: bevy::prelude::States> bevy::prelude::Plugin for ClearOnStateChange<S> {
+    fn build(&self, _app: &mut bevy::prelude::App) {}
+}
+
+impl crate::OverlaySnapshot {
+    pub fn capture(_world: &mut bevy::prelude::World) -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageRecord {
+    pub time: f64,
+    pub site: String,
+    pub change: RecordedChange,
+    pub color: Option<bevy::prelude::Color>,
+    pub timeout: f64,
+    pub push: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedChange {
+    Print(String),
+    Clear,
+    ClearAll,
+}
+
+#[derive(bevy::prelude::Resource, Clone, Debug, Default)]
+pub struct OverlayRecording;
+impl OverlayRecording {
+    pub fn records(&self) -> &[MessageRecord] {
+        &[]
+    }
+    pub fn clear(&mut self) {}
+    #[cfg(feature = "serde")]
+    pub fn write(&self, _writer: impl std::io::Write) -> std::io::Result<()> {
+        Ok(())
+    }
+    #[cfg(feature = "serde")]
+    pub fn write_to(&self, _path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(bevy::prelude::Resource, Clone, Debug, Default)]
+pub struct OverlayReplay;
+impl OverlayReplay {
+    pub fn new(_records: impl IntoIterator<Item = MessageRecord>) -> Self {
+        Self
+    }
+    pub fn is_finished(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "serde")]
+    pub fn read(_reader: impl std::io::BufRead) -> std::io::Result<Self> {
+        Ok(Self)
+    }
+    #[cfg(feature = "serde")]
+    pub fn read_from(_path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Ok(Self)
+    }
+}
+
+#[derive(bevy::prelude::Resource, Clone, Default)]
+pub struct OverlayConfig {
+    pub fallback_color: bevy::prelude::Color,
+    pub font_size: f32,
+    pub font: bevy::asset::Handle<bevy::text::Font>,
+    pub table_font: bevy::asset::Handle<bevy::text::Font>,
+    pub background_color: Option<bevy::prelude::Color>,
+    pub background_padding: f32,
+    pub prefix: crate::MessagePrefix,
+    pub timestamp: crate::Timestamp,
+}
+
+#[derive(Clone, Default)]
+pub struct OverlayCommands;
+impl OverlayCommands {
+    pub fn col(self, _color: bevy::prelude::Color) -> Self {
+        self
+    }
+    pub fn sec(self, _timeout: f64) -> Self {
+        self
+    }
+    pub fn print(&self, _text: impl Into<String>) {}
+    pub fn push(&self, _text: impl Into<String>) {}
+    pub fn print_keyed(
+        &self,
+        _key: impl Into<std::borrow::Cow<'static, str>>,
+        _text: impl Into<String>,
+    ) {
+    }
+    pub fn clear(&self, _key: impl Into<std::borrow::Cow<'static, str>>) {}
+    pub fn clear_all(&self) {}
+    pub fn pin(&self, _key: impl Into<std::borrow::Cow<'static, str>>) {}
+    pub fn unpin(&self, _key: impl Into<std::borrow::Cow<'static, str>>) {}
+    pub fn set_color(
+        &self,
+        _key: impl Into<std::borrow::Cow<'static, str>>,
+        _color: bevy::prelude::Color,
+    ) {
+    }
+    pub fn extend_timeout(&self, _key: impl Into<std::borrow::Cow<'static, str>>, _secs: f64) {}
+}
+
+#[derive(Clone)]
+pub struct OverlaySender;
+impl OverlaySender {
+    pub fn new(_name: impl Into<std::borrow::Cow<'static, str>>) -> Self {
+        Self
+    }
+    pub fn col(self, _color: bevy::prelude::Color) -> Self {
+        self
+    }
+    pub fn sec(self, _timeout: f64) -> Self {
+        self
+    }
+    pub fn print(&self, _text: impl Into<String>) {}
+    pub fn push(&self, _text: impl Into<String>) {}
+    pub fn progress(&self, _label: impl std::fmt::Display, _fraction: f32) {}
+    pub fn clear(&self) {}
+}
+
+#[must_use]
+pub struct ScreenTimer;
+
+#[cfg(feature = "assets")]
+pub struct OverlayAssetsPlugin<A>(std::marker::PhantomData<fn() -> A>);
+#[cfg(feature = "assets")]
+impl<A> Default for OverlayAssetsPlugin<A> {
+    fn default() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+#[cfg(feature = "assets")]
+impl<A: bevy::asset::Asset> bevy::prelude::Plugin for OverlayAssetsPlugin<A> {
+    fn build(&self, _app: &mut bevy::prelude::App) {}
+}
+
+#[cfg(feature = "console")]
+#[derive(bevy::prelude::Resource, Default)]
+pub struct ConsoleCommands;
+#[cfg(feature = "console")]
+impl ConsoleCommands {
+    pub fn register(
+        &mut self,
+        _name: impl Into<String>,
+        _handler: impl Fn(&mut bevy::prelude::World, &[&str]) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self
+    }
+}
+#[cfg(feature = "console")]
+pub struct ConsolePlugin {
+    pub toggle_key: bevy::prelude::KeyCode,
+}
+#[cfg(feature = "console")]
+impl Default for ConsolePlugin {
+    fn default() -> Self {
+        Self { toggle_key: bevy::prelude::KeyCode::Backquote }
+    }
+}
+#[cfg(feature = "console")]
+impl bevy::prelude::Plugin for ConsolePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<ConsoleCommands>();
+    }
+}
+
+#[cfg(feature = "remote")]
+pub struct OverlayRemotePlugin {
+    pub address: &'static str,
+}
+#[cfg(feature = "remote")]
+impl Default for OverlayRemotePlugin {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:7878" }
+    }
+}
+#[cfg(feature = "remote")]
+impl bevy::prelude::Plugin for OverlayRemotePlugin {
+    fn build(&self, _app: &mut bevy::prelude::App) {}
+}
+
+#[cfg(feature = "remote")]
+pub struct OverlayRemoteSink {
+    pub address: &'static str,
+    pub source: &'static str,
+}
+#[cfg(feature = "remote")]
+impl Default for OverlayRemoteSink {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:7879", source: "remote" }
+    }
+}
+#[cfg(feature = "remote")]
+impl bevy::prelude::Plugin for OverlayRemoteSink {
+    fn build(&self, _app: &mut bevy::prelude::App) {}
+}
+
+#[cfg(feature = "log")]
+pub struct OverlayLogger;
+#[cfg(feature = "log")]
+impl OverlayLogger {
+    pub fn new(_level: log::LevelFilter) -> Self {
+        Self
+    }
+    pub fn allow_target(self, _target: impl Into<String>) -> Self {
+        self
+    }
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub fn overlay_subscriber(subscriber: bevy::log::BoxedSubscriber) -> bevy::log::BoxedSubscriber {
+    subscriber
+}
+
+#[cfg(feature = "timings")]
+pub fn timing_subscriber(subscriber: bevy::log::BoxedSubscriber) -> bevy::log::BoxedSubscriber {
+    subscriber
+}
+#[cfg(feature = "timings")]
+pub struct SystemTimingsPlugin {
+    pub top: usize,
+    pub interval: f64,
+}
+#[cfg(feature = "timings")]
+impl Default for SystemTimingsPlugin {
+    fn default() -> Self {
+        Self { top: 5, interval: 1.0 }
+    }
+}
+#[cfg(feature = "timings")]
+impl bevy::prelude::Plugin for SystemTimingsPlugin {
+    fn build(&self, _app: &mut bevy::prelude::App) {}
+}
+
 #[macro_export]
 macro_rules! screen_print {
-    (push, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($color, format!($text $(, $fmt_args)*));
+    (@opts [$($values:tt)*] push, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] persist, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] no_prefix, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_print [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] blink, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] beep, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] pin, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] diff, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] sys, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($values)*] $($rest)+)
+    };
+    (@opts [$($values:tt)*] stats, $label:expr, $value:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$value);
+        };
+    }};
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_print [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $text:expr $(, $fmt_args:expr)*) => {{
+        let _ = || {
+            let _ = ($($values)* ::std::format_args!($text $(, $fmt_args)*));
+        };
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_print!(@opts [] $($args)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __screen_opts {
+    ($mac:ident [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($values)*] $($rest)+)
+    };
+    ($mac:ident [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($values)* (&$value),] $($rest)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_print_at {
+    ($position:expr, $($args:tt)+) => {{
+        let _ = || {
+            let _ = $position;
+        };
+        $crate::screen_print!($($args)+)
+    }};
+}
+
+#[macro_export]
+macro_rules! screen_print_entity {
+    ($entity:expr, $($args:tt)+) => {{
+        let _ = || {
+            let _ = $entity;
+        };
+        $crate::screen_print!($($args)+)
+    }};
+}
+
+#[macro_export]
+macro_rules! screen_graph {
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_graph [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $label:expr, $value:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$value);
+        };
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_graph!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_histogram {
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_histogram [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $label:expr, $value:expr, buckets: $buckets:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$value, &$buckets);
+        };
+    }};
+    (@opts [$($values:tt)*] $label:expr, $value:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$value);
+        };
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_histogram!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_table {
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_table [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_table [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $header:expr, $rows:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$header, &$rows);
+        };
     }};
-    (col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($color, format!($text $(, $fmt_args)*));
+    ($($args:tt)+) => {
+        $crate::screen_table!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_progress {
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_progress [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_progress [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $label:expr, $range:expr, $value:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$range, &$value);
+        };
     }};
-    (push, sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($color, $timeout, format!($text $(, $fmt_args)*));
+    ($($args:tt)+) => {
+        $crate::screen_progress!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_print_if {
+    ($condition:expr, $($args:tt)+) => {{
+        let _ = || {
+            let _ = $condition;
+        };
+        $crate::screen_print!($($args)+)
     }};
-    (sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($color, $timeout, format!($text $(, $fmt_args)*));
+}
+
+#[macro_export]
+macro_rules! screen_assert {
+    ($condition:expr, $($args:tt)+) => {{
+        let _ = || {
+            let _ = $condition;
+        };
+        $crate::screen_print!($($args)+)
+    }};
+    ($condition:expr $(,)?) => {{
+        let _ = || {
+            let _ = $condition;
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! screen_watch {
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_watch [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_watch [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $watched:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$watched);
+        };
     }};
-    (push, sec: $timeout:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($timeout, format!($text $(, $fmt_args)*));
+    ($($args:tt)+) => {
+        $crate::screen_watch!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_timer {
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_timer [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_timer [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $label:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label);
+        };
+        $crate::ScreenTimer
     }};
-    (sec: $timeout:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($timeout, format!($text $(, $fmt_args)*));
+    ($($args:tt)+) => {
+        $crate::screen_timer!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_group {
+    (@opts [$($values:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_group [$($values)*] once, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_group [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $header:expr, $body:block $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$header);
+        };
+        $body
     }};
-    (push, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = format!($text $(, $fmt_args)*);
+    ($($args:tt)+) => {
+        $crate::screen_group!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_counter {
+    (@opts [$($values:tt)*] every: $($rest:tt)+) => {
+        ::std::compile_error!("`screen_counter!` updates its message once per frame, `every` is not supported")
+    };
+    (@opts [$($values:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_counter [$($values)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($values:tt)*] $label:expr, reset: $reset:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label, &$reset);
+        };
     }};
-    ($text:expr $(, $fmt_args:expr)*) => {{
-        let _ = format!($text $(, $fmt_args)*);
+    (@opts [$($values:tt)*] $label:expr $(,)?) => {{
+        let _ = || {
+            let _ = ($($values)* &$label);
+        };
     }};
-    (@impl sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        let _ = ($color, $timeout, format!($text $(, $fmt_args)*));
+    ($($args:tt)+) => {
+        $crate::screen_counter!(@opts [] $($args)+)
+    };
+}
+
+#[macro_export]
+macro_rules! screen_clear {
+    () => {};
+    ($key:expr $(,)?) => {{
+        let _ = || {
+            let _ = $key;
+        };
     }};
 }
@@ -4,14 +4,14 @@
 //!
 //! The implementation is as follow:
 //! * We have a static variable [`static@COMMAND_CHANNELS`] of type [`CommandChannels`]
-//!   that contains channels for syncing [`Command`]s.
+//!   that contains a bounded queue for syncing [`Command`]s.
 //! * [`screen_print!`] secretly expands to a call of to that global variable,
-//!   it simply pushes messages to the sender channel using
-//!   [`CommandChannels::refresh_text`] method. This is why, `COMMAND_CHANNELS` is
+//!   it simply pushes messages to the queue using
+//!   [`CommandChannels::print`] method. This is why, `COMMAND_CHANNELS` is
 //!   public. The end user code needs to be able to access it. But it is kept
 //!   hidden thanks to the `#[doc(hidden)]` attribute.
-//! * The [`update_messages_as_per_commands`] system reads from the `receiver`
-//!   channel of `COMMAND_CHANNELS` and updates or adds new debug message entities.
+//! * The [`update_messages_as_per_commands`] system pops from the queue of
+//!   `COMMAND_CHANNELS` and updates or adds new debug message entities.
 //!   For each [`Command::Refresh`], a line is updated or added, a refresh can change
 //!   the text or the color, and will always update the [`Message::expiration`].
 //! * The [`layout_messages`] system takes care of the layout (making sure to
@@ -21,28 +21,81 @@
 //!
 //! ## Notes
 //!
-//! The channels use the `try_{send,iter}` methods to avoid blocking
-//! operations. Since we are already running parallel thanks to the bevy
-//! scheduler, there is no need for control-flow timing operations.
+//! The queue is a lock-free MPMC queue, so that systems printing in parallel
+//! do not contend on a lock, and never block. When it is full, what happens
+//! is controlled by the [`OverflowPolicy`], see [`CommandChannels::send`].
 //!
 //! Each individual invocation of [`screen_print!`] gets a unique
 //! [`InvocationSiteKey`], and a corresponding `Entity`.
-use std::fmt;
-use std::sync::mpsc::{self, Receiver, SyncSender};
-use std::sync::{Mutex, OnceLock};
+//!
+//! Each invocation of [`screen_print!`] also gets a [`PrintCache`], so that
+//! printing the same message every frame doesn't allocate a `String` each
+//! frame, it only pushes a [`Command::Touch`] keeping the message displayed.
+//!
+//! Messages can be sent from any thread, see [`OverlaySender`]. They are
+//! applied by the first run of [`update_messages_as_per_commands`] after they
+//! were queued, which may be a frame later when they are sent while it runs,
+//! and they expire counting from then.
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::f64::consts::TAU;
+use std::fmt::Write;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use std::{fmt, iter, mem};
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::diagnostic::DiagnosticPath;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::schedule::{InternedScheduleLabel, NodeId, Stepping, SystemConfigs};
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::render::camera::{ClearColorConfig, NormalizedRenderTarget, RenderTarget};
+use bevy::render::view::{RenderLayers, VisibilitySystems};
+use bevy::ui::update::update_target_camera_system;
+use bevy::ui::widget::measure_text_system;
+use bevy::ui::{UiScale, UiSystem};
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::{PrimaryWindow, WindowRef, WindowResized};
+use concurrent_queue::{ConcurrentQueue, PushError};
 
-use crate::block::Blocks;
+use crate::diff;
+use crate::graph::{Samples, Series};
+use crate::history::{HistoryEntry, OverlayHistory, OverlayMessageBuffer};
+use crate::markup::{self, Span};
+use crate::replay::{replay_messages, MessageRecord, OverlayRecording, RecordTaps, RecordedChange};
+use crate::snapshot::{OverlaySnapshot, SnapshotLine};
+use crate::state::{ScopedSites, StateScope};
+use crate::{
+    Alignment, Anchor, DebugOverlayCategories, DebugOverlayLocation, DebugOverlayOpacity,
+    DebugOverlayPaused, DebugOverlayScale, DebugOverlaySites, EcsStats, GamepadBindings,
+    InvocationSiteKey, Layout, LayoutArea, Level, Margin, MessageOrdering, MessagePrefix,
+    OverflowPolicy, OverlayFilter, OverlayLayout, OverlayMessage, OverlayMessageExpired,
+    OverlayMessageShown, OverlayScroll, OverlaySearch, OverlayTheme, OverlayViewport,
+    OverlayVisibility, TimeSource, Timestamp,
+};
 
 const MAX_LINES: usize = 4096;
 
 static COMMAND_CHANNELS: OnceLock<CommandChannels> = OnceLock::new();
 
+/// Reference point for the `every:` option of [`screen_print!`].
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Microseconds since [`START`] plus one, so that 0 can mean "never".
+fn now_micros() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64 + 1
+}
+
 #[doc(hidden)]
 pub fn command_channels() -> &'static CommandChannels {
-    let (sender, receiver) = mpsc::sync_channel(MAX_LINES);
-    COMMAND_CHANNELS.get_or_init(|| CommandChannels { sender, receiver: Mutex::new(receiver) })
+    COMMAND_CHANNELS.get_or_init(|| CommandChannels::new(MAX_LINES))
 }
 
 // TODO: better API?
@@ -53,307 +106,6437 @@ pub fn command_channels() -> &'static CommandChannels {
 ///
 /// # Limitations
 ///
-/// * Entity count: Entities used for displaying text are never despawned,
-///   so if at one point you have very many messages displayed at the same time,
-///   it might slow down afterward your game. Note that aready spawned entities
-///   are reused, so you need not fear leaks.
+/// * Entity count: Entities used for displaying `push` messages are kept
+///   around for reuse. Only up to [`OverlayPlugin::pool_size`] idle entities
+///   are kept, the rest are despawned after [`OverlayPlugin::pool_grace`]
+///   seconds. Entities for other messages are never despawned, but there is
+///   only one per invocation site.
 /// * Max call per frame: at most 4096 messages can be printed per frame,
-///   exceeding that amount will panic.
+///   what happens to the excess is controlled by the [`OverflowPolicy`]
+///   resource.
 ///
 /// # Usage
 ///
 /// Call `screen_print!` like you would call any `format!`-style macros from
 /// the standard lib.
 ///
-/// You can also customize color and timeout, by adding prefix optional arguments
-/// (only supported in this order):
+/// You can also customize the message by adding prefix optional arguments
+/// (in any order):
 ///
-/// 1. `push`: Do not overwrite previous text value. This allows
-///    printing multiple messages from the same macro call, you can use this
-///    in loops, or for messages that makes sense to duplicate on screen.
-///    Be advised! Using a `push` message once per frame will spam the log.
-/// 2. `sec: <timeout>`: specify in seconds for how long the text shows up
-///    (default is 7 seconds)
-/// 3. `col: <color>`: specify the color of the text. Default is
-///    `fallback_color` provided in `OverlayPlugin`, which itself defaults
-///    to yellow.
+/// * `push`: Do not overwrite previous text value. This allows
+///   printing multiple messages from the same macro call, you can use this
+///   in loops, or for messages that makes sense to duplicate on screen.
+///   Be advised! Using a `push` message once per frame will spam the log.
+/// * `persist`: Display the message until it is removed with
+///   [`screen_clear!`], rather than for a few seconds.
+/// * `no_prefix`: Do not display the `[file:line:column]` prefix, see
+///   [`OverlayPlugin::prefix`].
+/// * `sys`: Display the name of the function calling `screen_print!`,
+///   usually a bevy system, as prefix, like [`MessagePrefix::System`]. This
+///   applies whatever the [`OverlayPlugin::prefix`], even `Hidden`.
+/// * `once`: Only display the message the first time this invocation site is
+///   reached, for the entire run of the program.
+/// * `sec: <timeout>`: specify in seconds for how long the text shows up
+///   (default is 7 seconds)
+/// * `frames: <n>`: display the message for `n` frames instead of a duration,
+///   regardless of the frame rate. Frames are not counted while the overlay
+///   is [paused](DebugOverlayPaused).
+/// * `every: <interval>`: print at most once every `interval` seconds (of
+///   real time), other calls are ignored without formatting the text.
+/// * `col: <color>`: specify the color of the text. Default is the color of
+///   the message level and category in the [`OverlayTheme`], if any,
+///   otherwise `fallback_color` provided in `OverlayPlugin`, which itself
+///   defaults to yellow.
+/// * `alpha: <opacity>`: an `f32` between 0 and 1 multiplying the opacity of
+///   the message, to render low importance messages faint. Default is 1. See
+///   also [`DebugOverlayOpacity`].
+/// * `bg: <color>`: color of a background highlighting the message. Default
+///   is no background. Ignored in [`OverlayPlugin::single_text`] mode.
+/// * `border: <color>`: color of a thin border drawn around the box of the
+///   message, not around each letter. Default is no border. Ignored in
+///   [`OverlayPlugin::single_text`] mode.
+/// * `level: <level>`: the [`Level`] of the message, messages below the
+///   [`OverlayFilter::min_level`] are not displayed. Default is
+///   [`Level::Info`].
+/// * `cat: <category>`: a string naming the category of the message,
+///   messages of categories disabled in [`DebugOverlayCategories`] are not
+///   displayed. Default is no category.
+/// * `key: <key>`: a string identifying the message, used instead of the
+///   invocation site. Messages printed with the same key replace each other,
+///   even when printed from different places. Inside a loop, different keys
+///   can be used to display a message per iteration.
+/// * `window: <entity>`: the window on which to display the message. Default
+///   is [`OverlayPlugin::default_window`]. Only taken into account the first
+///   time a message is displayed.
+/// * `target: <name>`: display the message in the viewport of the camera
+///   with the [`OverlayViewport`] of that name, rather than on the whole
+///   window. Default is the whole window, which is also used while no camera
+///   has that name. Ignored in [`OverlayPlugin::single_text`] mode.
+/// * `blink`: pulse the brightness of the message twice a second, for
+///   critical conditions that must not go unnoticed. Ignored in
+///   [`OverlayPlugin::single_text`] mode.
+/// * `blink: <rate>`: like `blink`, pulsing `rate` times per second.
+/// * `beep`: play an audio cue when the message shows up, see
+///   [`OverlayPlugin::beep_sound`]. This requires the `audio` cargo feature,
+///   without it the option is ignored.
+/// * `size: <font size>`: the font size of the message. Default is
+///   [`OverlayPlugin::font_size`]. Like all messages, it is multiplied by
+///   [`DebugOverlayScale`].
+/// * `diff`: when the message text changes, highlight for a second the lines
+///   added in green, and the removed lines in red. Useful with large `{:#?}`
+///   dumps where a single field changes.
+/// * `align: <alignment>`: the [`Alignment`] of the message. Default is
+///   [`DebugOverlayLocation::alignment`].
+/// * `prio: <priority>`: an `i32`, messages of higher priority are displayed
+///   above the others, and are the last ones hidden when they don't all fit
+///   in [`OverlayPlugin::max_visible_lines`] or
+///   [`OverlayPlugin::max_height`]. Default is 0.
+/// * `lines: <n>`: display only the first `n` lines of the message, followed
+///   by a line counting the hidden ones. The whole message is displayed
+///   while hovered by the mouse, or while [`OverlayPlugin::expand_key`] is
+///   held. Default is to display all lines.
+/// * `tail: <n>`: keep the last `n` values printed at this invocation site
+///   (or key), and display them one per line, the newest on top, instead of
+///   replacing the previous value. Each print adds a value, even when it is
+///   identical to the previous one. Ignored with `push`.
+/// * `indent: <level>`: a `u32`, shift the message away from the side of the
+///   screen by `level` times [`OverlayPlugin::indent_width`], to visually
+///   nest related messages. Default is 0, messages in a [`screen_group!`] are
+///   further indented. Ignored in [`OverlayPlugin::single_text`] mode.
+/// * `coalesce: false`: never merge this `push` message with identical ones,
+///   see [`OverlayPlugin::coalesce`].
+/// * `scope: <state>`: clear the message when the state exits, with the
+///   [`ClearOnStateChange`](crate::ClearOnStateChange) plugin of the state
+///   type.
+/// * `clock: <time source>`: the [`TimeSource`] the `sec` timeout counts in.
+///   Default is [`OverlayPlugin::time_source`].
+/// * `pin`: the message doesn't expire and is displayed above the others,
+///   until unpinned with [`OverlayCommands::unpin`] or cleared with
+///   [`screen_clear!`]. It then expires as usual.
+/// * `icon: <icon>`: an [`Icon`] displayed before the message, either a
+///   glyph such as `"⚙"`, or a `Color` for a small square of that color, to
+///   tell categories of messages apart at a glance. Squares are ignored in
+///   [`OverlayPlugin::single_text`] mode. Whether a message has a square is
+///   only taken into account the first time it is displayed.
+/// * `stats`: must be the last option, followed by a label and a value
+///   convertible to `f32` with `as` rather than a format string. Displays the
+///   value with the min, average and max of the last 120 values printed at
+///   this invocation site (or key). Ignores `push`.
 ///
 /// ```rust,no_run
-/// use bevy_debug_text_overlay::{screen_print, OverlayPlugin};
+/// use bevy_debug_text_overlay::{screen_print, Alignment, Level, OverlayPlugin, TimeSource};
 /// use bevy::prelude::Color;
 ///
 /// let x = (13, 3.4, vec![1,2,3,4,5,6,7,8]);
 /// screen_print!("multiline: {x:#?}");
 /// screen_print!(push, "This shows multiple times");
+/// screen_print!(once, "This shows only once");
+/// screen_print!(every: 0.5, "updated twice a second: {:?}", x.2);
+/// for i in 0..3 {
+///     screen_print!(key: format!("item {i}"), "one line per item: {i}");
+/// }
 /// screen_print!(sec: 6.0, "first and second fields: {}, {}", x.0, x.1);
+/// screen_print!(frames: 1, "this frame only: {}", x.0);
 /// screen_print!(col: Color::BLUE, "single line: {x:?}");
 /// screen_print!(sec: 10.0, col: Color::BLUE, "last field: {:?}", x.2);
+/// screen_print!(col: Color::WHITE, bg: Color::RED, "CRITICAL: {x:?}");
+/// screen_print!(border: Color::RED, "watch out: {}", x.0);
+/// screen_print!(alpha: 0.4, "cache hits: {}", x.0);
+/// screen_print!(blink, col: Color::RED, "NaN detected: {}", x.1);
+/// screen_print!(blink: 5, "desync: {}", x.0);
+/// screen_print!(beep, col: Color::RED, "connection lost: {}", x.0);
+/// screen_print!(size: 24, "big: {}", x.0);
+/// screen_print!(diff, "changes: {x:#?}");
+/// screen_print!(lines: 3, "huge dump: {x:#?}");
+/// screen_print!(tail: 5, "last collision: {:?}", x.2);
+/// screen_print!(push, "loading level");
+/// screen_print!(push, indent: 1, "loading meshes: {}", x.0);
+/// screen_print!(align: Alignment::Right, "fps: {:>6.1}", x.1);
+/// screen_print!(prio: 10, col: Color::RED, "player dead: {}", x.0);
+/// screen_print!(level: Level::Trace, "very verbose: {x:?}");
+/// screen_print!(cat: "physics", "velocity: {}", x.1);
+/// screen_print!(target: "player 2", "velocity: {}", x.1);
+/// screen_print!(sys, "printed from: {}", x.0);
+/// screen_print!(clock: TimeSource::Real, "paused at: {}", x.1);
+/// screen_print!(pin, key: "fps", "fps: {}", x.1);
+/// screen_print!(icon: "⚙", "joints: {}", x.0);
+/// screen_print!(icon: Color::GREEN, "path length: {}", x.1);
+/// screen_print!(stats, "frame ms", x.1 * 1000.0);
+/// ```
+///
+/// # Color markup
+///
+/// Parts of a message can be colored by surrounding them with a color tag
+/// and `[/]`. Tags are either basic color names (`red`, `green`, `blue`,
+/// `yellow`, `orange`, `pink`, `purple`, `cyan`, `white`, `gray` and
+/// `black`) or hexadecimal colors such as `#ff8800`. Other brackets are
+/// displayed as is. The history and log only get the text without markup.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_print;
+///
+/// let (hp, max) = (3, 20);
+/// screen_print!("hp: [red]{hp}[/] / {max}");
+/// screen_print!("status: [#00ff00]ok[/], targets: {:?}", [1, 2]);
 /// ```
 #[macro_export]
 macro_rules! screen_print {
-    (push, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl push, sec: 7.0, col: Some($color), $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] push, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .push(true)] $($rest)+)
+    };
+    (@opts [$($opts:tt)*] persist, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .sec(f64::INFINITY)] $($rest)+)
     };
-    (col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl sec: 7.0, col: Some($color), $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] no_prefix, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .no_prefix(true)] $($rest)+)
     };
-    (push, sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl push, sec: $timeout, col: Some($color), $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] frames: $frames:expr, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .frames($frames as u32)] $($rest)+)
     };
-    (sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl sec: $timeout, col: Some($color), $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] blink, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .blink(2.0)] $($rest)+)
     };
-    (push, sec: $timeout:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl push, sec: $timeout, col: None, $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] beep, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .beep(true)] $($rest)+)
     };
-    (sec: $timeout:expr, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl sec: $timeout, col: None, $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] pin, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .pin(true)] $($rest)+)
     };
-    (push, $text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl push, sec: 7.0, col: None, $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] blink: $rate:expr, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .blink($rate as f64)] $($rest)+)
     };
-    ($text:expr $(, $fmt_args:expr)*) => {
-        $crate::screen_print!(@impl sec: 7.0, col: None, $text $(, $fmt_args)*);
+    (@opts [$($opts:tt)*] size: $size:expr, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .size($size as f32)] $($rest)+)
     };
-    (@impl sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        use $crate::{InvocationSiteKey, command_channels};
-        let key = InvocationSiteKey { file: file!(), line: line!(), column: column!() };
-        command_channels().refresh_text(key, || format!($text $(, $fmt_args)*), $timeout as f64, $color);
+    (@opts [$($opts:tt)*] diff, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .diff(true)] $($rest)+)
+    };
+    (@opts [$($opts:tt)*] sys, $($rest:tt)+) => {
+        $crate::screen_print!(@opts [$($opts)* .sys(true)] $($rest)+)
+    };
+    (@opts [$($opts:tt)*] stats, $label:expr, $value:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        command_channels().stats(key, options, $label, $value as f32);
     }};
-    (@impl push, sec: $timeout:expr, col: $color:expr, $text:expr $(, $fmt_args:expr)*) => {{
-        use $crate::{InvocationSiteKey, command_channels};
-        let key = InvocationSiteKey { file: file!(), line: line!(), column: column!() };
-        command_channels().push_text(key, || format!($text $(, $fmt_args)*), $timeout as f64, $color);
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_print [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_print [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $text:expr $(, $fmt_args:expr)*) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let system = {
+            fn f() {}
+            ::std::any::type_name_of_val(&f)
+        };
+        let options = PrintOptions::default().system(system)$($opts)*.cache({
+            static CACHE: $crate::PrintCache = $crate::PrintCache::new();
+            &CACHE
+        });
+        command_channels().print_fmt(key, options, format_args!($text $(, $fmt_args)*));
     }};
+    ($($args:tt)+) => {
+        $crate::screen_print!(@opts [] $($args)+)
+    };
 }
 
-/// Specific call site of [`screen_print!`].
+/// Prefix options shared by the macros of this crate.
 ///
-/// Used to identify where a message is coming from and replacing it on screen
-/// when updated.
-#[derive(Hash, PartialEq, Eq)]
+/// Appends the [`PrintOptions`] method call of the first option to `[..]`,
+/// then hands the rest of the arguments back to the `$mac` macro. Options
+/// with a value are calls to the method of the same name.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __screen_opts {
+    ($mac:ident [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($opts)* .once({
+            static PRINTED: ::std::sync::atomic::AtomicBool =
+                ::std::sync::atomic::AtomicBool::new(false);
+            &PRINTED
+        })] $($rest)+)
+    };
+    ($mac:ident [$($opts:tt)*] sec: $timeout:expr, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($opts)* .sec($timeout as f64)] $($rest)+)
+    };
+    ($mac:ident [$($opts:tt)*] every: $interval:expr, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($opts)* .every($interval as f64, {
+            static LAST_PRINT: ::std::sync::atomic::AtomicU64 =
+                ::std::sync::atomic::AtomicU64::new(0);
+            &LAST_PRINT
+        })] $($rest)+)
+    };
+    ($mac:ident [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::$mac!(@opts [$($opts)* .$opt($value)] $($rest)+)
+    };
+}
+
+/// Options of a [`screen_print!`] invocation, each method corresponds to a
+/// prefix argument of the macro.
 #[doc(hidden)]
-pub struct InvocationSiteKey {
-    pub file: &'static str,
-    pub line: u32,
-    pub column: u32,
+#[derive(Clone)]
+pub struct PrintOptions {
+    push: bool,
+    timeout: f64,
+    color: Option<Color>,
+    level: Level,
+    at: Option<WorldLabel>,
+    window: Option<Entity>,
+    /// Name of the [`OverlayViewport`] to display the message in.
+    target: Option<Cow<'static, str>>,
+    once: Option<&'static AtomicBool>,
+    every: Option<(f64, &'static AtomicU64)>,
+    key: Option<Cow<'static, str>>,
+    no_prefix: bool,
+    bg: Option<Color>,
+    border: Option<Color>,
+    /// Opacity of the message, before fading out.
+    alpha: f32,
+    cat: Option<Cow<'static, str>>,
+    cache: Option<&'static PrintCache>,
+    /// Use [`OverlayPlugin::table_font`], set by [`screen_table!`].
+    monospace: bool,
+    logged: bool,
+    /// How many times per second the message pulses.
+    blink: Option<f64>,
+    /// Play an audio cue when the message shows up.
+    beep: bool,
+    /// Keep the message on screen, above the others, until unpinned.
+    pin: bool,
+    /// Font size, before [`DebugOverlayScale`] is applied.
+    size: Option<f32>,
+    /// Whether to display the message, and whether it is currently
+    /// displayed, see [`screen_print_if!`].
+    only_if: Option<(bool, &'static AtomicBool)>,
+    /// Flash the message when its text changes, set by [`screen_watch!`].
+    watch: bool,
+    /// Highlight the lines that changed since the previous text.
+    diff: bool,
+    align: Option<Alignment>,
+    prio: i32,
+    /// For how many frames the message is displayed, instead of `timeout`.
+    frames: Option<u32>,
+    /// How many lines of the message are displayed when not expanded.
+    lines: Option<usize>,
+    /// How many of the last values printed are displayed.
+    tail: Option<usize>,
+    /// Nesting level, see [`OverlayPlugin::indent_width`].
+    indent: u32,
+    /// Whether identical consecutive `push` messages can be merged, see
+    /// [`OverlayPlugin::coalesce`].
+    coalesce: bool,
+    /// Clock the timeout counts in, see [`OverlayPlugin::time_source`].
+    clock: Option<TimeSource>,
+    icon: Option<Icon>,
+    /// Name of the function that printed the message, if known.
+    system: Option<&'static str>,
+    /// Use `system` as prefix, whatever the [`OverlayPlugin::prefix`].
+    sys: bool,
+    /// State the message is cleared on exit of, see
+    /// [`ClearOnStateChange`](crate::ClearOnStateChange).
+    scope: Option<StateScope>,
 }
-impl fmt::Display for InvocationSiteKey {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}:{}:{}]", self.file, self.line, self.column)
+impl Default for PrintOptions {
+    fn default() -> Self {
+        // Messages printed in a `screen_group!` are nested in it.
+        let (indent, color) = GROUPS.with(|groups| {
+            let groups = groups.borrow();
+            (groups.len() as u32, groups.last().copied().flatten())
+        });
+        Self {
+            push: false,
+            timeout: 7.0,
+            color,
+            level: Level::default(),
+            at: None,
+            window: None,
+            target: None,
+            once: None,
+            every: None,
+            key: None,
+            no_prefix: false,
+            bg: None,
+            border: None,
+            alpha: 1.0,
+            cat: None,
+            cache: None,
+            monospace: false,
+            logged: false,
+            blink: None,
+            beep: false,
+            pin: false,
+            size: None,
+            only_if: None,
+            watch: false,
+            diff: false,
+            align: None,
+            prio: 0,
+            frames: None,
+            lines: None,
+            tail: None,
+            indent,
+            coalesce: true,
+            clock: None,
+            icon: None,
+            system: None,
+            sys: false,
+            scope: None,
+        }
     }
 }
-
-enum Command {
-    /// Update in place or add new message already printed at given site.
-    Refresh {
-        key: InvocationSiteKey,
-        color: Option<Color>,
-        text: String,
-        timeout: f64,
-    },
-    /// Always add the message to the screen.
-    Push {
-        color: Option<Color>,
-        text: String,
-        timeout: f64,
-    },
+impl PrintOptions {
+    pub fn push(self, push: bool) -> Self {
+        Self { push, ..self }
+    }
+    pub fn sec(self, timeout: f64) -> Self {
+        Self { timeout, ..self }
+    }
+    pub fn col(self, color: Color) -> Self {
+        Self { color: Some(color), ..self }
+    }
+    pub fn level(self, level: Level) -> Self {
+        Self { level, ..self }
+    }
+    pub fn at(self, position: impl WorldPosition) -> Self {
+        let at = Some(WorldLabel::Position(position.world_position()));
+        Self { at, ..self }
+    }
+    pub fn entity(self, entity: Entity) -> Self {
+        Self { at: Some(WorldLabel::Entity(entity)), ..self }
+    }
+    pub fn window(self, window: Entity) -> Self {
+        Self { window: Some(window), ..self }
+    }
+    pub fn target(self, viewport: impl Into<Cow<'static, str>>) -> Self {
+        Self { target: Some(viewport.into()), ..self }
+    }
+    /// `printed` is set the first time the message is printed, and prevents
+    /// it from being printed again.
+    pub fn once(self, printed: &'static AtomicBool) -> Self {
+        Self { once: Some(printed), ..self }
+    }
+    /// `last_print` is the time of the last print of this message, in
+    /// microseconds since `START` plus one, 0 if it was never printed.
+    pub fn every(self, interval: f64, last_print: &'static AtomicU64) -> Self {
+        Self { every: Some((interval, last_print)), ..self }
+    }
+    pub fn key(self, key: impl Into<Cow<'static, str>>) -> Self {
+        Self { key: Some(key.into()), ..self }
+    }
+    pub fn no_prefix(self, no_prefix: bool) -> Self {
+        Self { no_prefix, ..self }
+    }
+    pub fn bg(self, color: Color) -> Self {
+        Self { bg: Some(color), ..self }
+    }
+    pub fn border(self, color: Color) -> Self {
+        Self { border: Some(color), ..self }
+    }
+    pub fn cat(self, category: impl Into<Cow<'static, str>>) -> Self {
+        Self { cat: Some(category.into()), ..self }
+    }
+    pub fn beep(self, beep: bool) -> Self {
+        Self { beep, ..self }
+    }
+    pub fn pin(self, pin: bool) -> Self {
+        Self { pin, ..self }
+    }
+    pub fn blink(self, rate: f64) -> Self {
+        Self { blink: Some(rate), ..self }
+    }
+    pub fn size(self, size: f32) -> Self {
+        Self { size: Some(size), ..self }
+    }
+    /// `shown` is set while the message is displayed, so that it is only
+    /// cleared once `condition` becomes false.
+    pub fn only_if(self, condition: bool, shown: &'static AtomicBool) -> Self {
+        Self { only_if: Some((condition, shown)), ..self }
+    }
+    pub fn watch(self, watch: bool) -> Self {
+        Self { watch, ..self }
+    }
+    pub fn diff(self, diff: bool) -> Self {
+        Self { diff, ..self }
+    }
+    pub fn align(self, alignment: Alignment) -> Self {
+        Self { align: Some(alignment), ..self }
+    }
+    pub fn prio(self, prio: i32) -> Self {
+        Self { prio, ..self }
+    }
+    /// Set the name of the function printing the message, from the path of
+    /// a function item declared inside it.
+    pub fn system(self, item_path: &'static str) -> Self {
+        let path = item_path.strip_suffix("::f").unwrap_or(item_path);
+        let path = path.trim_end_matches("::{{closure}}");
+        let system = path.rsplit("::").next();
+        Self { system, ..self }
+    }
+    pub fn sys(self, sys: bool) -> Self {
+        Self { sys, ..self }
+    }
+    pub fn coalesce(self, coalesce: bool) -> Self {
+        Self { coalesce, ..self }
+    }
+    pub fn scope<S: States>(self, state: S) -> Self {
+        Self { scope: Some(StateScope::of(&state)), ..self }
+    }
+    pub fn clock(self, clock: TimeSource) -> Self {
+        Self { clock: Some(clock), ..self }
+    }
+    pub fn icon(self, icon: impl Into<Icon>) -> Self {
+        Self { icon: Some(icon.into()), ..self }
+    }
+    pub fn lines(self, lines: usize) -> Self {
+        Self { lines: Some(lines), ..self }
+    }
+    pub fn tail(self, tail: usize) -> Self {
+        Self { tail: Some(tail), ..self }
+    }
+    pub fn alpha(self, alpha: f32) -> Self {
+        Self { alpha, ..self }
+    }
+    /// Added to the indent of the enclosing [`screen_group!`], if any.
+    pub fn indent(self, indent: u32) -> Self {
+        Self { indent: self.indent + indent, ..self }
+    }
+    pub fn frames(self, frames: u32) -> Self {
+        Self {
+            frames: Some(frames),
+            timeout: f64::INFINITY,
+            ..self
+        }
+    }
+    /// Like [`Self::only_if`] with the negated `condition`, red by default.
+    pub fn assertion(self, condition: bool, shown: &'static AtomicBool) -> Self {
+        Self {
+            color: Some(Color::RED),
+            ..self.only_if(!condition, shown)
+        }
+    }
+    /// The message comes from the log, so it is not logged again by
+    /// [`OverlayPlugin::mirror_to_log`].
+    pub fn logged(self, logged: bool) -> Self {
+        Self { logged, ..self }
+    }
+    /// `cache` remembers the last message sent from this invocation site, so
+    /// that identical messages are not formatted and sent again.
+    pub fn cache(self, cache: &'static PrintCache) -> Self {
+        Self { cache: Some(cache), ..self }
+    }
+    /// Whether the `once` and `every` options prevent printing now.
+    fn throttled(&self) -> bool {
+        if let Some(printed) = self.once {
+            if printed.swap(true, Ordering::Relaxed) {
+                return true;
+            }
+        }
+        if let Some((interval, last_print)) = self.every {
+            let now = now_micros();
+            let last = last_print.load(Ordering::Relaxed);
+            // Another thread may have printed after `now` was read.
+            if last != 0 && (now.saturating_sub(last) as f64) < interval * 1_000_000.0 {
+                return true;
+            }
+            // Only one of the threads printing from this site at once prints.
+            let swapped =
+                last_print.compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed);
+            if swapped.is_err() {
+                return true;
+            }
+        }
+        false
+    }
+    /// Feed everything affecting how the message is displayed to `hasher`.
+    fn hash_display(&self, hasher: &mut impl Hasher) {
+        self.timeout.to_bits().hash(hasher);
+        self.level.hash(hasher);
+        self.window.hash(hasher);
+        self.target.hash(hasher);
+        self.no_prefix.hash(hasher);
+        self.cat.hash(hasher);
+        self.blink.map(f64::to_bits).hash(hasher);
+        self.beep.hash(hasher);
+        self.pin.hash(hasher);
+        self.size.map(f32::to_bits).hash(hasher);
+        self.diff.hash(hasher);
+        self.align.hash(hasher);
+        self.prio.hash(hasher);
+        self.frames.hash(hasher);
+        self.lines.hash(hasher);
+        self.tail.hash(hasher);
+        self.indent.hash(hasher);
+        self.alpha.to_bits().hash(hasher);
+        self.coalesce.hash(hasher);
+        self.clock.hash(hasher);
+        self.scope.hash(hasher);
+        match &self.icon {
+            None => 0_u8.hash(hasher),
+            Some(Icon::Glyph(glyph)) => {
+                1_u8.hash(hasher);
+                glyph.hash(hasher);
+            }
+            Some(Icon::Square(color)) => {
+                2_u8.hash(hasher);
+                color.as_rgba_u32().hash(hasher);
+            }
+        }
+        for color in [self.color, self.bg, self.border] {
+            color.map(|c| c.as_rgba_u32()).hash(hasher);
+        }
+        match self.at {
+            None => 0_u8.hash(hasher),
+            Some(WorldLabel::Position(position)) => {
+                1_u8.hash(hasher);
+                position.to_array().map(f32::to_bits).hash(hasher);
+            }
+            Some(WorldLabel::Entity(entity)) => {
+                2_u8.hash(hasher);
+                entity.hash(hasher);
+            }
+        }
+    }
 }
 
-/// Queue text to display on the screen
+/// The last message sent from a [`screen_print!`] invocation site.
+///
+/// A message identical to the last one sent is not formatted again, only its
+/// timeout and last update time are refreshed, unless half of its timeout
+/// elapsed, so that it is displayed again if it was cleared in between. Such
+/// repeated messages are not part of [`OverlayRecording`]s.
 #[doc(hidden)]
-pub struct CommandChannels {
-    sender: SyncSender<Command>,
-    receiver: Mutex<Receiver<Command>>,
+pub struct PrintCache {
+    /// Hash of the text, [`PrintOptions`], key and
+    /// [`CommandChannels::generation`] of the last sent message.
+    hash: AtomicU64,
+    /// When the last message was sent, in microseconds since `START` plus
+    /// one, 0 if it was never sent.
+    sent: AtomicU64,
 }
-impl CommandChannels {
-    // POSSIBLE LEAD: consider providing an API so that at_interval (from demo.rs) can
-    // be used without too much hassle
-    pub fn refresh_text(
-        &self,
-        key: InvocationSiteKey,
-        text: impl FnOnce() -> String,
-        timeout: f64,
-        color: Option<Color>,
-    ) {
-        let text = format!("{key} {}\n", text());
-        let cmd = Command::Refresh { text, key, color, timeout };
-        let sent = self.sender.try_send(cmd).is_ok();
-        if !sent {
-            error!("Number of debug messages sent in one frame exceeds limit of {MAX_LINES}");
-        }
+impl PrintCache {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self { hash: AtomicU64::new(0), sent: AtomicU64::new(0) }
     }
-    pub fn push_text(
+    /// Whether the message is the same as the last one sent, otherwise
+    /// remember it as the last one sent.
+    fn unchanged(
         &self,
-        key: InvocationSiteKey,
-        text: impl FnOnce() -> String,
-        timeout: f64,
-        color: Option<Color>,
-    ) {
-        let text = format!("{key} {}\n", text());
-        let cmd = Command::Push { text, color, timeout };
-        let sent = self.sender.try_send(cmd).is_ok();
-        if !sent {
-            error!("Number of debug messages sent in one frame exceeds limit of {MAX_LINES}");
+        generation: u64,
+        key: &InvocationSiteKey,
+        options: &PrintOptions,
+        text: fmt::Arguments,
+    ) -> bool {
+        // Each print counts the frames of the message from the start again,
+        // or adds a value to its tail.
+        if options.frames.is_some() || options.tail.is_some() {
+            return false;
+        }
+        let mut hasher = HashWriter(DefaultHasher::new());
+        // Formatting into a hasher never fails.
+        let _ = hasher.write_fmt(text);
+        let mut hasher = hasher.0;
+        generation.hash(&mut hasher);
+        key.hash(&mut hasher);
+        options.hash_display(&mut hasher);
+        let hash = hasher.finish();
+
+        let now = now_micros();
+        let sent = self.sent.load(Ordering::Relaxed);
+        // Another thread may have sent after `now` was read.
+        let elapsed = now.saturating_sub(sent) as f64;
+        let fresh = sent != 0 && elapsed < options.timeout * 500_000.0;
+        if fresh && self.hash.load(Ordering::Relaxed) == hash {
+            return true;
         }
+        self.hash.store(hash, Ordering::Relaxed);
+        self.sent.store(now, Ordering::Relaxed);
+        false
     }
 }
 
-#[derive(Component)]
-struct Message {
-    expiration: f64,
-}
-impl Message {
-    fn new(expiration: f64) -> Self {
-        Self { expiration }
+/// Hashes formatted text without allocating it.
+struct HashWriter<H>(H);
+impl<H: Hasher> fmt::Write for HashWriter<H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
     }
 }
 
-#[derive(Resource)]
-struct Options {
-    font_size: f32,
-    color: Color,
+/// A position in the game world, see [`screen_print_at!`].
+#[doc(hidden)]
+pub trait WorldPosition {
+    fn world_position(self) -> Vec3;
 }
-impl<'a> From<&'a OverlayPlugin> for Options {
-    fn from(plugin: &'a OverlayPlugin) -> Self {
-        Self {
-            color: plugin.fallback_color,
-            font_size: plugin.font_size,
-        }
+impl WorldPosition for Vec3 {
+    fn world_position(self) -> Vec3 {
+        self
+    }
+}
+impl WorldPosition for Vec2 {
+    fn world_position(self) -> Vec3 {
+        self.extend(0.0)
     }
 }
 
-#[derive(Copy, Clone)]
-struct PushEntry {
-    entity: Entity,
-    expired: f64,
+/// Displayed before a message, see the `icon` option of [`screen_print!`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Icon {
+    /// Text such as an emoji, the font must have a glyph for it. The default
+    /// font only has ASCII characters, see [`OverlayPlugin::font`].
+    Glyph(Cow<'static, str>),
+    /// A small square of that color.
+    Square(Color),
 }
-#[derive(Default)]
-struct PushList(Vec<PushEntry>);
-impl PushList {
-    fn new_or_allocate(
-        &mut self,
-        spawn_new: impl FnOnce() -> Entity,
-        current: f64,
-        timeout: f64,
-    ) -> Option<Entity> {
-        let free_existing = self.0.iter_mut().find(|entry| entry.expired < current);
-        let ret = free_existing.as_ref().map(|entry| entry.entity);
-        let new_entry = || PushEntry { entity: spawn_new(), expired: current + timeout };
-        match free_existing {
-            Some(to_update) => to_update.expired = current + timeout,
-            None => self.0.push(new_entry()),
-        }
-        ret
+impl From<&'static str> for Icon {
+    fn from(glyph: &'static str) -> Self {
+        Self::Glyph(glyph.into())
     }
 }
-fn update_messages_as_per_commands(
-    mut messages: Query<(&mut Text, &mut Message)>,
-    mut key_entities: Local<HashMap<InvocationSiteKey, Entity>>,
-    mut push_entities: Local<PushList>,
-    mut cmds: Commands,
-    time: Res<Time>,
-    options: Res<Options>,
-) {
-    let channels = command_channels();
-    let text_style = |color| TextStyle {
-        color,
-        font_size: options.font_size,
-        ..Default::default()
-    };
-    let current_time = time.elapsed_seconds_f64();
-    let mut spawn_new = |text, color, timeout| {
-        let style = Style { position_type: PositionType::Absolute, ..default() };
-        cmds.spawn((
-            TextBundle::from_section(text, text_style(color)).with_style(style),
-            Message::new(timeout + current_time),
-        ))
-        .insert(Visibility::Hidden)
-        .id()
-    };
-    let mut update_message = |entity, new_text, new_color, timeout| {
-        // FIXME: this can skip requests if the scheduling acts up and we
-        // get two consecutive message from the same `screen_print!`
-        if let Ok((mut ui_text, mut message)) = messages.get_mut(entity) {
-            message.expiration = timeout + current_time;
-            if ui_text.sections[0].style.color != new_color {
-                ui_text.sections[0].style.color = new_color;
-            }
-            if ui_text.sections[0].value != new_text {
-                ui_text.sections[0].value = new_text;
-            }
+impl From<String> for Icon {
+    fn from(glyph: String) -> Self {
+        Self::Glyph(glyph.into())
+    }
+}
+impl From<char> for Icon {
+    fn from(glyph: char) -> Self {
+        Self::Glyph(glyph.to_string().into())
+    }
+}
+impl From<Color> for Icon {
+    fn from(color: Color) -> Self {
+        Self::Square(color)
+    }
+}
+impl Icon {
+    /// The color of the square icon of `print`, if any.
+    fn square(print: &PrintOptions) -> Option<Color> {
+        match print.icon {
+            Some(Icon::Square(color)) => Some(color),
+            _ => None,
         }
-    };
-    let iterator = channels.receiver.lock().unwrap();
-    for message in iterator.try_iter() {
-        match message {
-            Command::Refresh { key, color, text, timeout } => {
-                let color = color.unwrap_or(options.color);
-                if let Some(&entity) = key_entities.get(&key) {
-                    update_message(entity, text, color, timeout);
-                } else {
-                    let entity = spawn_new(text, color, timeout);
-                    key_entities.insert(key, entity);
-                }
-            }
-            Command::Push { color, text, timeout } => {
-                let color = color.unwrap_or(options.color);
-                let spawn = || spawn_new(text.clone(), color, timeout);
-                if let Some(entity) = push_entities.new_or_allocate(spawn, current_time, timeout) {
-                    update_message(entity, text, color, timeout);
-                }
-            }
+    }
+    /// `style` of a message node with a square icon, drawn as its left border,
+    /// for a message of `font_size`.
+    fn square_style(style: Style, font_size: f32) -> Style {
+        Style {
+            border: UiRect::left(Val::Px(font_size / 2.0)),
+            padding: UiRect::left(Val::Px(font_size / 3.0)),
+            ..style
         }
     }
 }
 
-fn layout_messages(
-    mut messages: Query<(Entity, &mut Style, &mut Visibility, &Node, &Message)>,
-    mut line_sizes: Local<Blocks<Entity, f32>>,
-    // position: Res<crate::DebugOverlayLocation>,
-    time: Res<Time>,
-) {
-    use Visibility::{Hidden, Visible};
-    for (entity, mut style, mut vis, node, message) in messages.iter_mut() {
-        let size = node.size();
-        let is_expired = message.expiration < time.elapsed_seconds_f64();
-        let is_visible = *vis == Visible;
-        if is_visible == is_expired {
-            *vis = if is_visible { Hidden } else { Visible };
-            if !is_expired {
-                let offset = line_sizes.insert_size(entity, size.y);
-                style.top = Val::Px(offset);
-                style.left = Val::Px(0.0);
-            } else {
-                line_sizes.remove(entity);
-            }
+/// Display text at the screen position of a point in the world.
+///
+/// The first argument is the position, either a `Vec2` or a `Vec3`, the rest
+/// are the same as [`screen_print!`]. The position is projected through the
+/// active camera with the lowest order every frame, so the label stays on the
+/// point when the camera moves.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_print_at;
+/// use bevy::prelude::{Color, Vec2, Vec3};
+///
+/// let node = Vec2::new(10.0, 4.0);
+/// screen_print_at!(node, "pathfinding node");
+/// screen_print_at!(Vec3::ZERO, col: Color::RED, "origin");
+/// ```
+#[macro_export]
+macro_rules! screen_print_at {
+    ($position:expr, $($args:tt)+) => {
+        $crate::screen_print!(@opts [.at($position)] $($args)+)
+    };
+}
+
+/// Display text on top of an entity.
+///
+/// The first argument is the `Entity`, the rest are the same as
+/// [`screen_print!`]. Each entity gets its own label, even when printed from
+/// the same invocation site. The label follows the entity's `GlobalTransform`
+/// and disappears when the entity is despawned.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_print_entity;
+/// use bevy::prelude::{Entity, Query};
+///
+/// # struct Health(u32);
+/// # impl bevy::prelude::Component for Health {
+/// #     type Storage = bevy::ecs::component::TableStorage;
+/// # }
+/// fn show_health(query: Query<(Entity, &Health)>) {
+///     for (entity, health) in &query {
+///         screen_print_entity!(entity, "hp: {}", health.0);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! screen_print_entity {
+    ($entity:expr, $($args:tt)+) => {
+        $crate::screen_print!(@opts [.entity($entity)] $($args)+)
+    };
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+impl Level {
+    /// Color of messages of this level coming from logging integrations.
+    pub(crate) fn color(self) -> Color {
+        match self {
+            Level::Error => Color::RED,
+            Level::Warn => Color::ORANGE,
+            Level::Info => Color::WHITE,
+            Level::Debug => Color::CYAN,
+            Level::Trace => Color::GRAY,
         }
     }
 }
 
-/// The text overlay plugin, you must add this plugin for the [`screen_print!`] macro
-/// to work.
+/// Display a graph of the last values of a number, followed by its current
+/// value.
 ///
-/// You can manage some of the text properties by setting the fields of the
-/// plugin.
-pub struct OverlayPlugin {
-    /// The color to use when none are specified in [`screen_print!`], by
-    /// default it is yellow.
-    pub fallback_color: Color,
-    /// The size of the message to display on screen, by default it is 13.0
-    pub font_size: f32,
+/// The first argument is the label of the graph, the second is the value,
+/// convertible to `f32` with `as`. The same prefix options as
+/// [`screen_print!`] are accepted, except `push`.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_graph;
+/// use bevy::prelude::Color;
+///
+/// let speed = 4.2;
+/// screen_graph!("speed", speed);
+/// screen_graph!(col: Color::GREEN, "enemies", 37);
+/// ```
+#[macro_export]
+macro_rules! screen_graph {
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_graph [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $label:expr, $value:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        command_channels().sample(key, options, $label, $value as f32);
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_graph!(@opts [] $($args)+)
+    };
 }
-impl Default for OverlayPlugin {
-    fn default() -> Self {
-        Self { fallback_color: Color::YELLOW, font_size: 13.0 }
-    }
+
+/// Display a histogram of the last values of a number.
+///
+/// The first argument is the label of the histogram, the second is the
+/// value, convertible to `f32` with `as`. The last 256 values are split in
+/// `buckets` ranges of equal width between their min and max, 16 by default,
+/// each range is drawn as a bar as high as the number of values it contains.
+/// This shows spikes that an average hides. The same prefix options as
+/// [`screen_print!`] are accepted, except `push`.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_histogram;
+///
+/// let frame_ms = 16.6;
+/// screen_histogram!("frame_ms", frame_ms);
+/// screen_histogram!("frame_ms", frame_ms, buckets: 20);
+/// ```
+#[macro_export]
+macro_rules! screen_histogram {
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_histogram [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $label:expr, $value:expr, buckets: $buckets:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        command_channels().histogram(key, options, $label, $value as f32, $buckets as u32);
+    }};
+    (@opts [$($opts:tt)*] $label:expr, $value:expr $(,)?) => {
+        $crate::screen_histogram!(@opts [$($opts)*] $label, $value, buckets: 16)
+    };
+    ($($args:tt)+) => {
+        $crate::screen_histogram!(@opts [] $($args)+)
+    };
 }
 
-impl Plugin for OverlayPlugin {
-    fn build(&self, app: &mut App) {
-        app.insert_resource::<Options>(self.into()).add_systems(
-            Update,
-            (update_messages_as_per_commands, layout_messages).chain_ignore_deferred(),
+/// Display rows of values as a table with aligned columns.
+///
+/// The first argument is the header, the second the rows. The header is any
+/// iterator of values implementing `Display`, the rows are an iterator of
+/// such iterators. Each column is as wide as its widest cell, tables are
+/// displayed using [`OverlayPlugin::table_font`], so that columns stay
+/// aligned. The same prefix options as [`screen_print!`] are accepted,
+/// except `push`. Tables are displayed without prefix, unless
+/// `no_prefix: false` is specified.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_table;
+/// use bevy::prelude::Color;
+///
+/// let enemies = [("goblin", 12, 3.5), ("troll", 120, 1.25)];
+/// let rows = enemies.iter().map(|(name, hp, speed)| {
+///     [name.to_string(), hp.to_string(), format!("{speed:.1}")]
+/// });
+/// screen_table!(["name", "hp", "speed"], rows);
+/// screen_table!(col: Color::GREEN, ["key", "value"], [["a", "1"], ["b", "2"]]);
+/// ```
+#[macro_export]
+macro_rules! screen_table {
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_table [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_table [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $header:expr, $rows:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default().no_prefix(true)$($opts)*;
+        command_channels().table(key, options, $header, $rows);
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_table!(@opts [] $($args)+)
+    };
+}
+
+/// Display a labelled horizontal bar filled according to a value in a range.
+///
+/// The first argument is the label, the second the range of the value, with
+/// its start being an empty bar and its end a full bar, the third is the
+/// value, convertible to `f32` with `as`. The same prefix options as
+/// [`screen_print!`] are accepted, except `push`. The bar is replaced each
+/// time this invocation site is reached, like other messages.
+///
+/// In [`OverlayPlugin::single_text`] mode and for world labels, the bar is
+/// drawn with text characters.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_progress;
+/// use bevy::prelude::Color;
+///
+/// let (loaded, total) = (12, 40);
+/// screen_progress!("loading", 0..=total, loaded);
+/// screen_progress!(col: Color::RED, "health", 0.0..=100.0, 42.5);
+/// ```
+#[macro_export]
+macro_rules! screen_progress {
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_progress [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_progress [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $label:expr, $range:expr, $value:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        let range = $range;
+        let range = (*range.start() as f32)..=(*range.end() as f32);
+        command_channels().progress(key, options, $label, range, $value as f32);
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_progress!(@opts [] $($args)+)
+    };
+}
+
+/// Display a message while `condition` is true, and remove it from the screen
+/// once `condition` becomes false.
+///
+/// The arguments after the condition are the same as [`screen_print!`], the
+/// message is only formatted when displayed. Messages printed with `push`
+/// are not removed.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_print_if;
+///
+/// let speed = 42.0_f32;
+/// screen_print_if!(speed > 40.0, "over the speed limit: {speed}");
+/// ```
+#[macro_export]
+macro_rules! screen_print_if {
+    ($condition:expr, $($args:tt)+) => {
+        $crate::screen_print!(@opts [.only_if($condition, {
+            static SHOWN: ::std::sync::atomic::AtomicBool =
+                ::std::sync::atomic::AtomicBool::new(false);
+            &SHOWN
+        })] $($args)+)
+    };
+}
+
+/// Display a message, red by default, while `condition` is false, and remove
+/// it from the screen once `condition` holds again.
+///
+/// The arguments after the condition are the same as [`screen_print!`], when
+/// there are none, the condition itself is displayed.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_assert;
+///
+/// let (hp, max) = (30, 20);
+/// screen_assert!(hp <= max, "hp ({hp}) is above max ({max})");
+/// screen_assert!(hp >= 0);
+/// ```
+#[macro_export]
+macro_rules! screen_assert {
+    ($condition:expr, $($args:tt)+) => {
+        $crate::screen_print!(@opts [.assertion($condition, {
+            static SHOWN: ::std::sync::atomic::AtomicBool =
+                ::std::sync::atomic::AtomicBool::new(false);
+            &SHOWN
+        })] $($args)+)
+    };
+    ($condition:expr $(,)?) => {
+        $crate::screen_assert!($condition, "assertion failed: {}", stringify!($condition))
+    };
+}
+
+/// Display `expr = value` using the `Debug` implementation of the value, the
+/// message flashes each time the value changes.
+///
+/// The same prefix options as [`screen_print!`] are accepted, except `push`,
+/// `persist` and `no_prefix`.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_watch;
+///
+/// let velocity = [1.0, 0.5];
+/// screen_watch!(velocity);
+/// screen_watch!(sec: 30, velocity[0] * 2.0);
+/// ```
+#[macro_export]
+macro_rules! screen_watch {
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_watch [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_watch [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $watched:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*.watch(true).cache({
+            static CACHE: $crate::PrintCache = $crate::PrintCache::new();
+            &CACHE
+        });
+        let text = format_args!("{} = {:?}", stringify!($watched), $watched);
+        command_channels().print_fmt(key, options, text);
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_watch!(@opts [] $($args)+)
+    };
+}
+
+/// Display how long the rest of the current scope takes to run.
+///
+/// Returns a [`ScreenTimer`] guard, when it is dropped, the time elapsed
+/// since its creation is displayed, along with the minimum, average and
+/// maximum durations measured at this invocation site. The argument is the
+/// label of the message, the same prefix options as [`screen_print!`] are
+/// accepted, except `push`.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_timer;
+///
+/// fn find_path() {
+///     let _timer = screen_timer!("pathfinding");
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! screen_timer {
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_timer [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_timer [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $label:expr $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, ScreenTimer, TimerStats};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        static STATS: TimerStats = TimerStats::new();
+        ScreenTimer::new(key, options, $label, &STATS)
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_timer!(@opts [] $($args)+)
+    };
+}
+
+/// Durations measured at a [`screen_timer!`] invocation site.
+#[doc(hidden)]
+pub struct TimerStats(Mutex<TimerTotals>);
+
+#[derive(Clone, Copy)]
+struct TimerTotals {
+    count: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+impl TimerStats {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        let totals = TimerTotals {
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        };
+        Self(Mutex::new(totals))
+    }
+    /// Add `elapsed` to the measured durations, returning the updated totals.
+    fn record(&self, elapsed: Duration) -> TimerTotals {
+        // A panic while holding the lock can't leave the totals inconsistent.
+        let mut totals = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        totals.count = totals.count.saturating_add(1);
+        totals.total = totals.total.saturating_add(elapsed);
+        totals.min = totals.min.min(elapsed);
+        totals.max = totals.max.max(elapsed);
+        *totals
+    }
+}
+
+/// Measures the time until it is dropped, see [`screen_timer!`].
+#[must_use = "The duration is measured until the timer is dropped"]
+pub struct ScreenTimer {
+    key: InvocationSiteKey,
+    options: PrintOptions,
+    label: String,
+    start: Instant,
+    stats: &'static TimerStats,
+}
+impl ScreenTimer {
+    #[doc(hidden)]
+    pub fn new(
+        key: InvocationSiteKey,
+        options: PrintOptions,
+        label: impl fmt::Display,
+        stats: &'static TimerStats,
+    ) -> Self {
+        let options = PrintOptions { push: false, ..options };
+        let label = label.to_string();
+        Self { key, options, label, stats, start: Instant::now() }
+    }
+}
+impl Drop for ScreenTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let TimerTotals { count, total, min, max } = self.stats.record(elapsed);
+        let average = total / count;
+        let (key, options) = (self.key.clone(), mem::take(&mut self.options));
+        command_channels().print(key, options, || {
+            let label = &self.label;
+            format!("{label}: {elapsed:.2?} (min {min:.2?}, avg {average:.2?}, max {max:.2?})")
+        });
+    }
+}
+
+/// Group the messages printed in a block under a header.
+///
+/// The arguments are the header text and the block, whose value is
+/// returned. Messages printed in the block, by any of the macros of this
+/// crate, are indented one level further than the header, see
+/// [`OverlayPlugin::indent_width`], and take the color of the header unless
+/// they have a `col` option. The same prefix options as [`screen_print!`]
+/// are accepted before the header, except `push`. Groups can be nested.
+/// Only the messages printed on the thread running the block are grouped.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::{screen_group, screen_print};
+/// use bevy::prelude::Color;
+///
+/// let (target, path) = ("player", vec![(1, 2), (3, 4)]);
+/// screen_group!(col: Color::ORANGE, "AI", {
+///     screen_print!("target: {target}");
+///     screen_print!("path: {path:?}");
+/// });
+/// ```
+#[macro_export]
+macro_rules! screen_group {
+    (@opts [$($opts:tt)*] once, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_group [$($opts)*] once, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_group [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $header:expr, $body:block $(,)?) => {{
+        use $crate::{InvocationSiteKey, PrintOptions, ScreenGroup};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        let _group = ScreenGroup::open(key, options, $header);
+        $body
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_group!(@opts [] $($args)+)
+    };
+}
+
+thread_local! {
+    /// Color of the [`screen_group!`]s being run on this thread, innermost
+    /// last, used as defaults by [`PrintOptions::default`].
+    static GROUPS: RefCell<Vec<Option<Color>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Group opened by [`screen_group!`], closed when dropped.
+#[doc(hidden)]
+#[must_use = "The group is closed when dropped"]
+pub struct ScreenGroup(());
+impl ScreenGroup {
+    /// Print the `header` and group the following messages under it.
+    pub fn open(key: InvocationSiteKey, options: PrintOptions, header: impl fmt::Display) -> Self {
+        let options = PrintOptions { push: false, ..options };
+        let color = options.color;
+        command_channels().print(key, options, || header.to_string());
+        GROUPS.with(|groups| groups.borrow_mut().push(color));
+        ScreenGroup(())
+    }
+}
+impl Drop for ScreenGroup {
+    fn drop(&mut self) {
+        GROUPS.with(|groups| groups.borrow_mut().pop());
+    }
+}
+
+/// Count how many times this invocation site is reached, and display the
+/// running total along with how many times per second it was reached.
+///
+/// The argument is the label of the counter. With `reset: <seconds>` after
+/// the label, the total goes back to zero every `<seconds>` seconds. The
+/// message is updated once per frame, however many times the counter is
+/// incremented. The same prefix options as [`screen_print!`] are accepted,
+/// except `push`, `once` and `every`.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::screen_counter;
+///
+/// fn spawn_enemy() {
+///     screen_counter!("enemies spawned");
+///     screen_counter!("enemies spawned this minute", reset: 60);
+/// }
+/// ```
+#[macro_export]
+macro_rules! screen_counter {
+    (@opts [$($opts:tt)*] every: $($rest:tt)+) => {
+        ::std::compile_error!("`screen_counter!` updates its message once per frame, `every` is not supported")
+    };
+    (@opts [$($opts:tt)*] $opt:ident: $value:expr, $($rest:tt)+) => {
+        $crate::__screen_opts!(screen_counter [$($opts)*] $opt: $value, $($rest)+)
+    };
+    (@opts [$($opts:tt)*] $label:expr, reset: $reset:expr $(,)?) => {
+        $crate::screen_counter!(@count [$($opts)*] $label, Some($reset as f64))
+    };
+    (@opts [$($opts:tt)*] $label:expr $(,)?) => {
+        $crate::screen_counter!(@count [$($opts)*] $label, None)
+    };
+    (@count [$($opts:tt)*] $label:expr, $reset:expr) => {{
+        use $crate::{Counter, InvocationSiteKey, PrintOptions, command_channels};
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let options = PrintOptions::default()$($opts)*;
+        static COUNTER: Counter = Counter::new();
+        command_channels().count(key, options, $label, &COUNTER, $reset);
+    }};
+    ($($args:tt)+) => {
+        $crate::screen_counter!(@opts [] $($args)+)
+    };
+}
+
+/// The count of a [`screen_counter!`] invocation site.
+#[doc(hidden)]
+pub struct Counter {
+    state: Mutex<CounterState>,
+    /// [`CommandChannels::generation`] plus one when the counter message was
+    /// queued, 0 if it is not queued.
+    queued: AtomicU64,
+}
+struct CounterState {
+    total: u64,
+    /// Seconds between resets of `total`, the `reset:` option.
+    reset: Option<f64>,
+    /// When `total` was last reset.
+    reset_start: Option<Instant>,
+    /// Increments since `rate_start`.
+    rate_count: u64,
+    rate_start: Option<Instant>,
+    /// Increments per second, measured over the last second.
+    rate: Option<f64>,
+}
+impl Counter {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        let state = CounterState {
+            total: 0,
+            reset: None,
+            reset_start: None,
+            rate_count: 0,
+            rate_start: None,
+            rate: None,
+        };
+        Self {
+            state: Mutex::new(state),
+            queued: AtomicU64::new(0),
+        }
+    }
+    /// Increment the count, resetting it first if `reset` seconds elapsed
+    /// since the last reset.
+    fn increment(&self, reset: Option<f64>) {
+        let now = Instant::now();
+        // A panic while holding the lock can't leave the count inconsistent.
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.reset = reset;
+        state.reset_start.get_or_insert(now);
+        state.reset_if_due(now);
+        state.rate_start.get_or_insert(now);
+        state.total += 1;
+        state.rate_count += 1;
+    }
+    /// Whether the text changes without being incremented, because the rate
+    /// or the total are due to be computed again.
+    fn outdated(&self) -> bool {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let due = |start: Option<Instant>, secs: f64| {
+            start.is_some_and(|start| now.duration_since(start).as_secs_f64() >= secs)
+        };
+        due(state.rate_start, 1.0)
+            || state
+                .reset
+                .is_some_and(|reset| due(state.reset_start, reset))
+    }
+    /// The text of the counter message, it can be queued again afterward.
+    fn text(&self, label: &str) -> String {
+        self.queued.store(0, Ordering::Relaxed);
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.reset_if_due(now);
+        if let Some(rate_start) = state.rate_start {
+            let elapsed = now.duration_since(rate_start).as_secs_f64();
+            if elapsed >= 1.0 {
+                state.rate = Some(state.rate_count as f64 / elapsed);
+                state.rate_count = 0;
+                state.rate_start = Some(now);
+            }
+        }
+        match state.rate {
+            Some(rate) => format!("{label}: {} ({rate:.1}/s)", state.total),
+            None => format!("{label}: {}", state.total),
+        }
+    }
+}
+
+impl CounterState {
+    fn reset_if_due(&mut self, now: Instant) {
+        let (Some(reset), Some(reset_start)) = (self.reset, self.reset_start) else {
+            return;
+        };
+        if now.duration_since(reset_start).as_secs_f64() >= reset {
+            self.total = 0;
+            self.reset_start = Some(now);
+        }
+    }
+}
+
+/// How many characters wide are progress bars drawn with text.
+const PROGRESS_TEXT_LEN: usize = 20;
+
+/// A progress bar drawn with text characters, see [`screen_progress!`].
+fn progress_text(label: &str, fraction: f32) -> String {
+    let filled = (fraction * PROGRESS_TEXT_LEN as f32).round() as usize;
+    let bar = "#".repeat(filled) + &" ".repeat(PROGRESS_TEXT_LEN - filled);
+    format!("{label} [{bar}] {:.0}%", fraction * 100.0)
+}
+
+/// Align `header` and `rows` in columns separated by two spaces, with a line
+/// of dashes below the header. Missing cells are left empty.
+fn format_table(header: Vec<String>, rows: Vec<Vec<String>>) -> String {
+    let column_count = iter::once(&header).chain(&rows).map(Vec::len).max();
+    let mut widths = vec![0; column_count.unwrap_or(0)];
+    for row in iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = cell.chars().count().max(*width);
+        }
+    }
+    let separator: Vec<_> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut text = String::new();
+    for row in [header, separator].into_iter().chain(rows) {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        let mut line = String::new();
+        for (i, width) in widths.iter().enumerate() {
+            let cell = row.get(i).map_or("", String::as_str);
+            if i != 0 {
+                line.push_str("  ");
+            }
+            line.push_str(&format!("{cell:<width$}"));
+        }
+        text.push_str(line.trim_end());
+    }
+    text
+}
+
+/// Remove a message printed with the `key:` option from the screen.
+///
+/// This is useful for messages printed with the `persist` option of
+/// [`screen_print!`], which otherwise stay on screen forever. Messages printed
+/// with `push` can't be cleared one by one.
+///
+/// Without argument, `screen_clear!()` removes every message, including
+/// `push` messages, for example when switching game states or entering a
+/// cutscene.
+///
+/// ```rust,no_run
+/// use bevy_debug_text_overlay::{screen_clear, screen_print};
+///
+/// let recording = true;
+/// if recording {
+///     screen_print!(persist, key: "recording", "recording ON");
+/// } else {
+///     screen_clear!("recording");
+/// }
+/// let cutscene = true;
+/// if cutscene {
+///     screen_clear!();
+/// }
+/// ```
+#[macro_export]
+macro_rules! screen_clear {
+    () => {
+        $crate::command_channels().clear_all()
+    };
+    ($key:expr $(,)?) => {
+        $crate::command_channels().clear($crate::InvocationSiteKey::Named($key.into()))
+    };
+}
+
+/// A change to the displayed messages.
+///
+/// `Pending` is `()` for commands as sent, and uninhabited once
+/// [`Command::resolve`] turned samples and counters into refreshes, so that
+/// the compiler checks they are not applied as is.
+enum Command<Pending = ()> {
+    /// Update in place or add new message already printed at given site.
+    Refresh {
+        key: InvocationSiteKey,
+        text: String,
+        options: PrintOptions,
+    },
+    /// Always add the message to the screen.
+    Push {
+        key: InvocationSiteKey,
+        text: String,
+        options: PrintOptions,
+    },
+    /// Add a value to the series at given site, and refresh its message.
+    Sample {
+        key: InvocationSiteKey,
+        label: String,
+        value: f32,
+        series: Series,
+        options: PrintOptions,
+        pending: Pending,
+    },
+    /// Update in place or add the progress bar at given site.
+    Progress {
+        key: InvocationSiteKey,
+        label: String,
+        /// How much of the bar is filled, between 0 and 1.
+        fraction: f32,
+        options: PrintOptions,
+    },
+    /// Increment the counter at given site, and refresh its message.
+    Count {
+        key: InvocationSiteKey,
+        label: String,
+        counter: &'static Counter,
+        options: PrintOptions,
+        pending: Pending,
+    },
+    /// Keep displaying the message already printed at given site, as if it
+    /// was refreshed with the same text, see [`PrintCache`].
+    Touch {
+        key: InvocationSiteKey,
+        options: PrintOptions,
+    },
+    /// Remove the message printed at given site from the screen.
+    Clear { key: InvocationSiteKey },
+    /// Remove all messages from the screen.
+    ClearAll,
+    /// Pin or unpin the message printed at given site.
+    Pin {
+        key: InvocationSiteKey,
+        pinned: bool,
+    },
+    /// Change the presentation of the message printed at given site, keeping
+    /// its text.
+    Edit {
+        key: InvocationSiteKey,
+        edit: MessageEdit,
+    },
+}
+
+/// A [`Command`] with its samples and counters resolved.
+type ResolvedCommand = Command<Infallible>;
+
+impl Command {
+    /// Turn samples and counters into refreshes of their text, and progress
+    /// bars too when they can't be displayed as bars.
+    fn resolve(self, samples: &mut Samples, bars: bool) -> ResolvedCommand {
+        match self {
+            Command::Sample { key, label, value, series, options, pending: () } => {
+                let text = samples.push(&key, &label, value, series);
+                Command::Refresh { key, text, options }
+            }
+            Command::Count { key, label, counter, options, pending: () } => {
+                let text = counter.text(&label);
+                Command::Refresh { key, text, options }
+            }
+            Command::Progress { key, label, fraction, options } if !bars => {
+                let text = progress_text(&label, fraction);
+                Command::Refresh { key, text, options }
+            }
+            Command::Progress { key, label, fraction, options } => {
+                Command::Progress { key, label, fraction, options }
+            }
+            Command::Refresh { key, text, options } => Command::Refresh { key, text, options },
+            Command::Push { key, text, options } => Command::Push { key, text, options },
+            Command::Touch { key, options } => Command::Touch { key, options },
+            Command::Clear { key } => Command::Clear { key },
+            Command::ClearAll => Command::ClearAll,
+            Command::Pin { key, pinned } => Command::Pin { key, pinned },
+            Command::Edit { key, edit } => Command::Edit { key, edit },
+        }
+    }
+}
+
+/// Change to a displayed message, see [`Command::Edit`].
+#[derive(Clone, Copy)]
+enum MessageEdit {
+    Color(Color),
+    /// Seconds to add to the timeout.
+    ExtendTimeout(f64),
+    /// Refresh the message with its timeout, without changing its text.
+    Touch(f64),
+}
+impl MessageEdit {
+    fn apply(self, message: &mut Message, clock: &OverlayClock, fade: f64) {
+        match self {
+            MessageEdit::Color(color) => message.color = color,
+            // Messages that already faded out are not displayed again.
+            MessageEdit::ExtendTimeout(secs) => {
+                if message.opacity(clock, fade).is_some() {
+                    message.expiration += secs;
+                }
+            }
+            MessageEdit::Touch(timeout) => {
+                if message.opacity(clock, fade).is_some() {
+                    message.expire_in(clock, timeout);
+                    message.updated = clock.elapsed;
+                }
+            }
+        }
+    }
+}
+
+/// A handle to print messages in the overlay without macros.
+///
+/// Messages printed with [`OverlayCommands::print`] and
+/// [`OverlayCommands::push`] are identified by the location of the caller,
+/// like with [`screen_print!`].
+///
+/// ```rust,no_run
+/// use bevy::prelude::Color;
+/// use bevy_debug_text_overlay::OverlayCommands;
+///
+/// let overlay = OverlayCommands::default().col(Color::RED).sec(2.0);
+/// overlay.print("replaced each time this line runs");
+/// overlay.push("added each time this line runs");
+/// overlay.print_keyed("status", "recording");
+/// overlay.clear("status");
+/// overlay.clear_all();
+/// overlay.pin("fps");
+/// overlay.unpin("fps");
+/// overlay.set_color("status", Color::RED);
+/// overlay.extend_timeout("status", 5.0);
+/// ```
+#[derive(Clone, Default)]
+pub struct OverlayCommands {
+    options: PrintOptions,
+}
+impl OverlayCommands {
+    /// Color of printed messages, by default it is
+    /// [`OverlayPlugin::fallback_color`].
+    pub fn col(self, color: Color) -> Self {
+        Self { options: self.options.col(color) }
+    }
+    /// For how many seconds printed messages show up, by default it is 7.
+    pub fn sec(self, timeout: f64) -> Self {
+        Self { options: self.options.sec(timeout) }
+    }
+    /// Display `text`, replacing the previous message printed from the same
+    /// location.
+    #[track_caller]
+    pub fn print(&self, text: impl Into<String>) {
+        let options = self.options.clone();
+        command_channels().print(caller_key(), options, || text.into());
+    }
+    /// Display `text` as a new message.
+    #[track_caller]
+    pub fn push(&self, text: impl Into<String>) {
+        let options = self.options.clone().push(true);
+        command_channels().print(caller_key(), options, || text.into());
+    }
+    /// Display `text`, replacing the previous message printed with `key`.
+    pub fn print_keyed(&self, key: impl Into<Cow<'static, str>>, text: impl Into<String>) {
+        let options = self.options.clone();
+        command_channels().print(InvocationSiteKey::Named(key.into()), options, || {
+            text.into()
+        });
+    }
+    /// Remove the message printed with `key` from the screen.
+    pub fn clear(&self, key: impl Into<Cow<'static, str>>) {
+        command_channels().clear(InvocationSiteKey::Named(key.into()));
+    }
+    /// Remove all messages from the screen, including `push` messages.
+    pub fn clear_all(&self) {
+        command_channels().clear_all();
+    }
+    /// Keep the message printed with `key` on screen, above the others, like
+    /// the `pin` option of [`screen_print!`].
+    pub fn pin(&self, key: impl Into<Cow<'static, str>>) {
+        command_channels().pin(InvocationSiteKey::Named(key.into()), true);
+    }
+    /// Let the message printed with `key` expire again, it is removed from
+    /// the screen if its timeout already elapsed.
+    pub fn unpin(&self, key: impl Into<Cow<'static, str>>) {
+        command_channels().pin(InvocationSiteKey::Named(key.into()), false);
+    }
+    /// Change the color of the message printed with `key`, without sending
+    /// its text again. It lasts until the message is printed again.
+    pub fn set_color(&self, key: impl Into<Cow<'static, str>>, color: Color) {
+        let key = InvocationSiteKey::Named(key.into());
+        command_channels().edit(key, MessageEdit::Color(color));
+    }
+    /// Display the message printed with `key` `secs` seconds longer. Messages
+    /// that already faded out are not displayed again.
+    pub fn extend_timeout(&self, key: impl Into<Cow<'static, str>>, secs: f64) {
+        let key = InvocationSiteKey::Named(key.into());
+        command_channels().edit(key, MessageEdit::ExtendTimeout(secs));
+    }
+}
+
+/// A handle to print messages in the overlay from tasks and threads outside
+/// of the ECS, such as `AsyncComputeTaskPool` tasks or `std::thread`s.
+///
+/// Each sender displays its messages on a line of its own, identified by its
+/// name. Sending never blocks. Messages show up on the next frame, their
+/// timeout and timestamp count from the time the overlay receives them rather
+/// than the time they were sent.
+///
+/// ```rust,no_run
+/// use bevy::tasks::AsyncComputeTaskPool;
+/// use bevy_debug_text_overlay::OverlaySender;
+///
+/// let sender = OverlaySender::new("level loading");
+/// let task = AsyncComputeTaskPool::get().spawn(async move {
+///     for chunk in 0..10 {
+///         sender.progress("chunks", chunk as f32 / 10.0);
+///     }
+///     sender.print("level loaded");
+/// });
+/// task.detach();
+/// ```
+#[derive(Clone)]
+pub struct OverlaySender {
+    key: InvocationSiteKey,
+    options: PrintOptions,
+}
+impl OverlaySender {
+    /// A sender displaying its messages on the line identified by `name`,
+    /// like messages printed with the `key:` option of [`screen_print!`].
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        let key = InvocationSiteKey::Named(name.into());
+        Self { key, options: PrintOptions::default() }
+    }
+    /// Color of printed messages, by default it is
+    /// [`OverlayPlugin::fallback_color`].
+    pub fn col(self, color: Color) -> Self {
+        Self { options: self.options.col(color), ..self }
+    }
+    /// For how many seconds printed messages show up, by default it is 7.
+    pub fn sec(self, timeout: f64) -> Self {
+        Self { options: self.options.sec(timeout), ..self }
+    }
+    /// Display `text`, replacing the previous message of this sender.
+    pub fn print(&self, text: impl Into<String>) {
+        let options = self.options.clone();
+        command_channels().print(self.key.clone(), options, || text.into());
+    }
+    /// Display `text` as a new message.
+    pub fn push(&self, text: impl Into<String>) {
+        let options = self.options.clone().push(true);
+        command_channels().print(self.key.clone(), options, || text.into());
+    }
+    /// Display a progress bar filled at `fraction`, between 0 and 1,
+    /// replacing the previous message of this sender.
+    pub fn progress(&self, label: impl fmt::Display, fraction: f32) {
+        let (key, options) = (self.key.clone(), self.options.clone());
+        command_channels().progress(key, options, label, 0.0..=1.0, fraction);
+    }
+    /// Remove the message of this sender from the screen.
+    pub fn clear(&self) {
+        command_channels().clear(self.key.clone());
+    }
+}
+
+#[track_caller]
+fn caller_key() -> InvocationSiteKey {
+    let location = Location::caller();
+    InvocationSiteKey::Site {
+        file: location.file(),
+        line: location.line(),
+        column: location.column(),
+    }
+}
+
+/// The filters of messages with a category, see [`CommandChannels::allows`].
+type PrintFilters = (OverlayFilter, DebugOverlayCategories);
+
+/// Source of [`CommandChannels::filters_generation`], so that generations
+/// are unique across channels.
+static FILTERS_GENERATIONS: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The filters last read by this thread, with their generation, so that
+    /// the lock of [`CommandChannels::filters`] is only taken after they
+    /// changed.
+    static FILTERS: RefCell<(u64, Option<Arc<PrintFilters>>)> = const { RefCell::new((0, None)) };
+}
+
+/// Queue text to display on the screen
+#[doc(hidden)]
+pub struct CommandChannels {
+    queue: ConcurrentQueue<Command>,
+    /// The [`OverflowPolicy`] as `u8`, kept in sync with the resource by
+    /// [`sync_overflow_policy`].
+    policy: AtomicU8,
+    /// How many messages were dropped since last frame.
+    dropped: AtomicUsize,
+    /// Changes when messages already sent might not be displayed anymore,
+    /// so that [`PrintCache`] doesn't skip sending them again.
+    generation: AtomicU64,
+    /// The [`OverlayFilter::min_level`] as `u8`, so that messages without
+    /// category that are filtered out are not even formatted.
+    min_level: AtomicU8,
+    /// The filters of messages with a category, `None` when all messages
+    /// must be sent, kept in sync with the resources by [`sync_print_gate`].
+    filters: RwLock<Option<Arc<PrintFilters>>>,
+    /// Changes when `filters` change, `0` while they are `None`.
+    filters_generation: AtomicU64,
+}
+impl CommandChannels {
+    fn new(capacity: usize) -> Self {
+        CommandChannels {
+            queue: ConcurrentQueue::bounded(capacity),
+            policy: AtomicU8::new(OverflowPolicy::default() as u8),
+            dropped: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+            min_level: AtomicU8::new(Level::Trace as u8),
+            filters: RwLock::new(None),
+            filters_generation: AtomicU64::new(0),
+        }
+    }
+    fn policy(&self) -> OverflowPolicy {
+        match self.policy.load(Ordering::Relaxed) {
+            0 => OverflowPolicy::DropNewest,
+            1 => OverflowPolicy::DropOldest,
+            _ => OverflowPolicy::Collapse,
+        }
+    }
+    /// Whether messages of `level` in the `cat` category are displayed,
+    /// otherwise they are not sent, nor formatted.
+    fn allows(&self, level: Level, cat: Option<&str>) -> bool {
+        let Some(cat) = cat else {
+            return level as u8 >= self.min_level.load(Ordering::Relaxed);
+        };
+        let generation = self.filters_generation.load(Ordering::Acquire);
+        FILTERS.with_borrow_mut(|(read_generation, filters)| {
+            if *read_generation != generation {
+                let latest = self.filters.read().unwrap_or_else(PoisonError::into_inner);
+                *filters = latest.clone();
+                *read_generation = generation;
+            }
+            let Some((filter, categories)) = filters.as_deref() else {
+                return true;
+            };
+            filter.allows(level, Some(cat)) && categories.is_enabled(cat)
+        })
+    }
+    /// Only send messages passing `filters`, or all messages if `None`.
+    fn set_filters(&self, filters: Option<PrintFilters>) {
+        let min_level = filters.as_ref().map_or(Level::Trace, |(f, _)| f.min_level);
+        self.min_level.store(min_level as u8, Ordering::Relaxed);
+        let generation = match filters {
+            Some(_) => FILTERS_GENERATIONS.fetch_add(1, Ordering::Relaxed),
+            None => 0,
+        };
+        *self.filters.write().unwrap_or_else(PoisonError::into_inner) = filters.map(Arc::new);
+        self.filters_generation.store(generation, Ordering::Release);
+    }
+    /// Queue `cmd`, handling a full queue according to the [`OverflowPolicy`].
+    fn send(&self, cmd: Command) {
+        // The queue lives in a static, it is never closed.
+        let dropped = match self.policy() {
+            OverflowPolicy::DropNewest | OverflowPolicy::Collapse => {
+                matches!(self.queue.push(cmd), Err(PushError::Full(_)))
+            }
+            // Replaces the oldest queued message when full.
+            OverflowPolicy::DropOldest => matches!(self.queue.force_push(cmd), Ok(Some(_))),
+        };
+        if dropped {
+            // Reported once per frame by `take_dropped`, rather than once
+            // per message.
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            // The dropped message must be sent again, even if unchanged.
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /// Report the messages dropped since the last call, returns the
+    /// "N messages dropped" line to display with [`OverflowPolicy::Collapse`],
+    /// otherwise logs a single error.
+    fn take_dropped(&self) -> Option<Command> {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped == 0 {
+            return None;
+        }
+        if self.policy() != OverflowPolicy::Collapse {
+            error!(
+                "{dropped} debug messages dropped, exceeding the limit of {MAX_LINES} per frame"
+            );
+            return None;
+        }
+        let key = InvocationSiteKey::Site { file: file!(), line: line!(), column: column!() };
+        let text = format!("{dropped} messages dropped");
+        let options = PrintOptions::default().col(Color::RED).level(Level::Error);
+        Some(Command::Refresh { key, text, options })
+    }
+    pub fn print(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        text: impl FnOnce() -> String,
+    ) {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        if !self.allows(options.level, options.cat.as_deref()) || options.throttled() {
+            return;
+        }
+        self.send_text(key, options, text());
+    }
+    /// Like [`CommandChannels::print`], but doesn't format nor send `text`
+    /// if it is identical to the last message sent with the same
+    /// [`PrintOptions::cache`].
+    pub fn print_fmt(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        text: fmt::Arguments,
+    ) {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        if !self.allows(options.level, options.cat.as_deref()) {
+            return;
+        }
+        if let Some((condition, shown)) = options.only_if {
+            if !condition {
+                if shown.swap(false, Ordering::Relaxed) {
+                    self.clear(key);
+                }
+                return;
+            }
+            shown.store(true, Ordering::Relaxed);
+        }
+        if options.throttled() {
+            return;
+        }
+        if let (Some(cache), false) = (options.cache, options.push) {
+            let generation = self.generation.load(Ordering::Relaxed);
+            if cache.unchanged(generation, &key, &options, text) {
+                self.send(Command::Touch { key, options });
+                return;
+            }
+        }
+        self.send_text(key, options, fmt::format(text));
+    }
+    fn send_text(&self, key: InvocationSiteKey, options: PrintOptions, text: String) {
+        if options.push {
+            self.send(Command::Push { key, text, options });
+        } else {
+            self.send(Command::Refresh { text, key, options });
+        }
+    }
+    pub fn sample(
+        &self,
+        key: InvocationSiteKey,
+        options: PrintOptions,
+        label: impl fmt::Display,
+        value: f32,
+    ) {
+        self.send_sample(key, options, label, value, Series::Graph);
+    }
+    pub fn histogram(
+        &self,
+        key: InvocationSiteKey,
+        options: PrintOptions,
+        label: impl fmt::Display,
+        value: f32,
+        buckets: u32,
+    ) {
+        self.send_sample(key, options, label, value, Series::Histogram(buckets));
+    }
+    pub fn stats(
+        &self,
+        key: InvocationSiteKey,
+        options: PrintOptions,
+        label: impl fmt::Display,
+        value: f32,
+    ) {
+        self.send_sample(key, options, label, value, Series::Stats);
+    }
+    fn send_sample(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        label: impl fmt::Display,
+        value: f32,
+        series: Series,
+    ) {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        if !self.allows(options.level, options.cat.as_deref()) || options.throttled() {
+            return;
+        }
+        let label = label.to_string();
+        self.send(Command::Sample { key, label, value, series, options, pending: () });
+    }
+    pub fn table<H, R>(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        header: H,
+        rows: impl IntoIterator<Item = R>,
+    ) where
+        H: IntoIterator,
+        H::Item: fmt::Display,
+        R: IntoIterator,
+        R::Item: fmt::Display,
+    {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        if !self.allows(options.level, options.cat.as_deref()) || options.throttled() {
+            return;
+        }
+        let cells = |row: R| row.into_iter().map(|cell| cell.to_string()).collect();
+        let header = header.into_iter().map(|cell| cell.to_string()).collect();
+        let text = format_table(header, rows.into_iter().map(cells).collect());
+        let options = PrintOptions { monospace: true, push: false, ..options };
+        self.send(Command::Refresh { key, text, options });
+    }
+    pub fn progress(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        label: impl fmt::Display,
+        range: RangeInclusive<f32>,
+        value: f32,
+    ) {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        if !self.allows(options.level, options.cat.as_deref()) || options.throttled() {
+            return;
+        }
+        let (start, end) = range.into_inner();
+        let fraction = ((value - start) / (end - start)).clamp(0.0, 1.0);
+        // A NaN fraction, from an empty range, is an empty bar.
+        let fraction = if fraction.is_nan() { 0.0 } else { fraction };
+        let label = label.to_string();
+        let options = PrintOptions { push: false, ..options };
+        self.send(Command::Progress { key, label, fraction, options });
+    }
+    pub fn count(
+        &self,
+        key: InvocationSiteKey,
+        mut options: PrintOptions,
+        label: impl fmt::Display,
+        counter: &'static Counter,
+        reset: Option<f64>,
+    ) {
+        let key = options.key.take().map_or(key, InvocationSiteKey::Named);
+        counter.increment(reset);
+        // The message shows the count when the command is read, it is
+        // only queued again once read, or when it might have been dropped.
+        let queued = self.generation.load(Ordering::Relaxed) + 1;
+        if counter.queued.swap(queued, Ordering::Relaxed) == queued {
+            return;
+        }
+        let label = label.to_string();
+        let options = PrintOptions { push: false, ..options };
+        self.send(Command::Count { key, label, counter, options, pending: () });
+    }
+    /// Remove the message identified by `key` from the screen, see
+    /// [`screen_clear!`].
+    pub fn clear(&self, key: InvocationSiteKey) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.send(Command::Clear { key });
+    }
+    /// Remove all messages from the screen, see [`screen_clear!`].
+    pub fn clear_all(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.send(Command::ClearAll);
+    }
+    /// Pin or unpin the message identified by `key`, see
+    /// [`OverlayCommands::pin`].
+    pub fn pin(&self, key: InvocationSiteKey, pinned: bool) {
+        self.send(Command::Pin { key, pinned });
+    }
+    /// Change the presentation of the message identified by `key`, see
+    /// [`OverlayCommands::set_color`].
+    fn edit(&self, key: InvocationSiteKey, edit: MessageEdit) {
+        self.send(Command::Edit { key, edit });
+    }
+}
+
+#[derive(Component)]
+struct Message {
+    expiration: f64,
+    /// Last time the message was printed.
+    updated: f64,
+    /// Last time the message showed up after being expired.
+    shown: f64,
+    site: InvocationSiteKey,
+    /// Color of the message before fading out.
+    color: Color,
+    /// Background color of the message before fading out.
+    bg: Color,
+    /// Border color of the message box before fading out.
+    border: Color,
+    /// Font size set with the `size` option of [`screen_print!`].
+    size: Option<f32>,
+    /// Color of each section of the message text, `None` for [`Self::color`].
+    spans: Vec<Option<Color>>,
+    /// The message text, with its color markup.
+    text: String,
+    /// Alignment set with the `align` option of [`screen_print!`].
+    align: Option<Alignment>,
+    /// Priority set with the `prio` option of [`screen_print!`].
+    prio: i32,
+    /// Frames left before the message expires, set with the `frames` option
+    /// of [`screen_print!`].
+    frames_left: Option<u32>,
+    /// Lines displayed when not `expanded`, set with the `lines` option of
+    /// [`screen_print!`].
+    lines: Option<usize>,
+    /// Whether all the lines are displayed, see [`expand_messages`].
+    expanded: bool,
+    /// Clock the timeout counts in when it isn't the overlay one, set with
+    /// the `clock` option of [`screen_print!`]. `expiration` counts in it.
+    clock: Option<TimeSource>,
+    /// Color of the square set with the `icon` option of [`screen_print!`],
+    /// before fading out.
+    icon: Option<Color>,
+    /// Whether the message is pinned, it then doesn't expire and is
+    /// displayed above the others.
+    pinned: bool,
+    /// Nesting level set with the `indent` option of [`screen_print!`].
+    indent: u32,
+    /// Opacity set with the `alpha` option of [`screen_print!`].
+    alpha: f32,
+    level: Level,
+    /// Category set with the `cat` option of [`screen_print!`].
+    cat: Option<Cow<'static, str>>,
+}
+impl Message {
+    fn new(site: InvocationSiteKey, current: f64, timeout: f64, color: Color) -> Self {
+        Self {
+            expiration: current + timeout,
+            updated: current,
+            shown: current,
+            site,
+            color,
+            bg: Color::NONE,
+            border: Color::NONE,
+            size: None,
+            spans: vec![None],
+            text: String::new(),
+            align: None,
+            prio: 0,
+            frames_left: None,
+            lines: None,
+            expanded: false,
+            clock: None,
+            icon: None,
+            pinned: false,
+            indent: 0,
+            alpha: 1.0,
+            level: Level::default(),
+            cat: None,
+        }
+    }
+    /// Sections of `text` holding the message, after the countdown if any.
+    fn sections<'a>(&self, text: &'a Text) -> &'a [TextSection] {
+        let start = text.sections.len().saturating_sub(self.spans.len());
+        &text.sections[start..]
+    }
+    /// The message text, without color markup.
+    fn plain_text(&self) -> String {
+        markup::strip(&self.text)
+    }
+    /// Replace the sections of `text` after the countdown by `spans`,
+    /// truncated to [`Self::lines`] unless [`Self::expanded`].
+    fn set_spans(&mut self, text: &mut Text, spans: Vec<Span>) {
+        let spans = match self.lines {
+            Some(lines) if !self.expanded => markup::truncate(spans, lines),
+            _ => spans,
+        };
+        let style = text.sections[0].style.clone();
+        let countdown = text.sections.len() - self.spans.len();
+        text.sections.truncate(countdown);
+        self.spans = spans.iter().map(|(_, color)| *color).collect();
+        let sections = spans
+            .into_iter()
+            .map(|(span, _)| TextSection::new(span, style.clone()));
+        text.sections.extend(sections);
+        let colors = self.section_colors(text.sections.len());
+        for (section, color) in text.sections.iter_mut().zip(colors) {
+            section.style.color = color;
+        }
+    }
+    /// Color of each of the `count` sections of the message [`Text`].
+    fn section_colors(&self, count: usize) -> impl Iterator<Item = Color> + '_ {
+        let countdown = count.saturating_sub(self.spans.len());
+        let spans = self.spans.iter().map(|span| span.unwrap_or(self.color));
+        iter::repeat(self.color).take(countdown).chain(spans)
+    }
+    /// Priority of the message, pinned messages come above all others.
+    fn rank(&self) -> i32 {
+        if self.pinned {
+            i32::MAX
+        } else {
+            self.prio
+        }
+    }
+    /// Opacity of the message at the current time of `clock` when it takes
+    /// `fade` seconds to fade out, `None` once it completely faded out.
+    fn opacity(&self, clock: &OverlayClock, fade: f64) -> Option<f32> {
+        if self.pinned {
+            return Some(1.0);
+        }
+        let fading_for = clock.now(self.clock) - self.expiration;
+        if fading_for <= 0.0 {
+            Some(1.0)
+        } else if fading_for < fade {
+            Some((1.0 - fading_for / fade) as f32)
+        } else {
+            None
+        }
+    }
+    /// Seconds left before the message starts fading out, counted in its
+    /// own clock.
+    fn remaining(&self, clock: &OverlayClock) -> f64 {
+        self.expiration - clock.now(self.clock)
+    }
+    /// Expire the message `timeout` seconds from now, counted in its own
+    /// clock.
+    fn expire_in(&mut self, clock: &OverlayClock, timeout: f64) {
+        self.expiration = clock.now(self.clock) + timeout;
+    }
+}
+
+#[derive(Resource)]
+struct Options {
+    font_size: f32,
+    color: Color,
+    /// Kept in sync with the resource by [`sync_overlay_theme`].
+    theme: OverlayTheme,
+    toggle_key: Option<KeyCode>,
+    background: Option<Color>,
+    padding: f32,
+    font_path: Option<&'static str>,
+    font: Handle<Font>,
+    table_font_path: Option<&'static str>,
+    table_font: Handle<Font>,
+    pool_size: usize,
+    pool_grace: f64,
+    single_text: bool,
+    max_visible_lines: Option<usize>,
+    max_height: Val,
+    max_width: Val,
+    dedicated_camera: bool,
+    default_window: WindowRef,
+    prefix: MessagePrefix,
+    timestamp: Timestamp,
+    fade: f64,
+    fade_background: bool,
+    history_dump_key: Option<KeyCode>,
+    snapshot_key: Option<KeyCode>,
+    snapshot_path: &'static str,
+    column_width: Option<f32>,
+    line_spacing: f32,
+    indent_width: f32,
+    layout: Layout,
+    click_to_copy: bool,
+    pause_key: Option<KeyCode>,
+    show_countdown: bool,
+    mirror_to_log: bool,
+    ordering: MessageOrdering,
+    group_by_file: bool,
+    collapse_key: Option<KeyCode>,
+    expand_key: Option<KeyCode>,
+    coalesce: bool,
+    #[cfg(feature = "audio")]
+    beep_sound: Option<&'static str>,
+    gamepad: Option<GamepadBindings>,
+    time_source: TimeSource,
+}
+impl<'a> From<&'a OverlayPlugin> for Options {
+    fn from(plugin: &'a OverlayPlugin) -> Self {
+        Self {
+            prefix: plugin.prefix.clone(),
+            timestamp: plugin.timestamp.clone(),
+            fade: plugin.fade_duration,
+            fade_background: plugin.fade_background,
+            history_dump_key: plugin.history_dump_key,
+            snapshot_key: plugin.snapshot_key,
+            snapshot_path: plugin.snapshot_path,
+            column_width: plugin.column_width,
+            line_spacing: plugin.line_spacing,
+            indent_width: plugin.indent_width,
+            layout: plugin.layout.clone(),
+            click_to_copy: plugin.click_to_copy,
+            pause_key: plugin.pause_key,
+            show_countdown: plugin.show_countdown,
+            mirror_to_log: plugin.mirror_to_log,
+            ordering: plugin.ordering,
+            group_by_file: plugin.group_by_file,
+            collapse_key: plugin.collapse_key,
+            expand_key: plugin.expand_key,
+            coalesce: plugin.coalesce,
+            #[cfg(feature = "audio")]
+            beep_sound: plugin.beep_sound,
+            gamepad: plugin.gamepad,
+            time_source: plugin.time_source,
+            default_window: plugin.default_window,
+            dedicated_camera: plugin.dedicated_camera,
+            max_visible_lines: plugin.max_visible_lines,
+            max_height: plugin.max_height,
+            max_width: plugin.max_width,
+            single_text: plugin.single_text,
+            pool_size: plugin.pool_size,
+            pool_grace: plugin.pool_grace,
+            font_path: plugin.font,
+            font: Handle::default(),
+            table_font_path: plugin.table_font,
+            table_font: Handle::default(),
+            color: plugin.fallback_color,
+            theme: OverlayTheme::default(),
+            font_size: plugin.font_size,
+            toggle_key: plugin.toggle_key,
+            background: plugin.background_color,
+            padding: plugin.background_padding,
+        }
+    }
+}
+impl Options {
+    /// Space between the edge of the background panel and the text.
+    fn padding(&self) -> f32 {
+        if self.background.is_some() {
+            self.padding
+        } else {
+            0.0
+        }
+    }
+    /// Color of a message printed with `print`, before fading out.
+    fn color_of(&self, print: &PrintOptions) -> Color {
+        let themed = || self.theme.color(print.level, print.cat.as_deref());
+        print.color.or_else(themed).unwrap_or(self.color)
+    }
+}
+
+/// A message displayed at the screen position of a point in the world,
+/// rather than stacked with other messages.
+#[derive(Component, Clone, Copy)]
+enum WorldLabel {
+    Position(Vec3),
+    /// Follows the `GlobalTransform` of given entity.
+    Entity(Entity),
+}
+
+/// The UI nodes all messages are children of.
+#[derive(Resource)]
+struct OverlayRoot {
+    /// Root of the primary window.
+    main: Entity,
+    /// Roots of other windows, see [`OverlayPlugin::default_window`].
+    windows: HashMap<Entity, Entity>,
+    /// Roots of the cameras with an [`OverlayViewport`].
+    viewports: HashMap<Entity, Entity>,
+}
+impl OverlayRoot {
+    fn all(&self) -> impl Iterator<Item = Entity> + '_ {
+        let others = self.windows.values().chain(self.viewports.values());
+        iter::once(self.main).chain(others.copied())
+    }
+}
+fn root_node(location: &DebugOverlayLocation) -> NodeBundle {
+    let mut style = Style { position_type: PositionType::Absolute, ..default() };
+    set_margins(&mut style, location);
+    NodeBundle { style, ..default() }
+}
+impl From<Margin> for Val {
+    fn from(margin: Margin) -> Self {
+        match margin {
+            Margin::Px(px) => Val::Px(px),
+            Margin::Percent(percent) => Val::Percent(percent),
+        }
+    }
+}
+impl Margin {
+    /// Size in logical pixels of the margin of a window edge of `size`.
+    fn resolve(self, size: f32) -> f32 {
+        match self {
+            Margin::Px(px) => px,
+            Margin::Percent(percent) => size * percent / 100.0,
+        }
+    }
+}
+/// Make the root node fill the window, except for the margins.
+fn set_margins(style: &mut Style, location: &DebugOverlayLocation) {
+    let horizontal = location.margin_horizontal.into();
+    let vertical = location.margin_vertical.into();
+    (style.left, style.right) = (horizontal, horizontal);
+    (style.top, style.bottom) = (vertical, vertical);
+}
+/// Move the root nodes when the [`DebugOverlayLocation`] margins change.
+fn update_margins(
+    mut styles: Query<&mut Style>,
+    location: Res<DebugOverlayLocation>,
+    root: Res<OverlayRoot>,
+) {
+    if !location.is_changed() {
+        return;
+    }
+    for root in root.all() {
+        if let Ok(mut style) = styles.get_mut(root) {
+            set_margins(&mut style, &location);
+        }
+    }
+}
+
+/// Visual settings of the overlay, initialized from the [`OverlayPlugin`]
+/// fields of the same name.
+///
+/// Modify this resource to restyle the overlay at runtime, for example from
+/// an in-game debug settings menu. Displayed messages are updated, except
+/// for their prefix and timestamp, which only change for new messages. The
+/// position of the overlay is controlled by [`DebugOverlayLocation`].
+#[derive(Resource, Clone)]
+pub struct OverlayConfig {
+    /// The color to use when none are specified in [`screen_print!`].
+    pub fallback_color: Color,
+    /// The font size of messages without a `size` option, before
+    /// [`DebugOverlayScale`] is applied.
+    pub font_size: f32,
+    pub font: Handle<Font>,
+    /// The font of [`screen_table!`] messages.
+    pub table_font: Handle<Font>,
+    /// Color of a panel drawn behind the messages, see
+    /// [`OverlayPlugin::background_color`].
+    pub background_color: Option<Color>,
+    /// Space between the edge of the background panel and the text.
+    pub background_padding: f32,
+    pub prefix: MessagePrefix,
+    pub timestamp: Timestamp,
+}
+impl<'a> From<&'a OverlayPlugin> for OverlayConfig {
+    fn from(plugin: &'a OverlayPlugin) -> Self {
+        Self {
+            fallback_color: plugin.fallback_color,
+            font_size: plugin.font_size,
+            font: Handle::default(),
+            table_font: Handle::default(),
+            background_color: plugin.background_color,
+            background_padding: plugin.background_padding,
+            prefix: plugin.prefix.clone(),
+            timestamp: plugin.timestamp.clone(),
+        }
+    }
+}
+
+/// The messages currently displayed, for other frontends such as the
+/// [`OverlayInspectorPlugin`](crate::OverlayInspectorPlugin).
+#[cfg(feature = "egui")]
+#[derive(SystemParam)]
+pub(crate) struct ActiveMessages<'w, 's> {
+    messages: Query<'w, 's, &'static Message>,
+    options: Res<'w, Options>,
+    clock: Res<'w, OverlayClock>,
+}
+#[cfg(feature = "egui")]
+impl ActiveMessages<'_, '_> {
+    /// Site, text without color markup, and color of the displayed messages,
+    /// sorted by site.
+    pub(crate) fn collect(&self) -> Vec<(&InvocationSiteKey, String, Color)> {
+        let (clock, fade) = (&self.clock, self.options.fade);
+        let mut active: Vec<_> = self
+            .messages
+            .iter()
+            .filter(|message| message.opacity(clock, fade).is_some())
+            .map(|message| (&message.site, message.plain_text(), message.color))
+            .collect();
+        active.sort_by_key(|(site, ..)| *site);
+        active
+    }
+}
+
+/// Load the fonts, the default font is used when there is no `AssetServer`.
+fn load_font(
+    mut config: ResMut<OverlayConfig>,
+    options: Res<Options>,
+    assets: Option<Res<AssetServer>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    if let Some(path) = options.font_path {
+        config.font = assets.load(path);
+    }
+    if let Some(path) = options.table_font_path {
+        config.table_font = assets.load(path);
+    }
+}
+
+fn sync_overlay_theme(theme: Res<OverlayTheme>, mut options: ResMut<Options>) {
+    if theme.is_changed() {
+        options.theme = theme.clone();
+    }
+}
+
+/// Apply changes of the [`OverlayConfig`] to the [`Options`] and displayed
+/// messages.
+fn sync_overlay_config(
+    config: Res<OverlayConfig>,
+    mut options: ResMut<Options>,
+    mut messages: Query<(&mut Text, &mut Message)>,
+    mut background: Query<&mut BackgroundColor, With<Background>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    let old_color = options.color;
+    let (old_font, old_table_font) = (options.font.clone(), options.table_font.clone());
+    let config = config.clone();
+    options.color = config.fallback_color;
+    options.font_size = config.font_size;
+    options.background = config.background_color;
+    options.padding = config.background_padding;
+    options.prefix = config.prefix;
+    options.timestamp = config.timestamp;
+    // Messages without a `col` option have the fallback color.
+    for (mut text, mut message) in &mut messages {
+        if message.color == old_color {
+            message.color = config.fallback_color;
+        }
+        let colors = message.section_colors(text.sections.len());
+        for (section, color) in text.sections.iter_mut().zip(colors) {
+            section.style.color = color;
+            if section.style.font == old_font {
+                section.style.font = config.font.clone();
+            } else if section.style.font == old_table_font {
+                section.style.font = config.table_font.clone();
+            }
+        }
+    }
+    if let Ok(mut background) = background.get_single_mut() {
+        background.0 = config.background_color.unwrap_or(Color::NONE);
+    }
+    options.font = config.font;
+    options.table_font = config.table_font;
+}
+
+/// Order of the camera spawned by [`OverlayPlugin::dedicated_camera`], high
+/// enough to render after all other cameras.
+const OVERLAY_CAMERA_ORDER: isize = 1024;
+
+/// The text entity displaying all messages, see [`OverlayPlugin::single_text`].
+#[derive(Component)]
+struct SingleText;
+
+/// A message displayed as a bar, see [`screen_progress!`].
+///
+/// It is a UI node with the label text and the bar as children.
+#[derive(Component)]
+struct ProgressBar {
+    label: Entity,
+    /// The part of the bar filled according to the progress.
+    fill: Entity,
+}
+
+/// Resources deciding which messages are displayed.
+#[derive(SystemParam)]
+struct Filters<'w> {
+    filter: Res<'w, OverlayFilter>,
+    categories: Res<'w, DebugOverlayCategories>,
+    sites: ResMut<'w, DebugOverlaySites>,
+}
+impl Filters<'_> {
+    fn is_changed(&self) -> bool {
+        self.filter.is_changed() || self.categories.is_changed() || self.sites.is_changed()
+    }
+    /// Whether messages printed from `site` with `print` are not displayed.
+    fn hides(&self, site: &InvocationSiteKey, print: &PrintOptions) -> bool {
+        let disabled = |cat: &Cow<str>| !self.categories.is_enabled(cat);
+        !self.filter.allows(print.level, print.cat.as_deref())
+            || print.cat.as_ref().is_some_and(disabled)
+            || !self.sites.is_enabled(site)
+    }
+}
+
+/// A paused message: its invocation site, label entity and viewport.
+type PausedKey = (InvocationSiteKey, Option<Entity>, Option<Cow<'static, str>>);
+
+/// Commands received while the overlay is paused, see [`DebugOverlayPaused`].
+///
+/// Only the last command of each message is kept, except for samples, which
+/// are all added to their series once resumed. At most [`MAX_LINES`] `push`
+/// messages and as many samples are kept. Counters hold their total, so the
+/// last count has them all. Only the last clear, pin and edit of each kind
+/// of a site are kept, timeout extensions add up.
+#[derive(Default)]
+struct PausedCommands {
+    /// `None` for commands replaced by a later one.
+    commands: Vec<Option<Command>>,
+    /// Index in `commands` of the last command for a given message, with its
+    /// label entity and viewport.
+    latest: HashMap<PausedKey, usize>,
+    /// Index in `commands` of the last change of each kind to a site.
+    changes: HashMap<(InvocationSiteKey, SiteChange), usize>,
+    pushes: usize,
+    samples: usize,
+}
+/// A kind of command changing all the messages of a site.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum SiteChange {
+    Clear,
+    Pin,
+    Edit(mem::Discriminant<MessageEdit>),
+}
+impl PausedCommands {
+    fn push(&mut self, command: Command) {
+        let key = match &command {
+            Command::Push { .. } => {
+                if self.pushes < MAX_LINES {
+                    self.pushes += 1;
+                    self.commands.push(Some(command));
+                }
+                return;
+            }
+            Command::Sample { .. } => {
+                if self.samples < MAX_LINES {
+                    self.samples += 1;
+                    self.commands.push(Some(command));
+                }
+                return;
+            }
+            Command::Clear { key } => {
+                // Following commands must be applied after the clear, and
+                // previous changes only applied to the cleared messages.
+                self.latest.retain(|(site, ..), _| site != key);
+                let cleared = |(site, change): &(_, _)| site == key && *change != SiteChange::Clear;
+                self.changes.retain(|change, _| !cleared(change));
+                self.change(key.clone(), SiteChange::Clear, command);
+                return;
+            }
+            Command::ClearAll => {
+                // Previous commands would be cleared anyway.
+                self.drain();
+                self.commands.push(Some(command));
+                return;
+            }
+            Command::Pin { key, .. } => {
+                self.change(key.clone(), SiteChange::Pin, command);
+                return;
+            }
+            Command::Edit { key, edit } => {
+                let change = SiteChange::Edit(mem::discriminant(edit));
+                self.change(key.clone(), change, command);
+                return;
+            }
+            Command::Touch { key, options } => {
+                let target = match options.at {
+                    Some(WorldLabel::Entity(target)) => Some(target),
+                    _ => None,
+                };
+                let key = (key.clone(), target, options.target.clone());
+                // The message is refreshed anyway by its previous command.
+                if !self.latest.contains_key(&key) {
+                    self.latest.insert(key, self.commands.len());
+                    self.commands.push(Some(command));
+                }
+                return;
+            }
+            Command::Refresh { key, options, .. } => {
+                let target = match options.at {
+                    Some(WorldLabel::Entity(target)) => Some(target),
+                    _ => None,
+                };
+                (key.clone(), target, options.target.clone())
+            }
+            Command::Progress { key, options, .. } | Command::Count { key, options, .. } => {
+                (key.clone(), None, options.target.clone())
+            }
+        };
+        if let Some(&index) = self.latest.get(&key) {
+            self.commands[index] = Some(command);
+        } else {
+            self.latest.insert(key, self.commands.len());
+            self.commands.push(Some(command));
+        }
+    }
+    /// Apply `command` after the other commands, in place of the previous
+    /// `change` to `site`.
+    fn change(&mut self, site: InvocationSiteKey, change: SiteChange, command: Command) {
+        let index = self.changes.remove(&(site.clone(), change));
+        let previous = index.and_then(|index| self.commands[index].take());
+        let command = match (previous, command) {
+            (
+                Some(Command::Edit { edit: MessageEdit::ExtendTimeout(previous), .. }),
+                Command::Edit { key, edit: MessageEdit::ExtendTimeout(secs) },
+            ) => {
+                let edit = MessageEdit::ExtendTimeout(previous + secs);
+                Command::Edit { key, edit }
+            }
+            (_, command) => command,
+        };
+        self.changes.insert((site, change), self.commands.len());
+        self.commands.push(Some(command));
+    }
+    fn drain(&mut self) -> Vec<Command> {
+        self.latest.clear();
+        self.changes.clear();
+        self.pushes = 0;
+        self.samples = 0;
+        mem::take(&mut self.commands)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Whether the overlay is paused, and what happened in the meantime.
+#[derive(SystemParam)]
+struct Pause<'w, 's> {
+    paused: Res<'w, DebugOverlayPaused>,
+    clock: Res<'w, OverlayClock>,
+    commands: Local<'s, PausedCommands>,
+}
+
+/// Where received messages are recorded.
+#[derive(SystemParam)]
+struct Records<'w> {
+    time: Res<'w, Time>,
+    history: Option<ResMut<'w, OverlayHistory>>,
+    recording: Option<ResMut<'w, OverlayRecording>>,
+    taps: Option<ResMut<'w, RecordTaps>>,
+    scoped: Option<ResMut<'w, ScopedSites>>,
+}
+
+impl Records<'_> {
+    /// Add the text of a displayed message to the [`OverlayHistory`], and
+    /// mirror it to the log.
+    fn record(
+        &mut self,
+        options: &Options,
+        site: &InvocationSiteKey,
+        text: String,
+        print: &PrintOptions,
+    ) {
+        let text = markup::strip(&text);
+        if options.mirror_to_log && !print.logged {
+            mirror_to_log(site, &text, print.level);
+        }
+        if let Some(history) = self.history.as_mut() {
+            let color = options.color_of(print);
+            let site = site.clone();
+            let time = self.time.elapsed_seconds_f64();
+            history.record(HistoryEntry { time, site, text, color });
+        }
+    }
+    /// Add `command` to the [`OverlayRecording`] and send it to the record
+    /// taps, once per time it was printed.
+    fn tap(&mut self, command: &ResolvedCommand, repeats: usize) {
+        let recorded = self.recording.is_some() || self.taps.is_some();
+        let Some(record) = message_record(command).filter(|_| recorded) else {
+            return;
+        };
+        let now = self.time.elapsed_seconds_f64();
+        for _ in 0..repeats {
+            if let Some(taps) = self.taps.as_mut() {
+                taps.send(&MessageRecord { time: now, ..record.clone() });
+            }
+            if let Some(recording) = self.recording.as_mut() {
+                recording.push(now, record.clone());
+            }
+        }
+    }
+}
+
+/// Remember the site of `command` if it is scoped to a state, so that it is
+/// cleared when the state exits.
+fn scope_site(scoped: &mut ScopedSites, command: &ResolvedCommand) {
+    let (Command::Refresh { key, options, .. }
+    | Command::Push { key, options, .. }
+    | Command::Progress { key, options, .. }) = command
+    else {
+        return;
+    };
+    if let Some(scope) = options.scope {
+        scoped.0.entry(scope).or_default().insert(key.clone());
+    }
+}
+
+/// `command` as recorded in an [`OverlayRecording`], `None` for commands
+/// that are not displayed as is.
+fn message_record(command: &ResolvedCommand) -> Option<MessageRecord> {
+    let print = |text| RecordedChange::Print(text);
+    let (site, change, options) = match command {
+        Command::Refresh { key, text, options } | Command::Push { key, text, options } => {
+            (Some(key), print(text.clone()), Some(options))
+        }
+        Command::Progress { key, label, fraction, options } => (
+            Some(key),
+            print(progress_text(label, *fraction)),
+            Some(options),
+        ),
+        Command::Clear { key } => (Some(key), RecordedChange::Clear, None),
+        Command::ClearAll => (None, RecordedChange::ClearAll, None),
+        Command::Sample { pending, .. } | Command::Count { pending, .. } => match *pending {},
+        Command::Touch { .. } | Command::Pin { .. } | Command::Edit { .. } => return None,
+    };
+    let default = PrintOptions::default();
+    let print = options.unwrap_or(&default);
+    Some(MessageRecord {
+        time: 0.0,
+        site: site.map_or_else(String::new, MessageRecord::site_name),
+        change,
+        color: print.color,
+        timeout: print.timeout,
+        push: matches!(command, Command::Push { .. }),
+    })
+}
+
+#[derive(SystemParam)]
+struct ProgressBars<'w, 's> {
+    bars: Query<
+        'w,
+        's,
+        (
+            &'static mut Message,
+            &'static ProgressBar,
+            &'static mut BackgroundColor,
+            &'static mut Outline,
+        ),
+        Without<Text>,
+    >,
+    labels: Query<'w, 's, &'static mut Text, Without<Message>>,
+    fills: Query<'w, 's, (&'static mut Style, &'static mut BackgroundColor), Without<Message>>,
+}
+impl ProgressBars<'_, '_> {
+    /// Update the progress bar `entity`, returns `false` if it is not a
+    /// progress bar.
+    fn update(
+        &mut self,
+        entity: Entity,
+        label: String,
+        fraction: f32,
+        color: Color,
+        print: &PrintOptions,
+        clock: &OverlayClock,
+    ) -> bool {
+        let Ok((mut message, bar, mut bg, mut outline)) = self.bars.get_mut(entity) else {
+            return false;
+        };
+        message.expire_in(clock, print.timeout);
+        message.updated = clock.elapsed;
+        message.frames_left = print.frames;
+        message.pinned |= print.pin;
+        message.indent = print.indent;
+        message.alpha = print.alpha;
+        message.level = print.level;
+        message.cat.clone_from(&print.cat);
+        message.color = color;
+        message.bg = print.bg.unwrap_or(Color::NONE);
+        message.border = print.border.unwrap_or(Color::NONE);
+        if bg.0 != message.bg {
+            bg.0 = message.bg;
+        }
+        if outline.color != message.border {
+            outline.color = message.border;
+        }
+        if let Ok(mut text) = self.labels.get_mut(bar.label) {
+            let section = &mut text.sections[0];
+            if section.value != label {
+                section.value = label;
+            }
+            if section.style.color != color {
+                section.style.color = color;
+            }
+        }
+        if let Ok((mut style, mut fill)) = self.fills.get_mut(bar.fill) {
+            let width = Val::Percent(fraction * 100.0);
+            if style.width != width {
+                style.width = width;
+            }
+            if fill.0 != color {
+                fill.0 = color;
+            }
+        }
+        true
+    }
+}
+
+/// The panel drawn behind messages, see [`OverlayPlugin::background_color`].
+#[derive(Component)]
+struct Background;
+
+fn spawn_overlay_root(
+    mut cmds: Commands,
+    options: Res<Options>,
+    location: Res<DebugOverlayLocation>,
+) {
+    let root = cmds.spawn(root_node(&location)).id();
+    if options.dedicated_camera {
+        let camera = Camera2dBundle {
+            camera: Camera {
+                order: OVERLAY_CAMERA_ORDER,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            ..default()
+        };
+        let layer = RenderLayers::layer((RenderLayers::TOTAL_LAYERS - 1) as u8);
+        let camera = cmds.spawn((camera, layer)).id();
+        cmds.entity(root).insert(TargetCamera(camera));
+    }
+    if options.single_text {
+        let padding = UiRect::all(Val::Px(options.padding()));
+        let style = Style {
+            position_type: PositionType::Absolute,
+            padding,
+            ..default()
+        };
+        let background_color = options.background.unwrap_or(Color::NONE).into();
+        let text = TextBundle { style, background_color, ..default() };
+        cmds.spawn((text, SingleText)).set_parent(root);
+    } else if let Some(color) = options.background {
+        let style = Style { position_type: PositionType::Absolute, ..default() };
+        let background = NodeBundle {
+            style,
+            background_color: color.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        };
+        cmds.spawn((background, Background)).set_parent(root);
+    }
+    cmds.insert_resource(OverlayRoot {
+        main: root,
+        windows: HashMap::new(),
+        viewports: HashMap::new(),
+    });
+}
+
+#[derive(Copy, Clone)]
+struct PushEntry {
+    entity: Entity,
+    expired: f64,
+}
+#[derive(Default)]
+struct PushList(Vec<PushEntry>);
+
+/// The last `push` message, that identical ones are merged into, see
+/// [`OverlayPlugin::coalesce`].
+struct LastPush {
+    entity: Entity,
+    key: InvocationSiteKey,
+    text: String,
+    /// How many times the message was pushed.
+    count: usize,
+    expiration: f64,
+}
+
+/// Entities displaying `push` messages.
+#[derive(Default)]
+struct Pushes {
+    entities: PushList,
+    /// World labels are not laid out, so they can't share entities with other
+    /// messages.
+    labels: PushList,
+    last: Option<LastPush>,
+}
+impl Pushes {
+    fn list(&mut self, is_label: bool) -> &mut PushList {
+        if is_label {
+            &mut self.labels
+        } else {
+            &mut self.entities
+        }
+    }
+}
+
+/// Displayed [`screen_counter!`] messages, their text is computed again each
+/// frame, so that their rate and `reset:` window are up to date even when
+/// they are no longer incremented.
+#[derive(Default)]
+struct Counters(HashMap<InvocationSiteKey, CounterMessage>);
+struct CounterMessage {
+    label: String,
+    counter: &'static Counter,
+    options: PrintOptions,
+    /// When the message expires, it is no longer refreshed afterward.
+    expiration: f64,
+}
+impl Counters {
+    /// Refresh the message of `command` while it is displayed, if it is a
+    /// counter.
+    fn track(&mut self, command: &Command, current_time: f64) {
+        if let Command::Count { key, label, counter, options, .. } = command {
+            let expiration = current_time + options.timeout;
+            let (label, options) = (label.clone(), options.clone());
+            let message = CounterMessage { label, counter, options, expiration };
+            self.0.insert(key.clone(), message);
+        }
+    }
+    /// Refreshes of the outdated counter messages, they expire as if they
+    /// were not refreshed.
+    fn refreshes(&mut self, current_time: f64) -> Vec<Command> {
+        self.0
+            .retain(|_, message| message.expiration > current_time);
+        let outdated = self
+            .0
+            .iter()
+            .filter(|(_, message)| message.counter.outdated());
+        let refresh = |(key, message): (&InvocationSiteKey, &CounterMessage)| {
+            let timeout = message.expiration - current_time;
+            Command::Refresh {
+                key: key.clone(),
+                text: message.counter.text(&message.label),
+                options: PrintOptions { timeout, ..message.options.clone() },
+            }
+        };
+        outdated.map(refresh).collect()
+    }
+}
+
+/// The last values printed with the `tail` option of [`screen_print!`], per
+/// invocation site, newest first.
+#[derive(Default)]
+struct Tails(HashMap<InvocationSiteKey, VecDeque<String>>);
+impl Tails {
+    /// Add `text` to the values of `key`, keeping the `len` newest ones, and
+    /// return them one per line, the newest on top.
+    fn push(&mut self, key: &InvocationSiteKey, text: &str, len: usize) -> String {
+        let values = self.0.entry(key.clone()).or_default();
+        values.push_front(text.trim_end_matches('\n').to_owned());
+        values.truncate(len.max(1));
+        let values: Vec<_> = values.iter().map(String::as_str).collect();
+        values.join("\n")
+    }
+}
+
+/// `shown` followed by how many times it was pushed.
+fn coalesced_text(shown: &str, count: usize) -> String {
+    match count {
+        1 => shown.to_owned(),
+        count => format!("{} \u{d7} {count}\n", shown.trim_end_matches('\n')),
+    }
+}
+
+/// Merge runs of identical `push` commands, with how many commands each one
+/// stands for.
+fn coalesce_pushes(
+    commands: impl Iterator<Item = Command>,
+    enabled: bool,
+) -> impl Iterator<Item = (Command, usize)> {
+    let identical = |command: &Command, next: &Command| match (command, next) {
+        (
+            Command::Push { key, text, options },
+            Command::Push {
+                key: next_key,
+                text: next_text,
+                options: next_options,
+            },
+        ) => {
+            let coalesce = options.coalesce && next_options.coalesce;
+            coalesce && options.at.is_none() && key == next_key && text == next_text
+        }
+        _ => false,
+    };
+    let mut commands = commands.peekable();
+    iter::from_fn(move || {
+        let command = commands.next()?;
+        let mut count = 1;
+        while enabled && commands.next_if(|next| identical(&command, next)).is_some() {
+            count += 1;
+        }
+        Some((command, count))
+    })
+}
+impl PushList {
+    fn new_or_allocate(
+        &mut self,
+        spawn_new: impl FnOnce() -> Entity,
+        current: f64,
+        timeout: f64,
+    ) -> Option<Entity> {
+        let free_existing = self.0.iter_mut().find(|entry| entry.expired < current);
+        let ret = free_existing.as_ref().map(|entry| entry.entity);
+        let new_entry = || PushEntry { entity: spawn_new(), expired: current + timeout };
+        match free_existing {
+            Some(to_update) => to_update.expired = current + timeout,
+            None => self.0.push(new_entry()),
+        }
+        ret
+    }
+    /// Remove and return entities idle for more than `grace` seconds,
+    /// keeping at most `keep` idle entities.
+    fn prune(&mut self, current: f64, grace: f64, keep: usize) -> Vec<Entity> {
+        let idle = self
+            .0
+            .iter()
+            .filter(|entry| entry.expired < current)
+            .count();
+        let mut to_remove = idle.saturating_sub(keep);
+        let mut pruned = Vec::new();
+        self.0.retain(|entry| {
+            let prune = to_remove > 0 && entry.expired + grace < current;
+            if prune {
+                to_remove -= 1;
+                pruned.push(entry.entity);
+            }
+            !prune
+        });
+        pruned
+    }
+}
+
+/// The background panel of a message, see [`PrintOptions::bg`] and
+/// [`PrintOptions::border`].
+type Panel<'a> = (
+    &'a mut BackgroundColor,
+    &'a mut Outline,
+    &'a mut BorderColor,
+);
+
+/// A displayed message, see [`Message`].
+type MessageNode<'a> = (
+    &'a mut Text,
+    &'a mut Message,
+    Option<&'a mut WorldLabel>,
+    Option<Panel<'a>>,
+    Option<&'a Blink>,
+);
+
+/// Entities displaying the messages, and what spawning new ones requires.
+#[derive(SystemParam)]
+struct MessageEntities<'w, 's> {
+    messages: Query<'w, 's, MessageNode<'static>>,
+    progress_bars: ProgressBars<'w, 's>,
+    /// The entity displaying the message of each site, per labeled entity or
+    /// viewport.
+    key_entities: Local<'s, HashMap<(InvocationSiteKey, Option<Entity>), Entity>>,
+    pushes: Local<'s, Pushes>,
+    cmds: Commands<'w, 's>,
+    root: ResMut<'w, OverlayRoot>,
+    windows: WindowCameras<'w, 's>,
+    options: Res<'w, Options>,
+    location: Res<'w, DebugOverlayLocation>,
+    scale: Res<'w, DebugOverlayScale>,
+    visibility: Res<'w, OverlayVisibility>,
+}
+
+/// The commands handled during a frame by [`update_messages_as_per_commands`],
+/// with the changes applied once they are all handled.
+#[derive(Default)]
+struct Frame {
+    clock: OverlayClock,
+    /// Time printed before the messages, see [`OverlayPlugin::timestamp`].
+    timestamp: Option<String>,
+    /// Entities spawned during this frame, they can't be updated yet.
+    fresh: HashSet<Entity>,
+    /// Entities to despawn because they were replaced.
+    replaced: Vec<Entity>,
+    /// Messages to expire now, they are recycled for `push` messages.
+    cleared: Vec<Entity>,
+    /// `push` messages removed with `screen_clear!()`.
+    expired_pushes: Vec<Entity>,
+    /// `Blink` components to insert, or remove when `None`.
+    blinks: Vec<(Entity, Option<f64>)>,
+    /// Messages to pin or unpin.
+    pins: Vec<(Entity, bool)>,
+    edits: Vec<(Entity, MessageEdit)>,
+    /// Watched messages whose value changed.
+    flashed: Vec<Entity>,
+    /// Messages displaying the difference with their previous text.
+    diffed: Vec<Entity>,
+    /// Whether a message printed with `beep` showed up.
+    beeped: bool,
+    /// Sites of the messages created this frame, for the `DebugOverlaySites`.
+    created_sites: Vec<InvocationSiteKey>,
+}
+
+impl MessageEntities<'_, '_> {
+    /// Forget the despawned messages and viewports, and keep `push` entities
+    /// from being reused while their message is displayed.
+    fn forget_despawned(&mut self, clock: &OverlayClock) {
+        // Labels of despawned entities are despawned in `place_world_labels`
+        self.key_entities.retain(|_, entity| {
+            self.messages.contains(*entity) || self.progress_bars.bars.contains(*entity)
+        });
+        let Pushes { entities, labels, .. } = &mut *self.pushes;
+        labels
+            .0
+            .retain(|entry| self.messages.contains(entry.entity));
+        // Messages printed with the `clock` option may expire later than
+        // planned, and pinned ones don't expire, their entities can't be
+        // reused before.
+        for entry in entities.0.iter_mut().chain(&mut labels.0) {
+            if let Ok((_, message, ..)) = self.messages.get(entry.entity) {
+                let expiration = match message.pinned {
+                    true => clock.elapsed,
+                    false => clock.elapsed + message.remaining(clock),
+                };
+                entry.expired = entry.expired.max(expiration + self.options.fade);
+            }
+        }
+        // Messages of removed viewports are despawned with their root.
+        self.root.viewports.retain(|camera, viewport_root| {
+            let exists = self.windows.viewports.contains(*camera);
+            if !exists {
+                self.cmds.entity(*viewport_root).despawn_recursive();
+            }
+            exists
+        });
+    }
+
+    /// Messages at the same site are distinct per labeled entity or viewport.
+    fn target_of(&self, print: &PrintOptions) -> Option<Entity> {
+        match print.at {
+            Some(WorldLabel::Entity(target)) => Some(target),
+            _ => self.windows.viewport(print.target.as_deref()),
+        }
+    }
+
+    /// Entities displaying the message of `site`.
+    fn site_entities<'a>(
+        &'a self,
+        site: &'a InvocationSiteKey,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        let of_site = self
+            .key_entities
+            .iter()
+            .filter(move |((key, _), _)| key == site);
+        of_site.map(|(_, entity)| *entity)
+    }
+
+    /// `text` with the timestamp, prefix and icon it is printed with.
+    fn prefixed(
+        &self,
+        frame: &Frame,
+        key: &InvocationSiteKey,
+        text: &str,
+        print: &PrintOptions,
+    ) -> String {
+        let prefix = match (&self.options.prefix, print.system) {
+            (_, Some(system)) if print.sys => Some(format!("[{system}]")),
+            (MessagePrefix::Hidden, _) => None,
+            (MessagePrefix::System, Some(system)) => Some(format!("[{system}]")),
+            (prefix, _) => prefix.format(key),
+        };
+        let prefix = prefix.filter(|_| !print.no_prefix);
+        let text = match (&frame.timestamp, prefix) {
+            (Some(time), Some(prefix)) => format!("{time} {prefix} {text}\n"),
+            (Some(time), None) => format!("{time} {text}\n"),
+            (None, Some(prefix)) => format!("{prefix} {text}\n"),
+            (None, None) => format!("{text}\n"),
+        };
+        match &print.icon {
+            Some(Icon::Glyph(glyph)) => format!("{glyph} {text}"),
+            _ => text,
+        }
+    }
+
+    fn text_style(&self, color: Color, print: &PrintOptions) -> TextStyle {
+        let options = &self.options;
+        TextStyle {
+            color,
+            font_size: print.size.unwrap_or(options.font_size) * self.scale.scale,
+            font: if print.monospace {
+                &options.table_font
+            } else {
+                &options.font
+            }
+            .clone(),
+        }
+    }
+
+    /// The text of a new message, after its countdown if it is shown.
+    fn message_text(&self, color: Color, print: &PrintOptions, spans: Vec<Span>) -> Text {
+        let style = self.text_style(color, print);
+        let countdown = self
+            .options
+            .show_countdown
+            .then(|| TextSection::new("", style.clone()));
+        let spans = spans.into_iter().map(|(span, span_color)| {
+            let color = span_color.unwrap_or(color);
+            TextSection::new(span, TextStyle { color, ..style.clone() })
+        });
+        Text::from_sections(countdown.into_iter().chain(spans))
+    }
+
+    /// The root node of the window or viewport a message is printed to.
+    fn parent_of(&mut self, print: &PrintOptions) -> Entity {
+        let primary_window = self.windows.primary.get_single().ok();
+        let default_window = self.options.default_window.normalize(primary_window);
+        let window = print
+            .window
+            .or(default_window.map(|window| window.entity()));
+        let viewport = self.windows.viewport(print.target.as_deref());
+        // Roots spawned while the overlay is hidden are hidden too.
+        let visibility = root_visibility(&self.visibility);
+        let Self { cmds, root, windows, location, .. } = self;
+        let new_node = || NodeBundle { visibility, ..root_node(location) };
+        match (viewport, window) {
+            (Some(camera), _) => {
+                let new_root = || cmds.spawn((new_node(), TargetCamera(camera))).id();
+                *root.viewports.entry(camera).or_insert_with(new_root)
+            }
+            (None, Some(window)) if Some(window) != primary_window => {
+                let new_root = || {
+                    let on_window = |(_, camera): &(Entity, &Camera)| {
+                        let target = camera.target.normalize(primary_window);
+                        matches!(target, Some(NormalizedRenderTarget::Window(w)) if w.entity() == window)
+                    };
+                    let camera = match windows.cameras.iter().find(on_window) {
+                        Some((camera, _)) => camera,
+                        None => {
+                            let target = RenderTarget::Window(WindowRef::Entity(window));
+                            let camera = Camera { target, ..default() };
+                            cmds.spawn(Camera2dBundle { camera, ..default() }).id()
+                        }
+                    };
+                    cmds.spawn((new_node(), TargetCamera(camera))).id()
+                };
+                *root.windows.entry(window).or_insert_with(new_root)
+            }
+            (None, _) => root.main,
+        }
+    }
+
+    /// Style of the UI node of `message`, with its icon and outline.
+    fn node_style(&self, message: &Message) -> (Style, BorderColor, Outline) {
+        let style = Style { position_type: PositionType::Absolute, ..default() };
+        let font_size = message.size.unwrap_or(self.options.font_size) * self.scale.scale;
+        let style = match message.icon {
+            Some(_) => Icon::square_style(style, font_size),
+            None => style,
+        };
+        let border_color = BorderColor(message.icon.unwrap_or(Color::NONE));
+        // Drawn outside of the node, so that it doesn't shift the text.
+        let outline = Outline::new(Val::Px(1.0), Val::ZERO, message.border);
+        (style, border_color, outline)
+    }
+
+    /// Spawn an entity displaying a new message, a progress bar filled to
+    /// `progress` if it is `Some`.
+    fn spawn(
+        &mut self,
+        frame: &mut Frame,
+        key: &InvocationSiteKey,
+        text: String,
+        print: &PrintOptions,
+        progress: Option<f32>,
+    ) -> Entity {
+        frame.beeped = frame.beeped || print.beep;
+        frame.created_sites.push(key.clone());
+        let color = self.options.color_of(print);
+        let spans = markup::parse(&text);
+        let spans = match print.lines {
+            Some(lines) => markup::truncate(spans, lines),
+            None => spans,
+        };
+        let mut message = Message {
+            bg: print.bg.unwrap_or(Color::NONE),
+            border: print.border.unwrap_or(Color::NONE),
+            size: print.size,
+            spans: spans.iter().map(|(_, span)| *span).collect(),
+            text: text.clone(),
+            align: print.align,
+            prio: print.prio,
+            frames_left: print.frames,
+            lines: print.lines,
+            clock: print.clock,
+            icon: Icon::square(print),
+            pinned: print.pin,
+            indent: print.indent,
+            alpha: print.alpha,
+            level: print.level,
+            cat: print.cat.clone(),
+            ..Message::new(key.clone(), frame.clock.elapsed, print.timeout, color)
+        };
+        message.expire_in(&frame.clock, print.timeout);
+        let entity = if self.options.single_text && print.at.is_none() {
+            // In single text mode, the message only holds the text, it is not
+            // a UI node, `render_single_text` copies it in the `SingleText`.
+            let text = self.message_text(color, print, spans);
+            self.cmds.spawn((text, message)).id()
+        } else if let Some(fraction) = progress {
+            self.spawn_progress_bar(message, text, print, fraction)
+        } else {
+            self.spawn_text(message, spans, print)
+        };
+        frame.fresh.insert(entity);
+        entity
+    }
+
+    fn spawn_text(&mut self, message: Message, spans: Vec<Span>, print: &PrintOptions) -> Entity {
+        let (style, border_color, outline) = self.node_style(&message);
+        let parent = self.parent_of(print);
+        let text = self.message_text(message.color, print, spans);
+        let text = TextBundle { text, style, ..default() };
+        let text = text.with_background_color(message.bg);
+        let mut entity = self.cmds.spawn((text, border_color, outline, message));
+        entity.insert(Visibility::Hidden).set_parent(parent);
+        if self.options.click_to_copy || print.lines.is_some() {
+            entity.insert(Interaction::default());
+        }
+        if let Some(label) = print.at {
+            entity.insert(label);
+        }
+        if let Some(rate) = print.blink {
+            entity.insert(Blink { rate });
+        }
+        entity.id()
+    }
+
+    /// Spawn a bar filled to `fraction`, after its `label`.
+    fn spawn_progress_bar(
+        &mut self,
+        message: Message,
+        label: String,
+        print: &PrintOptions,
+        fraction: f32,
+    ) -> Entity {
+        let (style, border_color, outline) = self.node_style(&message);
+        let parent = self.parent_of(print);
+        let (color, bg) = (message.color, message.bg);
+        let font_size = self.options.font_size * self.scale.scale;
+        let label = TextBundle::from_section(label, self.text_style(color, print));
+        let label = self.cmds.spawn(label).id();
+        let fill_style = Style {
+            width: Val::Percent(fraction * 100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        };
+        let fill = NodeBundle {
+            style: fill_style,
+            background_color: color.into(),
+            ..default()
+        };
+        let fill = self.cmds.spawn(fill).id();
+        let bar_style = Style {
+            width: Val::Px(font_size * 8.0),
+            height: Val::Px(font_size * 0.6),
+            ..default()
+        };
+        let track = color.with_a(color.a() * 0.25).into();
+        let bar = NodeBundle {
+            style: bar_style,
+            background_color: track,
+            ..default()
+        };
+        let bar = self.cmds.spawn(bar).add_child(fill).id();
+        let style = Style {
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(font_size / 2.0),
+            ..style
+        };
+        let node = NodeBundle {
+            style,
+            background_color: bg.into(),
+            border_color,
+            ..default()
+        };
+        let mut entity = self
+            .cmds
+            .spawn((node, outline, message, ProgressBar { label, fill }));
+        entity.insert(Visibility::Hidden).set_parent(parent);
+        entity.push_children(&[label, bar]).id()
+    }
+
+    /// Display `text` in the message `entity`, returns whether its text
+    /// changed.
+    fn update(
+        &mut self,
+        frame: &mut Frame,
+        entity: Entity,
+        key: &InvocationSiteKey,
+        text: &str,
+        print: &PrintOptions,
+    ) -> bool {
+        let Ok((mut ui_text, mut message, label, highlight, blink)) = self.messages.get_mut(entity)
+        else {
+            return false;
+        };
+        let (options, clock) = (&self.options, &frame.clock);
+        if blink.map(|blink| blink.rate) != print.blink {
+            frame.blinks.push((entity, print.blink));
+        }
+        if message.opacity(clock, options.fade).is_none() {
+            message.shown = clock.elapsed;
+            frame.beeped = frame.beeped || print.beep;
+        }
+        if message.site != *key {
+            message.site = key.clone();
+        }
+        message.clock = print.clock;
+        message.expire_in(clock, print.timeout);
+        message.updated = clock.elapsed;
+        message.color = options.color_of(print);
+        message.bg = print.bg.unwrap_or(Color::NONE);
+        message.border = print.border.unwrap_or(Color::NONE);
+        message.size = print.size;
+        message.align = print.align;
+        message.prio = print.prio;
+        message.frames_left = print.frames;
+        message.icon = Icon::square(print);
+        message.pinned |= print.pin;
+        message.indent = print.indent;
+        message.alpha = print.alpha;
+        message.level = print.level;
+        message.cat.clone_from(&print.cat);
+        let lines_changed = message.lines != print.lines;
+        message.lines = print.lines;
+        if let Some((mut bg, mut outline, mut border)) = highlight {
+            if bg.0 != message.bg {
+                bg.0 = message.bg;
+            }
+            if outline.color != message.border {
+                outline.color = message.border;
+            }
+            let icon_color = message.icon.unwrap_or(Color::NONE);
+            if border.0 != icon_color {
+                border.0 = icon_color;
+            }
+        }
+        let text_changed = message.text != text;
+        if text_changed {
+            let diff = print
+                .diff
+                .then(|| diff::spans(&message.text, text))
+                .flatten();
+            if diff.is_some() {
+                frame.diffed.push(entity);
+            }
+            let spans = diff.unwrap_or_else(|| markup::parse(text));
+            message.set_spans(&mut ui_text, spans);
+            message.text = text.to_owned();
+        } else if lines_changed {
+            message.set_spans(&mut ui_text, markup::parse(text));
+        }
+        let font_size = print.size.unwrap_or(options.font_size) * self.scale.scale;
+        let colors = message.section_colors(ui_text.sections.len());
+        for (section, color) in ui_text.sections.iter_mut().zip(colors) {
+            if section.style.color != color {
+                section.style.color = color;
+            }
+            if section.style.font_size != font_size {
+                section.style.font_size = font_size;
+            }
+        }
+        if let (Some(mut label), Some(new_label)) = (label, print.at) {
+            *label = new_label;
+        }
+        text_changed
+    }
+
+    /// Display a [`screen_print!`] message, in place of the previous one at
+    /// its site.
+    fn refresh(
+        &mut self,
+        frame: &mut Frame,
+        records: &mut Records,
+        tails: &mut Tails,
+        key: InvocationSiteKey,
+        text: String,
+        print: &PrintOptions,
+    ) {
+        let tail = print.tail.map(|len| tails.push(&key, &text, len));
+        let shown = self.prefixed(frame, &key, tail.as_deref().unwrap_or(&text), print);
+        let key = (key, self.target_of(print));
+        match self.key_entities.get(&key).copied() {
+            Some(entity) if !frame.fresh.contains(&entity) => {
+                if self.update(frame, entity, &key.0, &shown, print) {
+                    records.record(&self.options, &key.0, text, print);
+                    if print.watch {
+                        frame.flashed.push(entity);
+                    }
+                }
+            }
+            existing => {
+                // Entities spawned during this frame can't be updated yet,
+                // they are replaced instead.
+                frame.replaced.extend(existing);
+                records.record(&self.options, &key.0, text, print);
+                let entity = self.spawn(frame, &key.0, shown, print, None);
+                self.key_entities.insert(key, entity);
+            }
+        }
+    }
+
+    /// Display a [`screen_push!`] message `repeats` times, merged with the
+    /// last one when identical, see [`OverlayPlugin::coalesce`].
+    fn push(
+        &mut self,
+        frame: &mut Frame,
+        records: &mut Records,
+        key: InvocationSiteKey,
+        text: String,
+        print: &PrintOptions,
+        repeats: usize,
+    ) {
+        // Coalesced pushes are recorded as many times as printed.
+        for _ in 0..repeats {
+            records.record(&self.options, &key, text.clone(), print);
+        }
+        let shown = self.prefixed(frame, &key, &text, print);
+        let current_time = frame.clock.elapsed;
+        // Fading out messages can't be reused yet.
+        let timeout = print.timeout + self.options.fade;
+        let coalesce = self.options.coalesce && print.coalesce && print.at.is_none();
+        let last = self.pushes.last.take().filter(|last| {
+            let visible = last.expiration > current_time && !frame.fresh.contains(&last.entity);
+            coalesce && visible && last.key == key && last.text == text
+        });
+        if let Some(mut last) = last {
+            last.count += repeats;
+            last.expiration = current_time + print.timeout;
+            let shown = coalesced_text(&shown, last.count);
+            self.update(frame, last.entity, &key, &shown, print);
+            let mut entries = self.pushes.entities.0.iter_mut();
+            if let Some(entry) = entries.find(|entry| entry.entity == last.entity) {
+                entry.expired = current_time + timeout;
+            }
+            self.pushes.last = Some(last);
+            return;
+        }
+        let shown = coalesced_text(&shown, repeats);
+        // Taken out of `pushes` while spawning, which borrows `self`.
+        let is_label = print.at.is_some();
+        let mut push_list = mem::take(self.pushes.list(is_label));
+        let mut spawned = None;
+        let spawn = || {
+            let entity = self.spawn(frame, &key, shown.clone(), print, None);
+            spawned = Some(entity);
+            entity
+        };
+        let reused = push_list.new_or_allocate(spawn, current_time, timeout);
+        *self.pushes.list(is_label) = push_list;
+        if let Some(entity) = reused {
+            frame.created_sites.push(key.clone());
+            self.update(frame, entity, &key, &shown, print);
+        }
+        if let Some(entity) = reused.or(spawned).filter(|_| coalesce) {
+            let expiration = current_time + print.timeout;
+            self.pushes.last = Some(LastPush { entity, key, text, count: repeats, expiration });
+        }
+    }
+
+    /// Display a [`screen_progress!`] bar, in place of the previous one at
+    /// its site.
+    fn progress(
+        &mut self,
+        frame: &mut Frame,
+        key: InvocationSiteKey,
+        label: &str,
+        fraction: f32,
+        print: &PrintOptions,
+    ) {
+        let shown = self.prefixed(frame, &key, label, print);
+        let shown = shown.trim_end().to_owned();
+        let color = self.options.color_of(print);
+        let key = (key, self.windows.viewport(print.target.as_deref()));
+        let existing = self.key_entities.get(&key).copied();
+        let updated = existing.is_some_and(|entity| {
+            let bars = &mut self.progress_bars;
+            !frame.fresh.contains(&entity)
+                && bars.update(entity, shown.clone(), fraction, color, print, &frame.clock)
+        });
+        if !updated {
+            frame.replaced.extend(existing);
+            let entity = self.spawn(frame, &key.0, shown, print, Some(fraction));
+            self.key_entities.insert(key, entity);
+        }
+    }
+
+    /// Clear the messages of the sites matching `cleared`, for all labeled
+    /// entities and viewports.
+    fn clear(&mut self, frame: &mut Frame, cleared: impl Fn(&InvocationSiteKey) -> bool) {
+        self.key_entities.retain(|(site, _), entity| {
+            let clear = cleared(site);
+            if clear {
+                frame.cleared.push(*entity);
+            }
+            !clear
+        });
+    }
+
+    /// Clear all messages, `push` messages fade out before they are reused.
+    fn clear_all(&mut self, frame: &mut Frame) {
+        frame
+            .cleared
+            .extend(self.key_entities.drain().map(|(_, entity)| entity));
+        let expired = frame.clock.elapsed + self.options.fade;
+        let Pushes { entities, labels, last } = &mut *self.pushes;
+        *last = None;
+        for list in [entities, labels] {
+            list.0.retain_mut(|entry| {
+                if frame.fresh.contains(&entry.entity) {
+                    frame.replaced.push(entry.entity);
+                    return false;
+                }
+                entry.expired = entry.expired.min(expired);
+                frame.expired_pushes.push(entry.entity);
+                true
+            });
+        }
+    }
+
+    /// Extend the timeout of the message printed with `print` at `key`.
+    fn touch(&self, frame: &mut Frame, key: InvocationSiteKey, print: &PrintOptions) {
+        let entity = self
+            .key_entities
+            .get(&(key, self.target_of(print)))
+            .copied();
+        if let Some(entity) = entity.filter(|entity| !frame.fresh.contains(entity)) {
+            frame
+                .edits
+                .push((entity, MessageEdit::Touch(print.timeout)));
+        }
+    }
+
+    /// Pin, unpin and edit the messages as requested during `frame`.
+    fn edit(&mut self, frame: &Frame) {
+        let (clock, fade) = (frame.clock, self.options.fade);
+        for &(entity, pinned) in &frame.pins {
+            if let Ok((_, mut message, ..)) = self.messages.get_mut(entity) {
+                message.pinned = pinned;
+            } else if let Ok((mut message, ..)) = self.progress_bars.bars.get_mut(entity) {
+                message.pinned = pinned;
+            } else {
+                // Entities spawned during this frame can't be queried yet.
+                self.cmds.add(move |world: &mut World| {
+                    if let Some(mut message) = world.get_mut::<Message>(entity) {
+                        message.pinned = pinned;
+                    }
+                });
+            }
+        }
+        for &(entity, edit) in &frame.edits {
+            if let Ok((_, mut message, ..)) = self.messages.get_mut(entity) {
+                edit.apply(&mut message, &clock, fade);
+            } else if let Ok((mut message, ..)) = self.progress_bars.bars.get_mut(entity) {
+                edit.apply(&mut message, &clock, fade);
+            } else {
+                self.cmds.add(move |world: &mut World| {
+                    if let Some(mut message) = world.get_mut::<Message>(entity) {
+                        edit.apply(&mut message, &clock, fade);
+                    }
+                });
+            }
+        }
+        for &entity in &frame.expired_pushes {
+            if let Ok((_, mut message, ..)) = self.messages.get_mut(entity) {
+                message.expiration = message.expiration.min(clock.now(message.clock));
+                message.pinned = false;
+            }
+        }
+    }
+
+    /// Expire the messages cleared during `frame`, they are recycled for
+    /// `push` messages.
+    fn expire_cleared(&mut self, frame: &mut Frame) {
+        let recycled = frame.clock.elapsed + self.options.fade;
+        for entity in mem::take(&mut frame.cleared) {
+            if frame.fresh.contains(&entity) {
+                frame.replaced.push(entity);
+            } else if let Ok((_, mut message, label, ..)) = self.messages.get_mut(entity) {
+                message.expiration = frame.clock.now(message.clock);
+                message.pinned = false;
+                let entry = PushEntry { entity, expired: recycled };
+                self.pushes.list(label.is_some()).0.push(entry);
+            } else if self.progress_bars.bars.contains(entity) {
+                // Progress bars can't be reused for `push` messages.
+                self.cmds.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    /// Add the effects of the messages changed during `frame`, and despawn
+    /// the replaced and unused entities.
+    fn finish(&mut self, frame: Frame) {
+        #[cfg(feature = "audio")]
+        if frame.beeped {
+            let sound = self.options.beep_sound;
+            self.cmds
+                .add(move |world: &mut World| crate::audio::beep(world, sound));
+        }
+        let current_time = frame.clock.elapsed;
+        let (grace, keep) = (self.options.pool_grace, self.options.pool_size);
+        let pruned_entities = self.pushes.entities.prune(current_time, grace, keep);
+        let pruned_labels = self.pushes.labels.prune(current_time, grace, keep);
+        for (entity, blink) in frame.blinks {
+            match blink {
+                Some(rate) => self.cmds.entity(entity).insert(Blink { rate }),
+                None => self.cmds.entity(entity).remove::<Blink>(),
+            };
+        }
+        let until = current_time + WATCH_FLASH_DURATION;
+        for entity in frame.flashed {
+            self.cmds.entity(entity).insert(Flash { until });
+        }
+        let until = current_time + DIFF_DURATION;
+        for entity in frame.diffed {
+            self.cmds.entity(entity).insert(Diff { until });
+        }
+        let despawned = pruned_entities.into_iter().chain(pruned_labels);
+        for entity in despawned.chain(frame.replaced) {
+            self.cmds.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn update_messages_as_per_commands(
+    mut entities: MessageEntities,
+    mut samples: Local<Samples>,
+    mut tails: Local<Tails>,
+    mut counters: Local<Counters>,
+    mut records: Records,
+    mut filters: Filters,
+    mut pause: Pause,
+) {
+    let channels = command_channels();
+    if filters.is_changed() {
+        // Filtered out messages must be sent again to show up.
+        channels.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    let clock = *pause.clock;
+    entities.forget_despawned(&clock);
+    let timestamp = &entities.options.timestamp;
+    let timestamp = timestamp.format(records.time.elapsed(), SystemTime::now());
+    let mut frame = Frame { clock, timestamp, ..default() };
+    if filters.sites.is_changed() {
+        // Messages of disabled sites are removed.
+        entities.clear(&mut frame, |site| !filters.sites.is_enabled(site));
+    }
+    // Only handle commands queued before this system ran, so that producers
+    // running in parallel can't keep it busy forever.
+    let pending = channels.queue.len();
+    let queued = iter::from_fn(|| channels.queue.pop().ok()).take(pending);
+    if pause.paused.paused {
+        queued.for_each(|command| pause.commands.push(command));
+        // Messages of sites disabled while paused are still removed.
+        entities.expire_cleared(&mut frame);
+        entities.finish(frame);
+        return;
+    }
+    let current_time = clock.elapsed;
+    let queued = pause.commands.drain().into_iter().chain(queued);
+    let queued = queued.chain(counters.refreshes(current_time));
+    let dropped = channels.take_dropped();
+    let coalescing = entities.options.coalesce;
+    for (message, repeats) in coalesce_pushes(queued.chain(dropped), coalescing) {
+        // Only UI nodes can display bars.
+        let bars = match &message {
+            Command::Progress { options: print, .. } => {
+                !entities.options.single_text && print.at.is_none()
+            }
+            _ => true,
+        };
+        counters.track(&message, current_time);
+        let message = message.resolve(&mut samples, bars);
+        if let Some(scoped) = records.scoped.as_mut() {
+            scope_site(scoped, &message);
+        }
+        records.tap(&message, repeats);
+        match message {
+            Command::Refresh { key, options, .. }
+            | Command::Push { key, options, .. }
+            | Command::Progress { key, options, .. }
+            | Command::Touch { key, options }
+                if filters.hides(&key, &options) => {}
+            Command::Refresh { key, text, options } => {
+                let (records, tails) = (&mut records, &mut tails);
+                entities.refresh(&mut frame, records, tails, key, text, &options);
+            }
+            Command::Push { key, text, options } => {
+                entities.push(&mut frame, &mut records, key, text, &options, repeats);
+            }
+            Command::Progress { key, label, fraction, options } => {
+                entities.progress(&mut frame, key, &label, fraction, &options);
+            }
+            Command::Clear { key } => {
+                samples.remove(&key);
+                tails.0.remove(&key);
+                counters.0.remove(&key);
+                entities.clear(&mut frame, |site| *site == key);
+            }
+            Command::ClearAll => {
+                *samples = Samples::default();
+                *tails = Tails::default();
+                *counters = Counters::default();
+                entities.clear_all(&mut frame);
+            }
+            Command::Touch { key, options } => entities.touch(&mut frame, key, &options),
+            Command::Pin { key, pinned } => {
+                let pinned_entities = entities.site_entities(&key);
+                frame
+                    .pins
+                    .extend(pinned_entities.map(|entity| (entity, pinned)));
+            }
+            Command::Edit { key, edit } => {
+                let edited = entities.site_entities(&key);
+                frame.edits.extend(edited.map(|entity| (entity, edit)));
+            }
+            Command::Sample { pending, .. } | Command::Count { pending, .. } => match pending {},
+        }
+    }
+    // Only toggling sites counts as a change, not discovering them.
+    let sites = filters.sites.bypass_change_detection();
+    for site in &frame.created_sites {
+        sites.register(site);
+    }
+    entities.edit(&frame);
+    entities.expire_cleared(&mut frame);
+    entities.finish(frame);
+}
+
+/// Collect messages in the [`OverlayMessageBuffer`] rather than displaying
+/// them, see [`OverlayPlugin::headless`].
+fn buffer_messages(
+    mut buffer: ResMut<OverlayMessageBuffer>,
+    mut samples: Local<Samples>,
+    mut filters: Filters,
+    options: Res<Options>,
+    time: Res<Time>,
+) {
+    let channels = command_channels();
+    if filters.is_changed() {
+        channels.generation.fetch_add(1, Ordering::Relaxed);
+    }
+    let pending = channels.queue.len();
+    let queued = iter::from_fn(|| channels.queue.pop().ok()).take(pending);
+    for command in queued.chain(channels.take_dropped()) {
+        let (site, text, print) = match command.resolve(&mut samples, true) {
+            Command::Refresh { key, text, options } | Command::Push { key, text, options } => {
+                (key, text, options)
+            }
+            Command::Sample { pending, .. } | Command::Count { pending, .. } => match pending {},
+            Command::Progress { key, label, fraction, options } => {
+                (key, progress_text(&label, fraction), options)
+            }
+            Command::Clear { key } => {
+                samples.remove(&key);
+                continue;
+            }
+            Command::ClearAll => {
+                *samples = Samples::default();
+                continue;
+            }
+            Command::Touch { .. } | Command::Pin { .. } | Command::Edit { .. } => continue,
+        };
+        if filters.hides(&site, &print) {
+            continue;
+        }
+        filters.sites.bypass_change_detection().register(&site);
+        let text = markup::strip(&text);
+        if options.mirror_to_log && !print.logged {
+            mirror_to_log(&site, &text, print.level);
+        }
+        let color = options.color_of(&print);
+        let time = time.elapsed_seconds_f64();
+        buffer.push(HistoryEntry { time, site, text, color }, !print.push);
+    }
+}
+
+/// Target of the messages logged by [`mirror_to_log`].
+const MIRROR_TARGET: &str = concat!(env!("CARGO_CRATE_NAME"), "::mirror");
+
+/// Log a displayed message, see [`OverlayPlugin::mirror_to_log`].
+fn mirror_to_log(site: &InvocationSiteKey, text: &str, level: Level) {
+    // `tracing` callsites are statics, their target can't be the site.
+    guard_logging(|| match level {
+        Level::Error => error!(target: MIRROR_TARGET, site = %site, "{text}"),
+        Level::Warn => warn!(target: MIRROR_TARGET, site = %site, "{text}"),
+        Level::Info => info!(target: MIRROR_TARGET, site = %site, "{text}"),
+        Level::Debug => debug!(target: MIRROR_TARGET, site = %site, "{text}"),
+        Level::Trace => trace!(target: MIRROR_TARGET, site = %site, "{text}"),
+    });
+}
+
+thread_local! {
+    /// Whether the overlay is logging, or displaying a log record, on this
+    /// thread, see [`guard_logging`].
+    static LOGGING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `log` unless the overlay is already logging on this thread.
+///
+/// Both the mirroring of messages to the log and the loggers displaying log
+/// records in the overlay run through this, so that a record emitted while
+/// handling another, for example by a `Display` impl that logs, is dropped
+/// instead of recursing forever.
+pub(crate) fn guard_logging(log: impl FnOnce()) {
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            LOGGING.with(|logging| logging.set(false));
+        }
+    }
+    if LOGGING.with(|logging| logging.replace(true)) {
+        return;
+    }
+    let _reset = Reset;
+    log();
+}
+
+/// Keep the producer side filters of [`CommandChannels`] in sync with the
+/// filter resources. Messages must all be sent while they are recorded.
+fn sync_print_gate(
+    filter: Res<OverlayFilter>,
+    categories: Res<DebugOverlayCategories>,
+    recording: Option<Res<OverlayRecording>>,
+    taps: Option<Res<RecordTaps>>,
+    mut recorded: Local<bool>,
+) {
+    let is_recorded = recording.is_some() || taps.is_some();
+    if filter.is_changed() || categories.is_changed() || is_recorded != *recorded {
+        *recorded = is_recorded;
+        let filters = (filter.clone(), categories.clone());
+        command_channels().set_filters((!is_recorded).then_some(filters));
+    }
+}
+
+fn sync_overflow_policy(policy: Res<OverflowPolicy>) {
+    if policy.is_changed() {
+        command_channels()
+            .policy
+            .store(*policy as u8, Ordering::Relaxed);
+    }
+}
+
+/// Position `style` at `offset` away from `anchor`, and `indent` away from
+/// the screen edge.
+fn set_position(style: &mut Style, anchor: Anchor, offset: f32, indent: f32) {
+    use Anchor::*;
+    let offset = Val::Px(offset);
+    let indent = Val::Px(indent);
+    style.margin = UiRect::default();
+    (style.top, style.bottom) = match anchor {
+        TopLeft | TopRight => (offset, Val::Auto),
+        BottomLeft | BottomRight => (Val::Auto, offset),
+        CenterLeft | CenterRight => {
+            style.margin.top = offset;
+            (Val::Percent(50.0), Val::Auto)
+        }
+    };
+    (style.left, style.right) = match anchor {
+        TopLeft | BottomLeft | CenterLeft => (indent, Val::Auto),
+        TopRight | BottomRight | CenterRight => (Val::Auto, indent),
+    };
+}
+
+/// Buttons of the connected gamepads, see [`OverlayPlugin::gamepad`].
+#[derive(SystemParam)]
+struct GamepadInput<'w> {
+    gamepads: Option<Res<'w, Gamepads>>,
+    buttons: Option<Res<'w, ButtonInput<GamepadButton>>>,
+    options: Res<'w, Options>,
+}
+impl GamepadInput<'_> {
+    /// Whether the button bound by `binding` was just pressed on any gamepad.
+    fn just_pressed(&self, binding: fn(&GamepadBindings) -> Option<GamepadButtonType>) -> bool {
+        let (Some(gamepads), Some(buttons)) = (&self.gamepads, &self.buttons) else {
+            return false;
+        };
+        let Some(button) = self.options.gamepad.as_ref().and_then(binding) else {
+            return false;
+        };
+        gamepads
+            .iter()
+            .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button)))
+    }
+}
+
+fn toggle_overlay(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    gamepad: GamepadInput,
+    options: Res<Options>,
+    mut visibility: ResMut<OverlayVisibility>,
+) {
+    let key_pressed = keys
+        .zip(options.toggle_key)
+        .is_some_and(|(keys, toggle_key)| keys.just_pressed(toggle_key));
+    if key_pressed || gamepad.just_pressed(|bindings| bindings.toggle) {
+        visibility.visible = !visibility.visible;
+    }
+}
+
+fn toggle_pause(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    options: Res<Options>,
+    mut paused: ResMut<DebugOverlayPaused>,
+) {
+    let (Some(keys), Some(pause_key)) = (keys, options.pause_key) else {
+        return;
+    };
+    if keys.just_pressed(pause_key) {
+        paused.paused = !paused.paused;
+    }
+}
+
+/// Time in seconds used for message expiration, it counts in
+/// [`OverlayPlugin::time_source`] and doesn't advance while the overlay is
+/// [paused](DebugOverlayPaused).
+#[derive(Resource, Default, Clone, Copy)]
+struct OverlayClock {
+    elapsed: f64,
+    /// Time counted in each [`TimeSource`], for the messages printed with the
+    /// `clock` option, it doesn't advance while paused either.
+    real: f64,
+    virtual_time: f64,
+}
+impl OverlayClock {
+    /// The current time counted in `source`, the overlay clock if `None`.
+    fn now(&self, source: Option<TimeSource>) -> f64 {
+        match source {
+            None => self.elapsed,
+            Some(TimeSource::Real) => self.real,
+            Some(TimeSource::Virtual) => self.virtual_time,
+        }
+    }
+}
+fn tick_overlay_clock(
+    mut clock: ResMut<OverlayClock>,
+    paused: Res<DebugOverlayPaused>,
+    stepping: Res<SteppingFrame>,
+    options: Res<Options>,
+    time: Res<Time>,
+    real_time: Option<Res<Time<Real>>>,
+) {
+    if paused.paused || stepping.waiting {
+        return;
+    }
+    let virtual_delta = time.delta_seconds_f64();
+    let delta = |source| match (source, &real_time) {
+        (TimeSource::Real, Some(real_time)) => real_time.delta_seconds_f64(),
+        _ => virtual_delta,
+    };
+    clock.elapsed += delta(options.time_source);
+    clock.real += delta(TimeSource::Real);
+    clock.virtual_time += delta(TimeSource::Virtual);
+}
+
+/// Whether the current frame is a frame stepped with bevy's [`Stepping`], see
+/// [`OverlayPlugin::follow_stepping`].
+#[derive(Resource, Default)]
+struct SteppingFrame {
+    /// Position of the stepping cursor at the start of the frame.
+    start: Option<(InternedScheduleLabel, NodeId)>,
+    /// Stepping is enabled, but no system was stepped this frame yet.
+    waiting: bool,
+    /// How many frames were stepped since stepping was enabled.
+    stepped: u64,
+}
+fn begin_stepping_frame(mut frame: ResMut<SteppingFrame>, stepping: Option<Res<Stepping>>) {
+    frame.start = stepping.and_then(|stepping| stepping.cursor());
+}
+fn detect_stepped_frame(mut frame: ResMut<SteppingFrame>, stepping: Option<Res<Stepping>>) {
+    let Some(stepping) = stepping.filter(|stepping| stepping.is_enabled()) else {
+        frame.waiting = false;
+        frame.stepped = 0;
+        return;
+    };
+    // The cursor only moves when systems are stepped. It is only set once
+    // the stepped schedules ran once after enabling stepping.
+    frame.waiting = frame.start.is_none() || stepping.cursor() == frame.start;
+    if !frame.waiting {
+        frame.stepped += 1;
+    }
+    let stepped = frame.stepped;
+    screen_print!(
+        sec: 0.5, col: Color::ORANGE, prio: i32::MAX, no_prefix, key: "stepping",
+        "STEPPING: frame {stepped}"
+    );
+}
+
+fn dump_history(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    options: Res<Options>,
+    history: Option<Res<OverlayHistory>>,
+) {
+    let (Some(keys), Some(dump_key), Some(history)) = (keys, options.history_dump_key, history)
+    else {
+        return;
+    };
+    if keys.just_pressed(dump_key) {
+        let path = history.path.display();
+        match history.write_to(&history.path) {
+            Ok(()) => info!("Wrote debug overlay history to {path}"),
+            Err(err) => error!("Failed to write debug overlay history to {path}: {err}"),
+        }
+    }
+}
+
+/// The messages displayed in an [`OverlaySnapshot`] taken at `current` time.
+fn snapshot<'a>(
+    messages: impl Iterator<Item = (&'a Message, Option<&'a Visibility>)>,
+    clock: &OverlayClock,
+    fade: f64,
+) -> OverlaySnapshot {
+    let displayed = |(message, vis): (&Message, Option<&Visibility>)| {
+        // Messages scrolled out of view or overflowing are hidden.
+        let hidden = vis == Some(&Visibility::Hidden);
+        let line = || SnapshotLine {
+            time: message.shown,
+            site: message.site.clone(),
+            text: message.plain_text(),
+            color: message.color,
+        };
+        (!hidden && message.opacity(clock, fade).is_some()).then(line)
+    };
+    let mut lines: Vec<_> = messages.filter_map(displayed).collect();
+    lines.sort_by(|a, b| a.time.total_cmp(&b.time).then_with(|| a.site.cmp(&b.site)));
+    OverlaySnapshot { time: clock.elapsed, lines }
+}
+impl OverlaySnapshot {
+    /// The messages currently displayed by the overlay of `world`, there are
+    /// none in [`OverlayPlugin::headless`] mode.
+    pub fn capture(world: &mut World) -> Self {
+        let clock = world
+            .get_resource::<OverlayClock>()
+            .copied()
+            .unwrap_or_default();
+        let fade = world
+            .get_resource::<Options>()
+            .map_or(0.0, |options| options.fade);
+        let mut messages = world.query::<(&Message, Option<&Visibility>)>();
+        snapshot(messages.iter(world), &clock, fade)
+    }
+}
+
+fn write_snapshot(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    options: Res<Options>,
+    messages: Query<(&Message, Option<&Visibility>)>,
+    clock: Res<OverlayClock>,
+) {
+    let (Some(keys), Some(snapshot_key)) = (keys, options.snapshot_key) else {
+        return;
+    };
+    if keys.just_pressed(snapshot_key) {
+        let path = options.snapshot_path;
+        let snapshot = snapshot(messages.iter(), &clock, options.fade);
+        match snapshot.write_to(path) {
+            Ok(()) => info!("Wrote debug overlay snapshot to {path}"),
+            Err(err) => error!("Failed to write debug overlay snapshot to {path}: {err}"),
+        }
+    }
+}
+
+fn toggle_categories(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    gamepad: GamepadInput,
+    mut categories: ResMut<DebugOverlayCategories>,
+) {
+    if gamepad.just_pressed(|bindings| bindings.cycle_category) {
+        categories.cycle();
+    }
+    use KeyCode::*;
+    const DIGITS: [KeyCode; 9] = [
+        Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    ];
+    let Some(keys) = keys else {
+        return;
+    };
+    let hotkeys = categories.hotkeys.iter().zip(DIGITS);
+    let pressed = hotkeys.filter(|(_, key)| keys.just_pressed(*key));
+    let toggled: Vec<_> = pressed.map(|(category, _)| category.clone()).collect();
+    for category in toggled {
+        categories.toggle(category);
+    }
+}
+
+fn show_overlay(
+    visibility: Res<OverlayVisibility>,
+    root: Res<OverlayRoot>,
+    mut nodes: Query<&mut Visibility>,
+) {
+    if visibility.is_changed() {
+        let mut roots = nodes.iter_many_mut(root.all());
+        while let Some(mut root_vis) = roots.fetch_next() {
+            *root_vis = root_visibility(&visibility);
+        }
+    }
+}
+
+/// Visibility of the overlay root nodes, see [`OverlayVisibility`].
+fn root_visibility(visibility: &OverlayVisibility) -> Visibility {
+    if visibility.visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+fn place_world_labels(
+    mut labels: Query<(Entity, &mut Style, &mut Visibility, &Message, &WorldLabel)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    targets: Query<&GlobalTransform>,
+    mut cmds: Commands,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+    ui_scale: Option<Res<UiScale>>,
+) {
+    let scale = ui_scale.map_or(1.0, |scale| scale.0);
+    let active_cameras = cameras.iter().filter(|(camera, _)| camera.is_active);
+    let camera = active_cameras.min_by_key(|(camera, _)| camera.order);
+    for (entity, mut style, mut vis, message, label) in labels.iter_mut() {
+        let position = match *label {
+            WorldLabel::Position(position) => position,
+            WorldLabel::Entity(target) => match targets.get(target) {
+                Ok(transform) => transform.translation(),
+                Err(_) => {
+                    cmds.entity(entity).despawn_recursive();
+                    continue;
+                }
+            },
+        };
+        let is_expired = message.opacity(&clock, options.fade).is_none();
+        let to_screen =
+            |(camera, transform): (&Camera, _)| camera.world_to_viewport(transform, position);
+        match camera.and_then(to_screen) {
+            Some(screen_pos) if !is_expired => {
+                vis.set_if_neq(Visibility::Inherited);
+                style.left = Val::Px(screen_pos.x / scale);
+                style.top = Val::Px(screen_pos.y / scale);
+            }
+            _ => {
+                vis.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn render_single_text(
+    messages: Query<(Entity, &Text, &Message), (Without<Node>, Without<SingleText>)>,
+    mut single_text: Query<(&mut Text, &mut Style, &mut BackgroundColor), With<SingleText>>,
+    mut scroll: ResMut<OverlayScroll>,
+    location: Res<DebugOverlayLocation>,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+    overlay_opacity: Res<DebugOverlayOpacity>,
+) {
+    let Ok((mut text, mut style, mut background)) = single_text.get_single_mut() else {
+        return;
+    };
+    if location.is_changed() {
+        set_position(&mut style, location.anchor, 0.0, 0.0);
+    }
+    let mut active: Vec<_> = messages
+        .iter()
+        .filter(|(_, _, message)| message.opacity(&clock, options.fade).is_some())
+        .collect();
+    if let Some(limit) = options.max_visible_lines {
+        let updated = active
+            .iter()
+            .map(|(entity, _, message)| (*entity, (message.rank(), message.updated)));
+        let window = scroll_window(updated.collect(), limit, &mut scroll);
+        active.retain(|(entity, ..)| window.contains(entity));
+    }
+    if let Some(color) = options.background {
+        let opacities = active
+            .iter()
+            .filter_map(|(_, _, message)| message.opacity(&clock, options.fade));
+        let opacity = match options.fade_background {
+            true => opacities.fold(0.0, f32::max),
+            false => 1.0,
+        };
+        let faded = color.with_a(color.a() * opacity * overlay_opacity.opacity);
+        if background.0 != faded {
+            background.0 = faded;
+        }
+    }
+    // Messages of higher priority come first.
+    active.sort_unstable_by_key(|(entity, _, message)| (-message.rank(), *entity));
+    let sections = active.iter().flat_map(|(_, text, _)| &text.sections);
+    let same_section = |(old, new): (&TextSection, &TextSection)| {
+        old.value == new.value && old.style.color == new.style.color
+    };
+    let section_count = active
+        .iter()
+        .map(|(_, text, _)| text.sections.len())
+        .sum::<usize>();
+    let unchanged = text.sections.len() == section_count
+        && text.sections.iter().zip(sections.clone()).all(same_section);
+    if !unchanged {
+        text.sections = sections.cloned().collect();
+    }
+}
+
+fn scroll_overlay(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    gamepad: GamepadInput,
+    wheel: Option<Res<Events<MouseWheel>>>,
+    mut wheel_reader: Local<ManualEventReader<MouseWheel>>,
+    options: Res<Options>,
+    mut scroll: ResMut<OverlayScroll>,
+) {
+    let Some(page) = options.max_visible_lines else {
+        return;
+    };
+    let mut lines: f32 = 0.0;
+    if let Some(keys) = keys {
+        if keys.just_pressed(KeyCode::PageUp) {
+            lines -= page as f32;
+        }
+        if keys.just_pressed(KeyCode::PageDown) {
+            lines += page as f32;
+        }
+    }
+    if gamepad.just_pressed(|bindings| bindings.page_up) {
+        lines -= page as f32;
+    }
+    if gamepad.just_pressed(|bindings| bindings.page_down) {
+        lines += page as f32;
+    }
+    if let Some(wheel) = wheel {
+        // Scrolling up moves toward newer messages.
+        lines -= wheel_reader
+            .read(&wheel)
+            .map(|event| event.y.signum())
+            .sum::<f32>();
+    }
+    if lines != 0.0 {
+        scroll.offset = (scroll.offset as f32 + lines).max(0.0) as usize;
+    }
+}
+
+/// The priority and last update time of a message, see [`keep_first`].
+type Retention = (i32, f64);
+
+/// Order in which to keep messages when they don't all fit: the highest
+/// priority first, then the most recently updated.
+fn keep_first((lhs_prio, lhs): &Retention, (rhs_prio, rhs): &Retention) -> std::cmp::Ordering {
+    rhs_prio.cmp(lhs_prio).then(rhs.total_cmp(lhs))
+}
+
+/// The messages to show when there is a line limit.
+///
+/// Keeps the first `limit` messages of `active` according to [`keep_first`],
+/// after skipping the `scroll.offset` first ones. `scroll` is clamped so that
+/// there are always `limit` messages shown.
+fn scroll_window(
+    mut active: Vec<(Entity, Retention)>,
+    limit: usize,
+    scroll: &mut OverlayScroll,
+) -> HashSet<Entity> {
+    let max_scroll = active.len().saturating_sub(limit);
+    if scroll.offset > max_scroll {
+        scroll.offset = max_scroll;
+    }
+    active.sort_unstable_by(|(_, lhs), (_, rhs)| keep_first(lhs, rhs));
+    let window = active.into_iter().skip(scroll.offset).take(limit);
+    window.map(|(entity, _)| entity).collect()
+}
+
+/// The messages of `active` that don't fit in `max_height`.
+///
+/// Keeps the first messages according to [`keep_first`], given as
+/// `(entity, retention, height)`, leaving room for a `summary` line when some
+/// don't fit.
+fn height_overflow(
+    mut active: Vec<(Entity, Retention, f32)>,
+    max_height: f32,
+    summary: f32,
+) -> Vec<Entity> {
+    active.sort_unstable_by(|(_, lhs, _), (_, rhs, _)| keep_first(lhs, rhs));
+    let mut total = 0.0;
+    let mut fitting = active
+        .iter()
+        .take_while(|(.., height)| {
+            total += height;
+            total <= max_height
+        })
+        .count();
+    if fitting == active.len() {
+        return Vec::new();
+    }
+    let mut total: f32 = active[..fitting].iter().map(|(.., height)| height).sum();
+    while fitting > 0 && total + summary > max_height {
+        fitting -= 1;
+        total -= active[fitting].2;
+    }
+    active[fitting..]
+        .iter()
+        .map(|(entity, ..)| *entity)
+        .collect()
+}
+
+/// The "+ N more" line after the messages exceeding
+/// [`OverlayPlugin::max_height`].
+#[derive(Component)]
+struct OverflowSummary;
+
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+struct OverflowSummaries<'w, 's> {
+    lines: Query<
+        'w,
+        's,
+        (
+            &'static mut Style,
+            &'static mut Visibility,
+            &'static Node,
+            &'static mut Text,
+        ),
+        (
+            With<OverflowSummary>,
+            Without<Message>,
+            Without<FileHeader>,
+            Without<Background>,
+        ),
+    >,
+    /// Summary line of each overlay root.
+    spawned: Local<'s, HashMap<Entity, Entity>>,
+    scale: Res<'w, DebugOverlayScale>,
+    cmds: Commands<'w, 's>,
+}
+impl OverflowSummaries<'_, '_> {
+    /// Height of the summary line of `parent`, 0 until it is laid out.
+    fn height(&self, parent: Entity) -> f32 {
+        let line = self.spawned.get(&parent);
+        let node = line.and_then(|line| self.lines.get(*line).ok());
+        node.map_or(0.0, |(_, _, node, _)| node.size().y)
+    }
+    /// Show the count of `hidden` messages of `parent` at `offset`, returns
+    /// the height of the displayed line.
+    fn update(
+        &mut self,
+        parent: Entity,
+        hidden: usize,
+        offset: f32,
+        anchor: Anchor,
+        options: &Options,
+    ) -> f32 {
+        let font_size = options.font_size * self.scale.scale;
+        let Some(&line) = self.spawned.get(&parent) else {
+            if hidden == 0 {
+                return 0.0;
+            }
+            let style = TextStyle {
+                color: options.color,
+                font_size,
+                font: options.font.clone(),
+            };
+            let line = TextBundle {
+                text: Text::from_section(format!("+ {hidden} more"), style),
+                style: Style { position_type: PositionType::Absolute, ..default() },
+                ..default()
+            };
+            let mut line = self.cmds.spawn((line, OverflowSummary));
+            line.insert(Visibility::Hidden).set_parent(parent);
+            self.spawned.insert(parent, line.id());
+            return 0.0;
+        };
+        let Ok((mut style, mut vis, node, mut text)) = self.lines.get_mut(line) else {
+            return 0.0;
+        };
+        if hidden == 0 {
+            vis.set_if_neq(Visibility::Hidden);
+            return 0.0;
+        }
+        let value = format!("+ {hidden} more");
+        let section = &mut text.sections[0];
+        if section.value != value {
+            section.value = value;
+        }
+        if section.style.font_size != font_size {
+            section.style.font_size = font_size;
+        }
+        set_position(
+            &mut style,
+            anchor,
+            offset + options.padding(),
+            options.padding(),
+        );
+        vis.set_if_neq(Visibility::Inherited);
+        node.size().y
+    }
+}
+
+/// Header line of the messages printed from `file`, see
+/// [`OverlayPlugin::group_by_file`].
+#[derive(Component)]
+struct FileHeader {
+    file: &'static str,
+}
+
+/// Files whose messages are hidden under their [`FileHeader`].
+#[derive(Resource, Default)]
+struct CollapsedFiles(HashSet<&'static str>);
+
+fn header_text(file: &str, count: usize, collapsed: bool) -> String {
+    let sign = if collapsed { '+' } else { '-' };
+    format!("{sign} {file} ({count})")
+}
+
+/// Cameras and primary window, to find on which camera to display messages
+/// sent to other windows.
+#[derive(SystemParam)]
+struct WindowCameras<'w, 's> {
+    cameras: Query<'w, 's, (Entity, &'static Camera)>,
+    primary: Query<'w, 's, Entity, With<PrimaryWindow>>,
+    viewports: Query<'w, 's, (Entity, &'static OverlayViewport)>,
+}
+impl WindowCameras<'_, '_> {
+    /// The camera of the [`OverlayViewport`] named `target`.
+    fn viewport(&self, target: Option<&str>) -> Option<Entity> {
+        let target = target?;
+        let mut viewports = self.viewports.iter();
+        viewports
+            .find(|(_, viewport)| viewport.0 == target)
+            .map(|(camera, _)| camera)
+    }
+}
+
+/// Size of the windows and viewports of the [`OverlayRoot`] nodes.
+#[derive(SystemParam)]
+struct RootSizes<'w, 's> {
+    root: Res<'w, OverlayRoot>,
+    windows: Query<'w, 's, &'static Window>,
+    primary_window: Query<'w, 's, Entity, With<PrimaryWindow>>,
+    cameras: Query<'w, 's, &'static Camera>,
+    ui_scale: Option<Res<'w, UiScale>>,
+}
+impl RootSizes<'_, '_> {
+    /// Size in UI pixels of the window or viewport of the root node `parent`,
+    /// `None` if unknown.
+    fn size(&self, parent: Entity) -> Option<Vec2> {
+        let root = &self.root;
+        let mut viewports = root.viewports.iter();
+        let size = if let Some((camera, _)) = viewports.find(|(_, root)| **root == parent) {
+            self.cameras.get(*camera).ok()?.logical_viewport_size()?
+        } else {
+            let window = if parent == root.main {
+                self.primary_window.get_single().ok()?
+            } else {
+                let mut windows = root.windows.iter();
+                *windows.find(|(_, window_root)| **window_root == parent)?.0
+            };
+            let window = self.windows.get(window).ok()?;
+            Vec2::new(window.width(), window.height())
+        };
+        let scale = self.ui_scale.as_ref().map_or(1.0, |scale| scale.0);
+        Some(size / scale)
+    }
+}
+
+/// Whether all messages must be placed again, see [`invalidate_layout`].
+#[derive(Resource, Default)]
+struct LayoutInvalidated(bool);
+
+/// Place all messages again when a window is resized or the [`UiScale`]
+/// changes, as the space available to them and their wrap width change.
+fn invalidate_layout(
+    resized: Option<Res<Events<WindowResized>>>,
+    mut resized_reader: Local<ManualEventReader<WindowResized>>,
+    ui_scale: Option<Res<UiScale>>,
+    mut invalidated: ResMut<LayoutInvalidated>,
+) {
+    let is_resized = resized.is_some_and(|resized| resized_reader.read(&resized).count() > 0);
+    if is_resized || ui_scale.is_some_and(|scale| scale.is_changed()) {
+        invalidated.0 = true;
+    }
+}
+
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+struct FileGroups<'w, 's> {
+    headers: Query<
+        'w,
+        's,
+        (
+            &'static mut Style,
+            &'static mut Visibility,
+            Ref<'static, Node>,
+            &'static mut Text,
+            &'static Parent,
+        ),
+        (With<FileHeader>, Without<Message>),
+    >,
+    /// Header of each file in each overlay root.
+    spawned: Local<'s, HashMap<(Entity, &'static str), Entity>>,
+    collapsed: Res<'w, CollapsedFiles>,
+    scale: Res<'w, DebugOverlayScale>,
+    cmds: Commands<'w, 's>,
+}
+impl FileGroups<'_, '_> {
+    fn is_collapsed(&self, file: &str) -> bool {
+        self.collapsed.0.contains(file)
+    }
+    /// Spawn, update and despawn headers to match the `counts` of messages
+    /// per file, returns `true` if their size may have changed.
+    fn update(
+        &mut self,
+        counts: &HashMap<(Entity, &'static str), usize>,
+        options: &Options,
+    ) -> bool {
+        let mut changed = false;
+        let cmds = &mut self.cmds;
+        self.spawned.retain(|group, header| {
+            let keep = counts.contains_key(group);
+            if !keep {
+                cmds.entity(*header).despawn_recursive();
+                changed = true;
+            }
+            keep
+        });
+        for (&(parent, file), &count) in counts {
+            let text = header_text(file, count, self.collapsed.0.contains(file));
+            if let Some(&header) = self.spawned.get(&(parent, file)) {
+                let Ok((.., node, mut ui_text, _)) = self.headers.get_mut(header) else {
+                    continue;
+                };
+                changed |= node.is_changed();
+                if ui_text.sections[0].value != text {
+                    ui_text.sections[0].value = text;
+                    changed = true;
+                }
+                continue;
+            }
+            let style = TextStyle {
+                color: options.color,
+                font_size: options.font_size * self.scale.scale,
+                font: options.font.clone(),
+            };
+            let header = TextBundle {
+                text: Text::from_section(text, style),
+                style: Style { position_type: PositionType::Absolute, ..default() },
+                ..default()
+            };
+            let mut header = cmds.spawn((header, FileHeader { file }, Interaction::default()));
+            header.insert(Visibility::Hidden).set_parent(parent);
+            self.spawned.insert((parent, file), header.id());
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// Collapse or expand file groups when their header is clicked, or all of
+/// them when [`OverlayPlugin::collapse_key`] is pressed.
+fn toggle_file_groups(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    clicked: Query<(&Interaction, &FileHeader), Changed<Interaction>>,
+    headers: Query<&FileHeader>,
+    mut collapsed: ResMut<CollapsedFiles>,
+    options: Res<Options>,
+) {
+    for (interaction, header) in &clicked {
+        if *interaction == Interaction::Pressed && !collapsed.0.remove(header.file) {
+            collapsed.0.insert(header.file);
+        }
+    }
+    let (Some(keys), Some(collapse_key)) = (keys, options.collapse_key) else {
+        return;
+    };
+    if !keys.just_pressed(collapse_key) {
+        return;
+    }
+    if collapsed.0.is_empty() {
+        collapsed.0.extend(headers.iter().map(|header| header.file));
+    } else {
+        collapsed.0.clear();
+    }
+}
+
+/// Messages laid out in their overlay root, world labels are placed next to
+/// their entity instead.
+type LaidOutMessages<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Style,
+        &'static mut Visibility,
+        &'static Node,
+        &'static Message,
+        &'static Parent,
+        Option<&'static mut Text>,
+    ),
+    Without<WorldLabel>,
+>;
+
+/// Where messages are placed in their window or viewport, and which ones are
+/// in view.
+#[derive(SystemParam)]
+struct LayoutSpace<'w, 's> {
+    sizes: RootSizes<'w, 's>,
+    location: Res<'w, DebugOverlayLocation>,
+    options: Res<'w, Options>,
+    clock: Res<'w, OverlayClock>,
+    search: Res<'w, OverlaySearch>,
+    scroll: ResMut<'w, OverlayScroll>,
+}
+impl LayoutSpace<'_, '_> {
+    /// Area available to the messages of the root node `parent`.
+    fn layout_area(&self, parent: Entity) -> LayoutArea {
+        let (location, padding) = (&self.location, self.options.padding());
+        let size = self.sizes.size(parent).map(|size| {
+            let width = size.x - 2.0 * location.margin_horizontal.resolve(size.x);
+            let height = size.y - 2.0 * location.margin_vertical.resolve(size.y);
+            let height = match location.anchor {
+                Anchor::CenterLeft | Anchor::CenterRight => height / 2.0,
+                _ => height,
+            };
+            Vec2::new(width, height) - 2.0 * padding
+        });
+        LayoutArea {
+            size,
+            column_width: self.options.column_width,
+            line_spacing: self.options.line_spacing,
+        }
+    }
+    fn new_layout(&self, parent: Entity) -> (LayoutArea, Box<dyn OverlayLayout>) {
+        let area = self.layout_area(parent);
+        (area, self.options.layout.new_layout(area))
+    }
+    /// Messages are wrapped at a width resolved against their window, as
+    /// their root node is sized by its content.
+    fn wrap_width(&self, parent: Entity) -> Val {
+        let wrap_width = || {
+            let size = self.sizes.size(parent)?;
+            let width = self.options.max_width.resolve(size.x, size).ok()?;
+            Some(Val::Px((width - 2.0 * self.options.padding()).max(0.0)))
+        };
+        match self.options.max_width {
+            Val::Auto => Val::Auto,
+            _ => wrap_width().unwrap_or(Val::Auto),
+        }
+    }
+    fn move_to(&self, style: &mut Style, position: Vec2) {
+        let padding = self.options.padding();
+        let (offset, indent) = (position.y + padding, position.x + padding);
+        set_position(style, self.location.anchor, offset, indent);
+    }
+    fn place(
+        &self,
+        layout: &mut dyn OverlayLayout,
+        entity: Entity,
+        size: Vec2,
+        indent: Vec2,
+        style: &mut Style,
+    ) {
+        self.move_to(style, layout.insert(entity, size) + indent);
+    }
+    /// Indented messages are placed with their indent, and shifted by it.
+    fn indent_of(&self, message: &Message) -> Vec2 {
+        Vec2::X * message.indent as f32 * self.options.indent_width
+    }
+    /// The file `message` is grouped in, see [`OverlayPlugin::group_by_file`].
+    fn file_of(&self, message: &Message) -> Option<&'static str> {
+        match message.site {
+            InvocationSiteKey::Site { file, .. } if self.options.group_by_file => Some(file),
+            _ => None,
+        }
+    }
+    fn opacity(&self, message: &Message) -> Option<f32> {
+        message.opacity(&self.clock, self.options.fade)
+    }
+    /// Whether messages are placed in order, rather than in the first free
+    /// spot. When sorted, all messages are placed again when any of them
+    /// moves.
+    fn is_sorted(&self, messages: &LaidOutMessages) -> bool {
+        let prioritized = messages
+            .iter()
+            .any(|(.., message, _, _)| message.rank() != 0);
+        let options = &self.options;
+        options.ordering != MessageOrdering::Stable || options.group_by_file || prioritized
+    }
+    /// Messages not matching the [`OverlaySearch`] or scrolled out of view,
+    /// they are handled like expired ones.
+    fn out_of_view(&mut self, messages: &LaidOutMessages) -> HashSet<Entity> {
+        let unmatched: HashSet<Entity> = match self.search.text.as_str() {
+            "" => HashSet::new(),
+            _ => messages
+                .iter()
+                .filter(|(.., message, _, _)| !self.search.matches(&message.plain_text()))
+                .map(|(entity, ..)| entity)
+                .collect(),
+        };
+        let Some(limit) = self.options.max_visible_lines else {
+            return unmatched;
+        };
+        let active = messages
+            .iter()
+            .filter(|(entity, ..)| !unmatched.contains(entity))
+            .filter(|(.., message, _, _)| self.opacity(message).is_some())
+            .map(|(entity, .., message, _, _)| (entity, (message.rank(), message.updated)));
+        let window = scroll_window(active.collect(), limit, &mut self.scroll);
+        let entities = messages.iter().map(|(entity, ..)| entity);
+        let scrolled_out = entities.filter(|entity| !window.contains(entity));
+        unmatched.into_iter().chain(scrolled_out).collect()
+    }
+    /// Add the messages that don't fit in [`OverlayPlugin::max_height`] to
+    /// `out_of_view`, returns how many are hidden in each root.
+    fn overflow(
+        &self,
+        messages: &LaidOutMessages,
+        groups: &FileGroups,
+        summaries: &OverflowSummaries,
+        out_of_view: &mut HashSet<Entity>,
+    ) -> HashMap<Entity, usize> {
+        let mut hidden_counts = HashMap::new();
+        if self.options.max_height == Val::Auto {
+            return hidden_counts;
+        }
+        let mut stacks: HashMap<Entity, Vec<_>> = HashMap::new();
+        for (entity, _, _, node, message, parent, _) in messages {
+            let collapsed = self
+                .file_of(message)
+                .is_some_and(|file| groups.is_collapsed(file));
+            if !out_of_view.contains(&entity) && !collapsed && self.opacity(message).is_some() {
+                let stack = stacks.entry(parent.get()).or_default();
+                stack.push((entity, (message.rank(), message.updated), node.size().y));
+            }
+        }
+        let padding = self.options.padding();
+        for (parent, stack) in stacks {
+            let Some(size) = self.sizes.size(parent) else {
+                continue;
+            };
+            let Ok(max_height) = self.options.max_height.resolve(size.y, size) else {
+                continue;
+            };
+            let summary = summaries.height(parent);
+            let hidden = height_overflow(stack, max_height - 2.0 * padding, summary);
+            hidden_counts.insert(parent, hidden.len());
+            out_of_view.extend(hidden);
+        }
+        hidden_counts
+    }
+    /// Move the visible messages narrower than their stack to the side they
+    /// are aligned to.
+    fn align(&self, messages: &mut LaidOutMessages, stack_widths: &HashMap<Entity, f32>) {
+        let location = &self.location;
+        for (_, mut style, vis, node, message, parent, text) in messages.iter_mut() {
+            if *vis != Visibility::Inherited {
+                continue;
+            }
+            let alignment = message.align.unwrap_or(location.alignment);
+            let width = match self.options.column_width {
+                Some(column_width) => column_width,
+                None => stack_widths.get(&parent.get()).copied().unwrap_or(0.0),
+            };
+            let slack = (width - node.size().x - self.indent_of(message).x).max(0.0);
+            let (left, right) = alignment_margins(alignment, location.anchor, slack);
+            if style.margin.left != left || style.margin.right != right {
+                (style.margin.left, style.margin.right) = (left, right);
+            }
+            let justify = match alignment {
+                Alignment::Anchor | Alignment::Left => JustifyText::Left,
+                Alignment::Center => JustifyText::Center,
+                Alignment::Right => JustifyText::Right,
+            };
+            if let Some(mut text) = text.filter(|text| text.justify != justify) {
+                text.justify = justify;
+            }
+        }
+    }
+}
+
+/// The messages to place again, see [`RootLayouts::forget_outdated`].
+struct Outdated {
+    /// Whether all messages must be placed again.
+    all: bool,
+    /// Roots whose window or viewport was resized.
+    resized: Vec<Entity>,
+}
+
+/// What placing the messages found, see [`RootLayouts::place_messages`].
+#[derive(Default)]
+struct Placed {
+    sorted: bool,
+    /// Whether the sorted messages must be placed again.
+    reorder: bool,
+    /// Messages in view, when sorted.
+    active: Vec<Entity>,
+    /// Messages in view per root and file, see
+    /// [`OverlayPlugin::group_by_file`].
+    file_counts: HashMap<(Entity, &'static str), usize>,
+    /// Width of the widest message in view of each root.
+    stack_widths: HashMap<Entity, f32>,
+    /// Opacity of the most opaque message in view of the main root.
+    max_opacity: f32,
+}
+
+/// The layout of the messages of each overlay root.
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+struct RootLayouts<'w, 's> {
+    layouts: Local<'s, HashMap<Entity, (LayoutArea, Box<dyn OverlayLayout>)>>,
+    /// Messages and file headers in the order they were last placed in, when
+    /// sorted.
+    order: Local<'s, Vec<Entity>>,
+    invalidated: ResMut<'w, LayoutInvalidated>,
+    despawned: RemovedComponents<'w, 's, Message>,
+}
+impl RootLayouts<'_, '_> {
+    /// Forget the layouts of resized roots, and all of them when the
+    /// location or options change.
+    fn forget_outdated(&mut self, space: &LayoutSpace) -> Outdated {
+        // When the location changes, all visible messages need to be moved.
+        // Padding changes with the `OverlayConfig`.
+        let all = space.location.is_changed()
+            || space.options.is_changed()
+            || mem::take(&mut self.invalidated.0);
+        if all {
+            self.layouts.clear();
+        }
+        for entity in self.despawned.read() {
+            for (_, layout) in self.layouts.values_mut() {
+                layout.remove(entity);
+            }
+        }
+        // When the window is resized, its messages need to be moved.
+        let mut resized = Vec::new();
+        self.layouts.retain(|parent, (area, _)| {
+            let is_resized = *area != space.layout_area(*parent);
+            if is_resized {
+                resized.push(*parent);
+            }
+            !is_resized
+        });
+        Outdated { all, resized }
+    }
+    fn get(&mut self, space: &LayoutSpace, parent: Entity) -> &mut dyn OverlayLayout {
+        let new_layout = || space.new_layout(parent);
+        let (_, layout) = self.layouts.entry(parent).or_insert_with(new_layout);
+        layout.as_mut()
+    }
+    /// Size of the messages of `parent`, zero before they are placed.
+    fn size(&self, parent: Entity) -> Vec2 {
+        let layout = self.layouts.get(&parent);
+        layout.map_or(Vec2::ZERO, |(_, layout)| layout.size())
+    }
+    /// Show the messages in view and hide the others, placing them unless
+    /// they are sorted.
+    fn place_messages(
+        &mut self,
+        space: &LayoutSpace,
+        messages: &mut LaidOutMessages,
+        groups: &FileGroups,
+        out_of_view: &HashSet<Entity>,
+        outdated: &Outdated,
+    ) -> Placed {
+        // `Inherited` rather than `Visible`, so that the root node can hide
+        // all messages at once.
+        use Visibility::{Hidden, Inherited as Visible};
+        let sorted = space.is_sorted(messages);
+        let reorder = outdated.all || !outdated.resized.is_empty();
+        let mut placed = Placed { sorted, reorder, ..default() };
+        for (entity, mut style, mut vis, node, message, parent, _) in messages.iter_mut() {
+            let parent = parent.get();
+            let layout = self.get(space, parent);
+            let relayout = outdated.all || outdated.resized.contains(&parent);
+            let wrap = space.wrap_width(parent);
+            if style.max_width != wrap {
+                style.max_width = wrap;
+            }
+            let indent = space.indent_of(message);
+            let size = node.size() + indent;
+            // Messages scrolled out of view are handled like expired ones.
+            let opacity = space.opacity(message);
+            let is_expired = opacity.is_none() || out_of_view.contains(&entity);
+            let file = space.file_of(message);
+            if let (false, Some(file)) = (is_expired, file) {
+                *placed.file_counts.entry((parent, file)).or_insert(0) += 1;
+            }
+            // Collapsed messages are only counted in their file header.
+            let is_expired = is_expired || file.is_some_and(|file| groups.is_collapsed(file));
+            let is_visible = *vis == Visible;
+            if is_visible == is_expired {
+                *vis = if is_visible { Hidden } else { Visible };
+                if sorted {
+                    placed.reorder = true;
+                } else if !is_expired {
+                    space.place(layout, entity, size, indent, &mut style);
+                } else {
+                    layout.remove(entity);
+                }
+            } else if is_visible {
+                // Text changes and wrapping change the size of messages.
+                let placed_size = layout.size_of(entity);
+                let is_resized = placed_size.is_some_and(|placed| placed != size);
+                if sorted {
+                    placed.reorder |= is_resized && layout.resize(entity, size).is_some();
+                } else if relayout {
+                    layout.remove(entity);
+                    space.place(layout, entity, size, indent, &mut style);
+                } else if let Some(position) =
+                    is_resized.then(|| layout.resize(entity, size)).flatten()
+                {
+                    space.move_to(&mut style, position + indent);
+                }
+            }
+            if is_expired {
+                continue;
+            }
+            if sorted {
+                placed.active.push(entity);
+            }
+            let stack_width = placed.stack_widths.entry(parent).or_insert(0.0_f32);
+            *stack_width = stack_width.max(size.x);
+            if parent == space.sizes.root.main {
+                placed.max_opacity = placed.max_opacity.max(opacity.unwrap_or(0.0));
+            }
+        }
+        placed
+    }
+    /// Place the `active` messages and the file headers in order, if it
+    /// changed or they must `reorder`, see [`OverlayPlugin::ordering`].
+    fn sort(
+        &mut self,
+        space: &LayoutSpace,
+        messages: &mut LaidOutMessages,
+        groups: &mut FileGroups,
+        active: &[Entity],
+        reorder: bool,
+    ) {
+        let mut items: Vec<_> = active
+            .iter()
+            .filter_map(|&entity| messages.get(entity).ok())
+            .map(|(entity, .., message, _, text)| {
+                (space.file_of(message), Some((message, text)), entity)
+            })
+            .collect();
+        let headers = groups.spawned.iter();
+        items.extend(headers.map(|(&(_, file), &header)| (Some(file), None, header)));
+        items.sort_by(|(a_file, a, a_entity), (b_file, b, b_entity)| {
+            let by_message = || match (a, b) {
+                (Some(a), Some(b)) => compare_messages(space.options.ordering, *a, *b),
+                // Headers come before the messages of their file.
+                _ => a.is_some().cmp(&b.is_some()),
+            };
+            a_file
+                .cmp(b_file)
+                .then_with(by_message)
+                .then(a_entity.cmp(b_entity))
+        });
+        let sorted: Vec<_> = items.into_iter().map(|(.., entity)| entity).collect();
+        if !reorder && sorted == *self.order {
+            return;
+        }
+        self.layouts.clear();
+        for &entity in &sorted {
+            let (size, indent, parent) = match (messages.get(entity), groups.headers.get(entity)) {
+                (Ok((_, _, _, node, message, parent, _)), _) => {
+                    let indent = space.indent_of(message);
+                    (node.size() + indent, indent, parent.get())
+                }
+                (_, Ok((_, _, node, _, parent))) => (node.size(), Vec2::ZERO, parent.get()),
+                _ => continue,
+            };
+            let layout = self.get(space, parent);
+            if let Ok((_, mut style, ..)) = messages.get_mut(entity) {
+                space.place(layout, entity, size, indent, &mut style);
+            } else if let Ok((mut style, mut vis, ..)) = groups.headers.get_mut(entity) {
+                space.place(layout, entity, size, indent, &mut style);
+                vis.set_if_neq(Visibility::Inherited);
+            }
+        }
+        *self.order = sorted;
+    }
+    /// Show how many messages are hidden in each root after its messages,
+    /// returns the height of the line of the main root.
+    fn summarize(
+        &self,
+        space: &LayoutSpace,
+        summaries: &mut OverflowSummaries,
+        hidden_counts: &HashMap<Entity, usize>,
+    ) -> f32 {
+        let (root, anchor) = (&space.sizes.root, space.location.anchor);
+        let mut summary_height = 0.0;
+        for parent in root.all() {
+            let hidden = hidden_counts.get(&parent).copied().unwrap_or(0);
+            let offset = self.size(parent).y;
+            let height = summaries.update(parent, hidden, offset, anchor, &space.options);
+            if parent == root.main {
+                summary_height = height;
+            }
+        }
+        summary_height
+    }
+}
+
+/// The panel drawn behind the messages of the main root, see
+/// [`OverlayPlugin::background_color`].
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+struct BackgroundPanel<'w, 's> {
+    panel: Query<
+        'w,
+        's,
+        (
+            &'static mut Style,
+            &'static mut Visibility,
+            &'static mut BackgroundColor,
+        ),
+        (With<Background>, Without<Message>, Without<FileHeader>),
+    >,
+    opacity: Res<'w, DebugOverlayOpacity>,
+}
+impl BackgroundPanel<'_, '_> {
+    /// Cover `size` with the panel, as opaque as the most opaque message when
+    /// [`OverlayPlugin::fade_background`] is set.
+    fn update(&mut self, size: Vec2, max_opacity: f32, space: &LayoutSpace) {
+        use Visibility::{Hidden, Inherited as Visible};
+        let Ok((mut style, mut vis, mut background)) = self.panel.get_single_mut() else {
+            return;
+        };
+        let (options, padding) = (&space.options, space.options.padding());
+        let new_vis = if size.y == 0.0 { Hidden } else { Visible };
+        vis.set_if_neq(new_vis);
+        set_position(&mut style, space.location.anchor, 0.0, 0.0);
+        style.width = Val::Px(size.x + 2.0 * padding);
+        style.height = Val::Px(size.y + 2.0 * padding);
+        if let Some(color) = options.background {
+            let opacity = if options.fade_background {
+                max_opacity
+            } else {
+                1.0
+            };
+            let faded = color.with_a(color.a() * opacity * self.opacity.opacity);
+            if background.0 != faded {
+                background.0 = faded;
+            }
+        }
+    }
+}
+
+fn layout_messages(
+    mut messages: LaidOutMessages,
+    mut groups: FileGroups,
+    mut summaries: OverflowSummaries,
+    mut background: BackgroundPanel,
+    mut layouts: RootLayouts,
+    mut space: LayoutSpace,
+) {
+    let outdated = layouts.forget_outdated(&space);
+    let mut out_of_view = space.out_of_view(&messages);
+    let hidden_counts = space.overflow(&messages, &groups, &summaries, &mut out_of_view);
+    let mut placed =
+        layouts.place_messages(&space, &mut messages, &groups, &out_of_view, &outdated);
+    if space.options.group_by_file {
+        placed.reorder |= groups.update(&placed.file_counts, &space.options);
+    }
+    if placed.sorted {
+        let (active, reorder) = (&placed.active, placed.reorder);
+        layouts.sort(&space, &mut messages, &mut groups, active, reorder);
+    }
+    space.align(&mut messages, &placed.stack_widths);
+    let summary_height = layouts.summarize(&space, &mut summaries, &hidden_counts);
+    let size = layouts.size(space.sizes.root.main) + Vec2::Y * summary_height;
+    background.update(size, placed.max_opacity, &space);
+}
+
+/// Left and right margins moving a message `slack` pixels narrower than its
+/// stack to the `alignment` side.
+fn alignment_margins(alignment: Alignment, anchor: Anchor, slack: f32) -> (Val, Val) {
+    use Anchor::*;
+    let toward_right = match alignment {
+        Alignment::Anchor => return (Val::ZERO, Val::ZERO),
+        Alignment::Left => 0.0,
+        Alignment::Center => 0.5,
+        Alignment::Right => 1.0,
+    };
+    match anchor {
+        TopLeft | BottomLeft | CenterLeft => (Val::Px(slack * toward_right), Val::ZERO),
+        TopRight | BottomRight | CenterRight => (Val::ZERO, Val::Px(slack * (1.0 - toward_right))),
+    }
+}
+
+/// Compare two messages and their text, see [`OverlayPlugin::ordering`].
+fn compare_messages(
+    ordering: MessageOrdering,
+    (a, a_text): (&Message, Option<&Text>),
+    (b, b_text): (&Message, Option<&Text>),
+) -> std::cmp::Ordering {
+    // Progress bars have no `Text`, their label is in a child entity.
+    fn chars<'a>(message: &Message, text: Option<&'a Text>) -> impl Iterator<Item = char> + 'a {
+        let sections = text.map_or(&[][..], |text| message.sections(text));
+        sections.iter().flat_map(|section| section.value.chars())
+    }
+    // Messages of higher priority come first.
+    let by_prio = b.rank().cmp(&a.rank());
+    by_prio.then_with(|| match ordering {
+        MessageOrdering::Stable => std::cmp::Ordering::Equal,
+        MessageOrdering::CallSite => a.site.cmp(&b.site).then(a.shown.total_cmp(&b.shown)),
+        MessageOrdering::Chronological => a.shown.total_cmp(&b.shown),
+        MessageOrdering::Alphabetical => chars(a, a_text).cmp(chars(b, b_text)),
+    })
+}
+
+/// Resize messages, file headers and progress bar labels when the
+/// [`DebugOverlayScale`] changes.
+fn scale_messages(
+    mut messages: Query<(&mut Text, &Message, Option<&mut Style>)>,
+    bars: Query<(&Message, &ProgressBar)>,
+    headers: Query<Entity, With<FileHeader>>,
+    mut texts: Query<&mut Text, Without<Message>>,
+    scale: Res<DebugOverlayScale>,
+    options: Res<Options>,
+) {
+    // The font size of the `Options` changes with the `OverlayConfig`.
+    if !scale.is_changed() && !options.is_changed() {
+        return;
+    }
+    let font_size = |size: Option<f32>| size.unwrap_or(options.font_size) * scale.scale;
+    let resize = |text: &mut Text, font_size| {
+        for section in &mut text.sections {
+            section.style.font_size = font_size;
+        }
+    };
+    for (mut text, message, style) in &mut messages {
+        resize(&mut text, font_size(message.size));
+        if let (Some(mut style), Some(_)) = (style, message.icon) {
+            let square = Icon::square_style(style.clone(), font_size(message.size));
+            style.border = square.border;
+            style.padding = square.padding;
+        }
+    }
+    for (message, bar) in &bars {
+        if let Ok(mut label) = texts.get_mut(bar.label) {
+            resize(&mut label, font_size(message.size));
+        }
+    }
+    let mut headers = texts.iter_many_mut(&headers);
+    while let Some(mut header) = headers.fetch_next() {
+        resize(&mut header, font_size(None));
+    }
+}
+
+/// Display the time remaining before messages expire, see
+/// [`OverlayPlugin::show_countdown`].
+fn update_countdowns(
+    mut messages: Query<(&mut Text, &Message)>,
+    clock: Res<OverlayClock>,
+    options: Res<Options>,
+) {
+    if !options.show_countdown {
+        return;
+    }
+    for (mut text, message) in &mut messages {
+        if text.sections.len() < 2 {
+            continue;
+        }
+        let remaining = message.remaining(&clock).max(0.0);
+        // Persistent messages do not expire.
+        let countdown = if remaining.is_finite() {
+            format!("{remaining:.1}s ")
+        } else {
+            String::new()
+        };
+        if text.sections[0].value != countdown {
+            text.sections[0].value = countdown;
+        }
+    }
+}
+
+/// Set the opacity of messages, lower while they fade out, see
+/// [`OverlayPlugin::fade_duration`] and [`DebugOverlayOpacity`].
+fn fade_messages(
+    mut messages: Query<(&mut Text, &Message, Option<Panel>)>,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+    overlay_opacity: Res<DebugOverlayOpacity>,
+) {
+    for (mut text, message, highlight) in messages.iter_mut() {
+        let Some(opacity) = message.opacity(&clock, options.fade) else {
+            continue;
+        };
+        set_opacity(
+            &mut text,
+            message,
+            highlight,
+            opacity * overlay_opacity.opacity,
+        );
+    }
+}
+
+/// Set the colors of `message` to their original value, with their alpha
+/// multiplied by `opacity` and the `alpha` of the message.
+fn set_opacity(
+    text: &mut Text,
+    message: &Message,
+    highlight: Option<(Mut<BackgroundColor>, Mut<Outline>, Mut<BorderColor>)>,
+    opacity: f32,
+) {
+    let opacity = opacity * message.alpha;
+    let faded = |color: Color| color.with_a(color.a() * opacity);
+    let colors = message.section_colors(text.sections.len());
+    for (section, color) in text.sections.iter_mut().zip(colors) {
+        let color = faded(color);
+        if section.style.color != color {
+            section.style.color = color;
+        }
+    }
+    if let Some((mut bg, mut outline, mut border)) = highlight {
+        let (bg_color, outline_color) = (faded(message.bg), faded(message.border));
+        if bg.0 != bg_color {
+            bg.0 = bg_color;
+        }
+        if outline.color != outline_color {
+            outline.color = outline_color;
+        }
+        let icon_color = faded(message.icon.unwrap_or(Color::NONE));
+        if border.0 != icon_color {
+            border.0 = icon_color;
+        }
+    }
+}
+
+/// A message printed with the `blink` option of [`screen_print!`], it pulses
+/// `rate` times per second.
+#[derive(Component)]
+struct Blink {
+    rate: f64,
+}
+
+/// Pulse the brightness of [`Blink`]ing messages, and restore it once they
+/// stop blinking.
+fn blink_messages(
+    mut blinking: Query<(&mut Text, &Message, Option<Panel>, &Blink)>,
+    mut messages: Query<(&mut Text, &Message, Option<Panel>), Without<Blink>>,
+    mut stopped: RemovedComponents<Blink>,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+    overlay_opacity: Res<DebugOverlayOpacity>,
+) {
+    let current_time = clock.elapsed;
+    let opacity = |message: &Message| {
+        let opacity = message.opacity(&clock, options.fade);
+        opacity.map(|opacity| opacity * overlay_opacity.opacity)
+    };
+    for (mut text, message, highlight, blink) in &mut blinking {
+        let Some(opacity) = opacity(message) else {
+            continue;
+        };
+        // Oscillates between 0.25 and 1.0
+        let pulse = (TAU * current_time * blink.rate).cos() * 0.375 + 0.625;
+        set_opacity(&mut text, message, highlight, opacity * pulse as f32);
+    }
+    for entity in stopped.read() {
+        if let Ok((mut text, message, highlight)) = messages.get_mut(entity) {
+            let opacity = opacity(message).unwrap_or(1.0);
+            set_opacity(&mut text, message, highlight, opacity);
+        }
+    }
+}
+
+/// Keep the [`OverlayMessage`] of message entities up to date, and send
+/// [`OverlayMessageShown`] and [`OverlayMessageExpired`] events.
+fn publish_messages(
+    mut messages: Query<(Entity, Ref<Message>, Option<&mut OverlayMessage>)>,
+    mut shown_events: EventWriter<OverlayMessageShown>,
+    mut expired_events: EventWriter<OverlayMessageExpired>,
+    mut cmds: Commands,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+) {
+    for (entity, message, published) in &mut messages {
+        let expired = message.opacity(&clock, options.fade).is_none();
+        let was_expired = published.as_ref().map(|published| published.expired);
+        let mut notify = |published: &OverlayMessage| {
+            let (site, text, level) = (
+                published.site.clone(),
+                published.text.clone(),
+                published.level,
+            );
+            match (was_expired, expired) {
+                (None | Some(true), false) => {
+                    shown_events.send(OverlayMessageShown { entity, site, text, level });
+                }
+                (Some(false), true) => {
+                    expired_events.send(OverlayMessageExpired { entity, site, text, level });
+                }
+                _ => {}
+            }
+        };
+        let published = match published {
+            // Only build the text again when the message changed.
+            Some(mut published) if !message.is_changed() => {
+                if published.expired != expired {
+                    published.expired = expired;
+                    notify(&published);
+                }
+                continue;
+            }
+            published => published,
+        };
+        let mut text = message.plain_text();
+        text.truncate(text.trim_end().len());
+        let new = OverlayMessage {
+            text,
+            site: message.site.clone(),
+            level: message.level,
+            category: message.cat.clone(),
+            created: message.shown,
+            expiration: message.expiration,
+            expired,
+        };
+        notify(&new);
+        match published {
+            Some(mut published) => {
+                published.set_if_neq(new);
+            }
+            None => {
+                cmds.entity(entity).insert(new);
+            }
+        }
+    }
+}
+
+/// How long in seconds a message flashes after being copied, see
+/// [`OverlayPlugin::click_to_copy`].
+const FLASH_DURATION: f64 = 0.2;
+
+/// How long in seconds a message flashes after its value changed, see
+/// [`screen_watch!`].
+const WATCH_FLASH_DURATION: f64 = 0.5;
+
+/// A message that was just copied or whose watched value changed, it flashes
+/// until `until`.
+#[derive(Component)]
+struct Flash {
+    until: f64,
+}
+
+/// Expire messages printed with the `frames` option of [`screen_print!`]
+/// once they were displayed for that many frames.
+fn count_message_frames(
+    mut messages: Query<&mut Message>,
+    paused: Res<DebugOverlayPaused>,
+    stepping: Res<SteppingFrame>,
+    clock: Res<OverlayClock>,
+) {
+    if paused.paused || stepping.waiting {
+        return;
+    }
+    for mut message in &mut messages {
+        let Some(frames_left) = message.frames_left else {
+            continue;
+        };
+        let frames_left = frames_left.saturating_sub(1);
+        message.frames_left = (frames_left != 0).then_some(frames_left);
+        if frames_left == 0 {
+            message.expiration = clock.now(message.clock);
+        }
+    }
+}
+
+/// How long in seconds the changed lines of a message are highlighted, see
+/// the `diff` option of [`screen_print!`].
+const DIFF_DURATION: f64 = 1.0;
+
+/// A message highlighting the lines that changed since its previous text
+/// until `until`.
+#[derive(Component)]
+struct Diff {
+    until: f64,
+}
+
+/// Display the text of messages without the changed lines highlight once
+/// their [`Diff`] is over.
+fn expire_diffs(
+    mut messages: Query<(Entity, &Diff, &mut Text, &mut Message)>,
+    mut cmds: Commands,
+    clock: Res<OverlayClock>,
+) {
+    let current_time = clock.elapsed;
+    for (entity, diff, mut text, mut message) in &mut messages {
+        if diff.until <= current_time {
+            let spans = markup::parse(&message.text);
+            message.set_spans(&mut text, spans);
+            cmds.entity(entity).remove::<Diff>();
+        }
+    }
+}
+
+/// Copy clicked messages to the clipboard, see
+/// [`OverlayPlugin::click_to_copy`].
+fn copy_clicked_messages(
+    messages: Query<(Entity, &Interaction, &Message), Changed<Interaction>>,
+    mut cmds: Commands,
+    options: Res<Options>,
+    clock: Res<OverlayClock>,
+) {
+    // Truncated messages are also interactive, to expand them on hover.
+    if !options.click_to_copy {
+        return;
+    }
+    for (entity, interaction, message) in &messages {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let text = message.plain_text();
+        let text = text.trim_end();
+        #[cfg(feature = "clipboard")]
+        crate::clipboard::copy(text);
+        #[cfg(not(feature = "clipboard"))]
+        info!("Copied debug message:\n{text}");
+        let until = clock.elapsed + FLASH_DURATION;
+        cmds.entity(entity).insert(Flash { until });
+    }
+}
+
+/// Display all the lines of messages printed with the `lines` option while
+/// they are hovered, or while [`OverlayPlugin::expand_key`] is held.
+fn expand_messages(
+    mut messages: Query<(&mut Message, &mut Text, Option<&Interaction>)>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    options: Res<Options>,
+) {
+    let held = keys
+        .zip(options.expand_key)
+        .is_some_and(|(keys, expand_key)| keys.pressed(expand_key));
+    for (mut message, mut text, interaction) in &mut messages {
+        if message.lines.is_none() {
+            continue;
+        }
+        let hovered = interaction.is_some_and(|interaction| *interaction != Interaction::None);
+        let expanded = held || hovered;
+        if message.expanded != expanded {
+            message.expanded = expanded;
+            let spans = markup::parse(&message.text);
+            message.set_spans(&mut text, spans);
+        }
+    }
+}
+
+/// Highlight [`Flash`]ing messages, and restore their background once done.
+fn flash_messages(
+    mut messages: Query<(Entity, &Flash, &Message, Option<&mut BackgroundColor>)>,
+    mut cmds: Commands,
+    clock: Res<OverlayClock>,
+) {
+    let current_time = clock.elapsed;
+    for (entity, flash, message, bg) in &mut messages {
+        // In single text mode, messages have no background.
+        let Some(mut bg) = bg else {
+            cmds.entity(entity).remove::<Flash>();
+            continue;
+        };
+        if flash.until <= current_time {
+            bg.0 = message.bg;
+            cmds.entity(entity).remove::<Flash>();
+        } else {
+            bg.0 = message.color.with_a(0.4);
+        }
+    }
+}
+
+/// The text overlay plugin, you must add this plugin for the [`screen_print!`] macro
+/// to work.
+///
+/// You can manage some of the text properties by setting the fields of the
+/// plugin. Visual settings can be changed at runtime through the
+/// [`OverlayConfig`] resource.
+pub struct OverlayPlugin {
+    /// The color to use when none are specified in [`screen_print!`], by
+    /// default it is yellow.
+    pub fallback_color: Color,
+    /// The size of the message to display on screen, by default it is 13.0
+    pub font_size: f32,
+    /// Asset path of the font to use, by default it is the bevy default font.
+    pub font: Option<&'static str>,
+    /// What to do with messages exceeding the 4096 per frame limit, by
+    /// default they are dropped. Can be changed later through the
+    /// [`OverflowPolicy`] resource.
+    pub overflow_policy: OverflowPolicy,
+    /// Key to press to hide or show the overlay, by default there is none.
+    /// The overlay can also be hidden with the [`OverlayVisibility`] resource.
+    pub toggle_key: Option<KeyCode>,
+    /// Color of a panel drawn behind the messages, use a transparent color
+    /// to keep the scene visible. By default there is no background.
+    pub background_color: Option<Color>,
+    /// Space between the edge of the background panel and the text, by
+    /// default it is 4.0. Ignored when there is no background.
+    pub background_padding: f32,
+    /// How many idle `push` message entities to keep around for reuse, by
+    /// default it is 64.
+    pub pool_size: usize,
+    /// How long in seconds `push` message entities exceeding
+    /// [`Self::pool_size`] stay idle before being despawned, by default it
+    /// is 10 seconds.
+    pub pool_grace: f64,
+    /// Display all messages in a single `Text` entity, rather than one
+    /// entity per message. This is much cheaper when there are hundreds of
+    /// messages, but messages move around when other messages expire. By
+    /// default it is `false`.
+    pub single_text: bool,
+    /// Display the frame rate, frame time and entity count, adding the
+    /// required diagnostic plugins. By default it is `false`.
+    pub show_fps: bool,
+    /// Diagnostics of the [`DiagnosticsStore`] to display, smoothed, with
+    /// their path and suffix. Unlike [`OverlayPlugin::show_fps`], the plugins
+    /// measuring them are not added. By default there is none.
+    ///
+    /// ```rust,no_run
+    /// use bevy::diagnostic::{EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+    /// use bevy::prelude::*;
+    /// use bevy_debug_text_overlay::OverlayPlugin;
+    ///
+    /// let show_diagnostics = vec![
+    ///     FrameTimeDiagnosticsPlugin::FPS,
+    ///     EntityCountDiagnosticsPlugin::ENTITY_COUNT,
+    /// ];
+    /// App::new().add_plugins((
+    ///     DefaultPlugins,
+    ///     FrameTimeDiagnosticsPlugin,
+    ///     EntityCountDiagnosticsPlugin,
+    ///     OverlayPlugin { show_diagnostics, ..default() },
+    /// ));
+    /// ```
+    ///
+    /// [`DiagnosticsStore`]: bevy::diagnostic::DiagnosticsStore
+    pub show_diagnostics: Vec<DiagnosticPath>,
+    /// Display the entity and archetype counts, and how many entities have
+    /// the components of [`EcsStats`]. By default there is none.
+    pub ecs_stats: Option<EcsStats>,
+    /// Maximum number of messages displayed at the same time. Only the most
+    /// recently printed are shown, use PageUp/PageDown or the mouse wheel to
+    /// scroll through the others. By default there is no limit.
+    pub max_visible_lines: Option<usize>,
+    /// Maximum height of the stack of messages, `Val::Percent` being
+    /// relative to the window height. Only the most recently printed
+    /// messages that fit are shown, the others are counted in a "+ N more"
+    /// line at the end. Ignored in [`OverlayPlugin::single_text`] mode. By
+    /// default it is `Val::Auto`, there is no limit.
+    pub max_height: Val,
+    /// Maximum width of messages, `Val::Percent` being relative to the
+    /// window width. Longer lines are wrapped at word boundaries, and the
+    /// message takes the height of the wrapped text. Ignored in
+    /// [`OverlayPlugin::single_text`] mode. By default it is `Val::Auto`,
+    /// lines are never wrapped.
+    pub max_width: Val,
+    /// Spawn a camera dedicated to the overlay, rendering after all other
+    /// cameras, so that messages are always drawn on top of other UI. It
+    /// only renders the last `RenderLayers` layer, in addition to UI. This
+    /// also lets the overlay work when there is no other camera. By default
+    /// it is `false`.
+    pub dedicated_camera: bool,
+    /// The window on which to display messages without a `window:` option,
+    /// by default it is the primary window.
+    ///
+    /// Messages on other windows than the primary window are displayed using
+    /// the first camera rendering to that window, if there is no such camera,
+    /// one is spawned.
+    pub default_window: WindowRef,
+    /// What to display before each message, by default it is
+    /// [`MessagePrefix::Full`].
+    pub prefix: MessagePrefix,
+    /// Time to display before each message, by default it is
+    /// [`Timestamp::Hidden`].
+    pub timestamp: Timestamp,
+    /// Seconds it takes for expired messages to fade out rather than
+    /// disappear instantly, by default it is 0.
+    pub fade_duration: f64,
+    /// Also fade out the background when all messages are fading out. By
+    /// default it is `false`.
+    pub fade_background: bool,
+    /// Record the last `history` messages displayed in the [`OverlayHistory`]
+    /// resource. By default no history is kept.
+    pub history: Option<usize>,
+    /// Key to press to write the [`OverlayHistory`] to
+    /// [`OverlayHistory::path`], by default there is none.
+    pub history_dump_key: Option<KeyCode>,
+    /// Key to press to write an [`OverlaySnapshot`] of the displayed messages
+    /// to [`Self::snapshot_path`], by default there is none.
+    pub snapshot_key: Option<KeyCode>,
+    /// Where to write snapshots taken with [`Self::snapshot_key`], as JSON if
+    /// it has the `json` extension, as text otherwise. By default it is
+    /// `overlay_snapshot.txt`.
+    pub snapshot_path: &'static str,
+    /// Width of columns messages wrap into when they would go past the edge
+    /// of the window, each additional column is placed further from the
+    /// side of the [`DebugOverlayLocation::anchor`]. By default there is no
+    /// wrapping. Ignored in [`Self::single_text`] mode.
+    pub column_width: Option<f32>,
+    /// How messages are placed on screen, by default they are stacked, see
+    /// [`Layout`]. Ignored in [`Self::single_text`] mode.
+    pub layout: Layout,
+    /// Space in pixels between consecutive messages of [`Layout::Stack`], by
+    /// default it is 0.
+    pub line_spacing: f32,
+    /// Width in pixels of one level of the `indent` option of
+    /// [`screen_print!`], by default it is 16.
+    pub indent_width: f32,
+    /// Asset path of the font used by [`screen_table!`], it should be a
+    /// monospace font, so that columns are aligned. By default it is the bevy
+    /// default font, which is monospace.
+    pub table_font: Option<&'static str>,
+    /// Copy messages to the clipboard when clicked, they briefly flash to
+    /// confirm it. This requires the `clipboard` cargo feature, without it
+    /// clicked messages are logged instead. By default it is `false`. Ignored
+    /// in [`Self::single_text`] mode.
+    pub click_to_copy: bool,
+    /// Key to press to pause or resume the overlay, by default there is none.
+    /// The overlay can also be paused with the [`DebugOverlayPaused`]
+    /// resource.
+    pub pause_key: Option<KeyCode>,
+    /// Display before each message the seconds remaining before it expires.
+    /// By default it is `false`.
+    pub show_countdown: bool,
+    /// Also log messages with bevy's log macros, at their [`Level`], with the
+    /// `bevy_debug_text_overlay::mirror` target and the invocation site as
+    /// the `site` field. The site can't be the target, as `tracing` targets
+    /// are known at compile time. Like the [`OverlayHistory`], a
+    /// message is logged when it first shows up and each time its text
+    /// changes. Messages coming from the log are not logged again. By default
+    /// it is `false`.
+    pub mirror_to_log: bool,
+    /// Collect messages in the [`OverlayMessageBuffer`] resource rather than
+    /// displaying them. No UI entity is spawned and most other options are
+    /// ignored, this lets the plugin run without window, for example in
+    /// tests. By default it is `false`.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_debug_text_overlay::{screen_print, OverlayMessageBuffer, OverlayPlugin};
+    ///
+    /// let mut app = App::new();
+    /// app.add_plugins((MinimalPlugins, OverlayPlugin { headless: true, ..default() }))
+    ///     .add_systems(Update, || screen_print!("score: {}", 42));
+    /// app.update();
+    /// assert!(app.world.resource::<OverlayMessageBuffer>().contains("score: 42"));
+    /// ```
+    pub headless: bool,
+    /// Order of messages on screen, by default it is
+    /// [`MessageOrdering::Stable`], other orderings move messages around when
+    /// messages show up or expire. Ignored in [`Self::single_text`] mode.
+    pub ordering: MessageOrdering,
+    /// Cluster messages by the file they are printed from, under a header
+    /// line naming the file. Clicking a header collapses or expands its
+    /// messages. Messages with a `key:` are not grouped. By default it is
+    /// `false`. Ignored in [`Self::single_text`] mode.
+    pub group_by_file: bool,
+    /// Key to press to collapse or expand all file groups, see
+    /// [`Self::group_by_file`]. By default there is none.
+    pub collapse_key: Option<KeyCode>,
+    /// Key to hold to display all the lines of messages truncated with the
+    /// `lines` option of [`screen_print!`]. By default there is none, they
+    /// are only expanded while hovered by the mouse.
+    pub expand_key: Option<KeyCode>,
+    /// Merge identical consecutive `push` messages into one, followed by how
+    /// many times it was pushed, as in `retrying × 37`. Messages printed with
+    /// `coalesce: false` are never merged. By default it is `false`.
+    pub coalesce: bool,
+    /// Asset path of the sound played when messages printed with the `beep`
+    /// option show up. By default it is a short tone. This requires the
+    /// `audio` cargo feature and bevy's `AudioPlugin`.
+    pub beep_sound: Option<&'static str>,
+    /// Gamepad buttons to toggle the overlay, scroll through messages, and
+    /// cycle through the [`DebugOverlayCategories::hotkeys`] categories, for
+    /// dev kits without keyboard. By default there are none, use
+    /// `Some(GamepadBindings::default())` for the default bindings.
+    pub gamepad: Option<GamepadBindings>,
+    /// The clock message timeouts count in, by default it is
+    /// [`TimeSource::Virtual`], so that messages don't expire while the game
+    /// is paused. Messages printed with the `clock` option use their own.
+    pub time_source: TimeSource,
+    /// Environment variable read when the plugin is added, to configure the
+    /// overlay without code changes, by default it is `DEBUG_OVERLAY`.
+    ///
+    /// It is a comma-separated list of directives, like `RUST_LOG`:
+    /// * `off` or `on`: hide or show the overlay, see [`OverlayVisibility`].
+    /// * `<level>`: set [`OverlayFilter::min_level`], one of `trace`,
+    ///   `debug`, `info`, `warn` or `error`.
+    /// * `<category>=<level>`: set the minimum level of a category in
+    ///   [`OverlayFilter::categories`].
+    /// * `<category>=off`: disable a category in [`DebugOverlayCategories`].
+    ///
+    /// For example, `DEBUG_OVERLAY=warn,physics=trace,ai=off`. Invalid
+    /// directives are ignored with a warning.
+    pub env_var: Option<&'static str>,
+    /// While bevy's [`Stepping`] is enabled, only count message timeouts and
+    /// `frames:` on stepped frames, so that messages printed every frame by
+    /// stepped systems stay on screen between steps. A "STEPPING: frame N"
+    /// banner is displayed, counting the stepped frames. The `PostUpdate`
+    /// schedule, where the overlay runs, must not be stepped. By default it
+    /// is `false`.
+    pub follow_stepping: bool,
+}
+impl Default for OverlayPlugin {
+    fn default() -> Self {
+        Self {
+            fallback_color: Color::YELLOW,
+            font_size: 13.0,
+            font: None,
+            overflow_policy: OverflowPolicy::default(),
+            toggle_key: None,
+            background_color: None,
+            background_padding: 4.0,
+            pool_size: 64,
+            pool_grace: 10.0,
+            single_text: false,
+            show_fps: false,
+            show_diagnostics: Vec::new(),
+            ecs_stats: None,
+            max_visible_lines: None,
+            max_height: Val::Auto,
+            max_width: Val::Auto,
+            dedicated_camera: false,
+            default_window: WindowRef::Primary,
+            prefix: MessagePrefix::default(),
+            timestamp: Timestamp::default(),
+            fade_duration: 0.0,
+            fade_background: false,
+            history: None,
+            history_dump_key: None,
+            snapshot_key: None,
+            snapshot_path: "overlay_snapshot.txt",
+            column_width: None,
+            layout: Layout::Stack,
+            line_spacing: 0.0,
+            indent_width: 16.0,
+            table_font: None,
+            click_to_copy: false,
+            pause_key: None,
+            show_countdown: false,
+            mirror_to_log: false,
+            headless: false,
+            ordering: MessageOrdering::Stable,
+            group_by_file: false,
+            collapse_key: None,
+            expand_key: None,
+            coalesce: false,
+            beep_sound: None,
+            gamepad: None,
+            time_source: TimeSource::Virtual,
+            env_var: Some("DEBUG_OVERLAY"),
+            follow_stepping: false,
+        }
+    }
+}
+
+/// The systems of the [`OverlayPlugin`], they run in `PostUpdate`, before the
+/// UI layout, [`OverlaySet::Update`] then [`OverlaySet::Layout`].
+///
+/// Messages printed by systems running before them, such as systems in
+/// `Update` or `FixedUpdate`, show up in the same frame. Systems printing
+/// messages in `PostUpdate` must be ordered before [`OverlaySet::Update`]
+/// for their messages to show up in the same frame. Messages printed from the
+/// render world show up in the next frame.
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{screen_print, OverlaySet};
+///
+/// fn late_system(transforms: Query<&Transform>) {
+///     screen_print!("transforms: {}", transforms.iter().len());
+/// }
+/// App::new().add_systems(PostUpdate, late_system.before(OverlaySet::Update));
+/// ```
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OverlaySet {
+    /// Handle input and the printed messages, spawning and updating the
+    /// message entities.
+    Update,
+    /// Place messages on screen and update their appearance. Message
+    /// entities spawned in [`OverlaySet::Update`] are only laid out in the
+    /// next frame, once their size is known.
+    ///
+    /// It runs before [`UiSystem::Layout`], so that messages are placed in
+    /// the same frame, with the node sizes computed in the previous frame.
+    /// Systems ordered after it see the `Style` and `Visibility` of the
+    /// placed messages, but their `Node` size and `GlobalTransform` lag one
+    /// frame behind, unless the systems also run after [`UiSystem::Layout`].
+    Layout,
+}
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        // The `PrintCache`s of a previous `App` must not skip messages this
+        // one never received.
+        command_channels()
+            .generation
+            .fetch_add(1, Ordering::Relaxed);
+        for set in [OverlaySet::Update, OverlaySet::Layout] {
+            let set = set
+                .before(UiSystem::Layout)
+                .before(measure_text_system)
+                .before(update_target_camera_system)
+                .before(VisibilitySystems::VisibilityPropagate);
+            app.configure_sets(PostUpdate, set);
+        }
+        let layout = OverlaySet::Layout.after_ignore_deferred(OverlaySet::Update);
+        app.configure_sets(PostUpdate, layout);
+        if self.show_fps {
+            crate::diagnostics::add_fps_diagnostics(app);
+        }
+        if !self.show_diagnostics.is_empty() {
+            let paths = self.show_diagnostics.clone();
+            crate::diagnostics::add_diagnostics(app, paths);
+        }
+        if let Some(stats) = self.ecs_stats.clone() {
+            crate::diagnostics::add_ecs_stats(app, stats);
+        }
+        app.insert_resource::<Options>(self.into())
+            .insert_resource::<OverlayConfig>(self.into())
+            .insert_resource(self.overflow_policy)
+            .init_resource::<OverlayFilter>()
+            .init_resource::<OverlaySearch>()
+            .init_resource::<DebugOverlayCategories>()
+            .init_resource::<DebugOverlaySites>()
+            .init_resource::<OverlayTheme>()
+            .add_event::<OverlayMessageShown>()
+            .add_event::<OverlayMessageExpired>();
+        let sync = (
+            sync_overflow_policy,
+            sync_overlay_config,
+            sync_overlay_theme,
+            sync_print_gate,
+        )
+            .chain();
+        if self.headless {
+            app.init_resource::<OverlayMessageBuffer>().add_systems(
+                PostUpdate,
+                (sync, replay_messages, buffer_messages)
+                    .chain()
+                    .in_set(OverlaySet::Update),
+            );
+        } else {
+            self.build_display(app, sync);
+        }
+        self.read_env_var(&mut app.world);
+        // Filters of a previous `App` must not apply to messages printed
+        // before the first frame.
+        command_channels().set_filters(None);
+    }
+}
+impl OverlayPlugin {
+    /// Add what displays the messages, unless [`OverlayPlugin::headless`].
+    fn build_display(&self, app: &mut App, sync: SystemConfigs) {
+        if let Some(capacity) = self.history {
+            app.insert_resource(OverlayHistory::new(capacity));
+        }
+        app.init_resource::<DebugOverlayLocation>()
+            .init_resource::<OverlayVisibility>()
+            .init_resource::<OverlayScroll>()
+            .init_resource::<DebugOverlayPaused>()
+            .init_resource::<OverlayClock>()
+            .init_resource::<SteppingFrame>()
+            .init_resource::<CollapsedFiles>()
+            .init_resource::<DebugOverlayScale>()
+            .init_resource::<DebugOverlayOpacity>()
+            .init_resource::<LayoutInvalidated>()
+            .add_systems(Startup, (load_font, spawn_overlay_root))
+            .add_systems(
+                PostUpdate,
+                (
+                    (toggle_pause, tick_overlay_clock).chain(),
+                    sync,
+                    toggle_categories,
+                    toggle_file_groups,
+                    update_margins,
+                    (replay_messages, update_messages_as_per_commands).chain(),
+                    scale_messages,
+                    scroll_overlay,
+                    update_countdowns,
+                )
+                    .chain_ignore_deferred()
+                    .in_set(OverlaySet::Update),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    (invalidate_layout, layout_messages).chain(),
+                    count_message_frames,
+                    publish_messages,
+                    fade_messages,
+                    blink_messages,
+                    (copy_clicked_messages, expand_messages),
+                    flash_messages,
+                    expire_diffs,
+                    place_world_labels,
+                    render_single_text,
+                    (dump_history, write_snapshot),
+                    (toggle_overlay, show_overlay).chain(),
+                )
+                    .chain_ignore_deferred()
+                    .in_set(OverlaySet::Layout),
+            );
+        if self.follow_stepping {
+            let begin = begin_stepping_frame
+                .after(Stepping::begin_frame)
+                .before(Main::run_main);
+            let detect = detect_stepped_frame.before(tick_overlay_clock);
+            app.add_systems(Main, begin)
+                .add_systems(PostUpdate, detect.in_set(OverlaySet::Update));
+        }
+    }
+    /// Apply the settings of the [`OverlayPlugin::env_var`] environment
+    /// variable.
+    fn read_env_var(&self, world: &mut World) {
+        let Some(var) = self.env_var else {
+            return;
+        };
+        let Ok(spec) = std::env::var(var) else {
+            return;
+        };
+        let settings = EnvSettings::parse(&spec, |directive| {
+            warn!("Invalid {var} directive: {directive:?}");
+        });
+        if let Some(visible) = settings.visible {
+            if let Some(mut visibility) = world.get_resource_mut::<OverlayVisibility>() {
+                visibility.visible = visible;
+            }
+        }
+        let mut filter = world.resource_mut::<OverlayFilter>();
+        if let Some(min_level) = settings.min_level {
+            filter.min_level = min_level;
+        }
+        for (cat, level) in &settings.categories {
+            if let Some(level) = level {
+                filter.categories.insert(cat.clone().into(), *level);
+            }
+        }
+        let mut categories = world.resource_mut::<DebugOverlayCategories>();
+        for (cat, level) in settings.categories {
+            if level.is_none() {
+                categories.disable(cat);
+            }
+        }
+    }
+}
+
+/// Settings read from the [`OverlayPlugin::env_var`] environment variable.
+#[derive(Debug, Default, PartialEq)]
+struct EnvSettings {
+    visible: Option<bool>,
+    min_level: Option<Level>,
+    /// Minimum level of categories, `None` for disabled categories.
+    categories: Vec<(String, Option<Level>)>,
+}
+impl EnvSettings {
+    /// Parse comma-separated directives, calling `invalid` with the ones that
+    /// can't be parsed.
+    fn parse(spec: &str, mut invalid: impl FnMut(&str)) -> Self {
+        let level = |name: &str| match name.to_lowercase().as_str() {
+            "trace" => Some(Level::Trace),
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        };
+        let mut settings = EnvSettings::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((cat, "off")) if !cat.is_empty() => {
+                    settings.categories.push((cat.to_owned(), None));
+                }
+                Some((cat, name)) => match level(name) {
+                    Some(level) if !cat.is_empty() => {
+                        settings.categories.push((cat.to_owned(), Some(level)));
+                    }
+                    _ => invalid(directive),
+                },
+                None if directive == "off" => settings.visible = Some(false),
+                None if directive == "on" => settings.visible = Some(true),
+                None => match level(directive) {
+                    Some(level) => settings.min_level = Some(level),
+                    None => invalid(directive),
+                },
+            }
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_settings() {
+        let mut invalid = Vec::new();
+        let spec = "warn, physics=TRACE,ai=off,,verbose,gfx=loud,off";
+        let settings = EnvSettings::parse(spec, |d| invalid.push(d.to_owned()));
+        let expected = EnvSettings {
+            visible: Some(false),
+            min_level: Some(Level::Warn),
+            categories: vec![
+                ("physics".to_owned(), Some(Level::Trace)),
+                ("ai".to_owned(), None),
+            ],
+        };
+        assert_eq!(settings, expected);
+        assert_eq!(invalid, ["verbose", "gfx=loud"]);
+    }
+    #[test]
+    fn test_filters_read_again_when_changed() {
+        let channels = CommandChannels::new(8);
+        assert!(channels.allows(Level::Info, Some("ai")));
+        let mut categories = DebugOverlayCategories::default();
+        categories.disable("ai");
+        channels.set_filters(Some((OverlayFilter::default(), categories)));
+        assert!(!channels.allows(Level::Info, Some("ai")));
+        assert!(channels.allows(Level::Info, Some("physics")));
+
+        // Another channel has its own filters.
+        let other = CommandChannels::new(8);
+        assert!(other.allows(Level::Info, Some("ai")));
+        let filter = OverlayFilter { min_level: Level::Warn, ..default() };
+        other.set_filters(Some((filter, DebugOverlayCategories::default())));
+        assert!(!other.allows(Level::Info, Some("ai")));
+        assert!(!channels.allows(Level::Info, Some("ai")));
+        channels.set_filters(None);
+        assert!(channels.allows(Level::Info, Some("ai")));
+    }
+    #[test]
+    fn test_dropped_messages_reported_once() {
+        let channels = CommandChannels::new(2);
+        for _ in 0..5 {
+            channels.send(Command::ClearAll);
+        }
+        assert_eq!(channels.queue.len(), 2);
+        // Logged rather than displayed with the default policy.
+        assert!(channels.take_dropped().is_none());
+        assert_eq!(channels.dropped.load(Ordering::Relaxed), 0);
+
+        let collapse = OverflowPolicy::Collapse as u8;
+        channels.policy.store(collapse, Ordering::Relaxed);
+        for _ in 0..3 {
+            channels.send(Command::ClearAll);
+        }
+        let Some(Command::Refresh { text, .. }) = channels.take_dropped() else {
+            panic!("expected a line counting the dropped messages");
+        };
+        assert_eq!(text, "3 messages dropped");
+        assert!(channels.take_dropped().is_none());
+    }
+    #[test]
+    fn test_message_opacity() {
+        let message = Message::new(InvocationSiteKey::Named("a".into()), 0.0, 2.0, Color::WHITE);
+        let at = |elapsed| OverlayClock { elapsed, ..default() };
+        assert_eq!(message.opacity(&at(1.0), 1.0), Some(1.0));
+        assert_eq!(message.opacity(&at(2.5), 1.0), Some(0.5));
+        assert_eq!(message.opacity(&at(3.5), 1.0), None);
+        assert_eq!(message.opacity(&at(2.5), 0.0), None);
+
+        // Messages with a `clock` expire in their own clock.
+        let message = Message { clock: Some(TimeSource::Real), ..message };
+        let clock = OverlayClock { elapsed: 1.0, real: 5.0, ..default() };
+        assert_eq!(message.opacity(&clock, 1.0), None);
+        assert_eq!(message.remaining(&clock), -3.0);
+    }
+    fn push_list(expirations: &[f64]) -> PushList {
+        let entry = |(i, &expired)| PushEntry { entity: Entity::from_raw(i as u32), expired };
+        PushList(expirations.iter().enumerate().map(entry).collect())
+    }
+    #[test]
+    fn test_prune_keeps_pool_size() {
+        let mut list = push_list(&[1., 2., 3., 4., 20.]);
+        let pruned = list.prune(15., 5., 2);
+        assert_eq!(pruned, [Entity::from_raw(0), Entity::from_raw(1)]);
+        assert_eq!(list.0.len(), 3);
+    }
+    #[test]
+    fn test_prune_grace_period() {
+        let mut list = push_list(&[1., 2., 12., 13.]);
+        let pruned = list.prune(15., 5., 0);
+        assert_eq!(pruned, [Entity::from_raw(0), Entity::from_raw(1)]);
+        assert_eq!(list.0.len(), 2);
+    }
+    #[test]
+    fn test_alignment_margins() {
+        let px = Val::Px;
+        let margins = |alignment, anchor| alignment_margins(alignment, anchor, 10.0);
+        assert_eq!(
+            margins(Alignment::Right, Anchor::TopLeft),
+            (px(10.0), Val::ZERO)
+        );
+        assert_eq!(
+            margins(Alignment::Center, Anchor::TopRight),
+            (Val::ZERO, px(5.0))
+        );
+        assert_eq!(
+            margins(Alignment::Left, Anchor::BottomRight),
+            (Val::ZERO, px(10.0))
+        );
+        assert_eq!(
+            margins(Alignment::Anchor, Anchor::TopRight),
+            (Val::ZERO, Val::ZERO)
+        );
+    }
+    #[test]
+    fn test_height_overflow_keeps_recent() {
+        let entity = Entity::from_raw;
+        let active = vec![
+            (entity(0), (0, 1.0), 10.0),
+            (entity(1), (0, 3.0), 10.0),
+            (entity(2), (0, 2.0), 10.0),
+        ];
+        assert_eq!(height_overflow(active.clone(), 30.0, 10.0), []);
+        assert_eq!(height_overflow(active.clone(), 25.0, 0.0), [entity(0)]);
+        // Room is made for the summary line.
+        assert_eq!(
+            height_overflow(active.clone(), 25.0, 10.0),
+            [entity(2), entity(0)]
+        );
+        // High priority messages are kept even when older.
+        let mut active = active;
+        active[0].1 .0 = 1;
+        assert_eq!(height_overflow(active, 25.0, 0.0), [entity(2)]);
+    }
+    #[test]
+    fn test_format_table() {
+        let header = vec!["name".to_owned(), "hp".to_owned()];
+        let rows = vec![
+            vec!["goblin".to_owned(), "12".to_owned()],
+            vec!["orc".to_owned()],
+            vec!["troll".to_owned(), "120".to_owned(), "boss".to_owned()],
+        ];
+        let expected = "name    hp\n------  ---  ----\ngoblin  12\norc\ntroll   120  boss";
+        assert_eq!(format_table(header, rows), expected);
+    }
+    #[test]
+    fn test_progress_text() {
+        assert_eq!(
+            progress_text("load", 0.5),
+            "load [##########          ] 50%"
+        );
+        assert_eq!(
+            progress_text("hp", 1.0),
+            format!("hp [{}] 100%", "#".repeat(20))
+        );
+    }
+    #[test]
+    fn test_paused_commands_keep_last() {
+        let refresh = |key: &'static str, text: &str| Command::Refresh {
+            key: InvocationSiteKey::Named(key.into()),
+            text: text.to_owned(),
+            options: PrintOptions::default(),
+        };
+        let texts = |commands: Vec<Command>| -> Vec<String> {
+            let text = |command| match command {
+                Command::Refresh { text, .. } => text,
+                Command::Clear { key } => format!("clear {key}"),
+                _ => unreachable!(),
+            };
+            commands.into_iter().map(text).collect()
+        };
+        let mut paused = PausedCommands::default();
+        paused.push(refresh("a", "a1"));
+        paused.push(refresh("b", "b1"));
+        paused.push(refresh("a", "a2"));
+        assert_eq!(texts(paused.drain()), ["a2", "b1"]);
+
+        paused.push(refresh("a", "a1"));
+        paused.push(Command::Clear { key: InvocationSiteKey::Named("a".into()) });
+        paused.push(refresh("a", "a2"));
+        assert_eq!(texts(paused.drain()), ["a1", "clear [a]", "a2"]);
+        assert!(paused.drain().is_empty());
+
+        for value in [1.0, 2.0] {
+            paused.push(Command::Sample {
+                key: InvocationSiteKey::Named("graph".into()),
+                label: "graph".to_owned(),
+                value,
+                series: Series::Graph,
+                options: PrintOptions::default(),
+                pending: (),
+            });
+        }
+        let values = |command| match command {
+            Command::Sample { value, .. } => value,
+            _ => unreachable!(),
+        };
+        let values: Vec<_> = paused.drain().into_iter().map(values).collect();
+        assert_eq!(values, [1.0, 2.0]);
+    }
+    #[test]
+    fn test_every_prints_once_from_racing_threads() {
+        static LAST_PRINT: AtomicU64 = AtomicU64::new(0);
+        let options = PrintOptions::default().every(60.0, &LAST_PRINT);
+        let printed = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    if !options.throttled() {
+                        printed.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(printed.into_inner(), 1);
+    }
+    #[test]
+    fn test_paused_site_changes_keep_last() {
+        let key = || InvocationSiteKey::Named("a".into());
+        let edit = |edit| Command::Edit { key: key(), edit };
+        let describe = |commands: Vec<Command>| -> Vec<String> {
+            let describe = |command| match command {
+                Command::Pin { pinned, .. } => format!("pin {pinned}"),
+                Command::Edit { edit: MessageEdit::ExtendTimeout(secs), .. } => {
+                    format!("extend {secs}")
+                }
+                Command::Edit { edit: MessageEdit::Color(color), .. } => {
+                    format!("blue {}", color == Color::BLUE)
+                }
+                Command::Clear { .. } => "clear".to_owned(),
+                _ => unreachable!(),
+            };
+            commands.into_iter().map(describe).collect()
+        };
+        let mut paused = PausedCommands::default();
+        for pinned in [true, false, true] {
+            paused.push(Command::Pin { key: key(), pinned });
+        }
+        for _ in 0..3 {
+            paused.push(edit(MessageEdit::ExtendTimeout(1.0)));
+        }
+        paused.push(edit(MessageEdit::Color(Color::RED)));
+        paused.push(edit(MessageEdit::Color(Color::BLUE)));
+        let expected = ["pin true", "extend 3", "blue true"];
+        assert_eq!(describe(paused.drain()), expected);
+
+        for _ in 0..2 {
+            paused.push(edit(MessageEdit::ExtendTimeout(1.0)));
+            paused.push(Command::Clear { key: key() });
+        }
+        paused.push(edit(MessageEdit::ExtendTimeout(1.0)));
+        let expected = ["extend 1", "extend 1", "clear", "extend 1"];
+        assert_eq!(describe(paused.drain()), expected);
+    }
+    #[test]
+    fn test_guard_logging_drops_nested_records() {
+        let mut logged = Vec::new();
+        guard_logging(|| {
+            logged.push("outer");
+            guard_logging(|| logged.push("nested"));
+        });
+        guard_logging(|| logged.push("next"));
+        assert_eq!(logged, ["outer", "next"]);
+    }
+    #[test]
+    fn test_throttled_after_concurrent_print() {
+        static LAST_PRINT: AtomicU64 = AtomicU64::new(0);
+        let options = PrintOptions::default().every(60.0, &LAST_PRINT);
+        assert!(!options.throttled());
+        assert!(options.throttled());
+        // Printed by another thread after `now` was read.
+        LAST_PRINT.store(now_micros() + 1_000_000, Ordering::Relaxed);
+        assert!(options.throttled());
+    }
+    #[test]
+    fn test_print_cache_sent_concurrently() {
+        let cache = PrintCache::new();
+        let key = InvocationSiteKey::Named("cache".into());
+        let options = PrintOptions::default();
+        assert!(!cache.unchanged(0, &key, &options, format_args!("value")));
+        // Sent by another thread after `now` was read.
+        cache
+            .sent
+            .store(now_micros() + 1_000_000, Ordering::Relaxed);
+        assert!(cache.unchanged(0, &key, &options, format_args!("value")));
+    }
+    #[test]
+    fn test_print_cache_unchanged() {
+        let cache = PrintCache::new();
+        let key = InvocationSiteKey::Named("cache".into());
+        let options = PrintOptions::default();
+        let unchanged = |generation, options: &PrintOptions, value: u32| {
+            cache.unchanged(generation, &key, options, format_args!("value: {value}"))
+        };
+        assert!(!unchanged(0, &options, 1));
+        assert!(unchanged(0, &options, 1));
+        assert!(!unchanged(0, &options, 2));
+        assert!(!unchanged(1, &options, 2));
+        assert!(!unchanged(1, &options.clone().col(Color::RED), 2));
+        assert!(unchanged(1, &options.col(Color::RED), 2));
+    }
+    #[test]
+    fn test_timer_stats_record() {
+        let stats = TimerStats::new();
+        stats.record(Duration::from_millis(4));
+        stats.record(Duration::from_millis(1));
+        let totals = stats.record(Duration::from_millis(7));
+        assert_eq!(totals.count, 3);
+        assert_eq!(totals.total / totals.count, Duration::from_millis(4));
+        assert_eq!(totals.min, Duration::from_millis(1));
+        assert_eq!(totals.max, Duration::from_millis(7));
+    }
+
+    #[test]
+    fn test_compare_messages() {
+        use std::cmp::Ordering::{Greater, Less};
+        let message = |site: &'static str, shown| {
+            let site = InvocationSiteKey::Named(site.into());
+            Message {
+                shown,
+                ..Message::new(site, 0.0, 1.0, Color::WHITE)
+            }
+        };
+        let (early_b, late_a) = (message("b", 1.0), message("a", 2.0));
+        let (text_a, text_b) = (
+            Text::from_section("a", default()),
+            Text::from_section("b", default()),
+        );
+        let compare = |ordering, a, b| compare_messages(ordering, a, b);
+        let early_b_text_a = (&early_b, Some(&text_a));
+        let late_a_text_b = (&late_a, Some(&text_b));
+        assert_eq!(
+            compare(MessageOrdering::CallSite, early_b_text_a, late_a_text_b),
+            Greater
+        );
+        assert_eq!(
+            compare(
+                MessageOrdering::Chronological,
+                early_b_text_a,
+                late_a_text_b
+            ),
+            Less
+        );
+        assert_eq!(
+            compare(MessageOrdering::Alphabetical, early_b_text_a, late_a_text_b),
+            Less
+        );
+        assert_eq!(
+            compare(
+                MessageOrdering::Alphabetical,
+                (&early_b, None),
+                late_a_text_b
+            ),
+            Less
         );
     }
 }
@@ -0,0 +1,334 @@
+//! Streaming of the overlay messages over TCP, see [`OverlayRemotePlugin`]
+//! and [`OverlayRemoteSink`].
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::replay::{MessageRecord, RecordTaps, RecordedChange};
+
+/// Largest message accepted by [`OverlayRemoteSink`], in bytes.
+const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// How long the streaming thread waits for messages before checking for
+/// new clients.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a client may take to read a message before it is disconnected,
+/// so that a stalled client doesn't hold back the others.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many messages wait to be streamed at most, further messages are
+/// dropped until the streaming thread catches up.
+const STREAM_CAPACITY: usize = 4096;
+
+/// How many processes [`OverlayRemoteSink`] receives messages from at once,
+/// further connections are closed.
+const MAX_SOURCES: usize = 16;
+
+/// How long a process may take to send the rest of a message once it started
+/// sending it, before [`OverlayRemoteSink`] disconnects it.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Streams the messages received by the overlay to TCP clients, so that they
+/// can be read on another device than the one running the game.
+///
+/// Each message is sent as a [`MessageRecord`] encoded in JSON, prefixed by
+/// its length in bytes as a big-endian `u32`. The record `time` is the game
+/// time when the message was received. Clients only get the messages received
+/// after they connected. Messages are not streamed in
+/// [`OverlayPlugin::headless`](crate::OverlayPlugin::headless) mode.
+///
+/// Clients that don't read the messages fast enough are disconnected. The
+/// messages are not authenticated nor encrypted, prefer a loopback address
+/// and forward the port over SSH to read them from another device.
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin).
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{OverlayPlugin, OverlayRemotePlugin};
+///
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     OverlayPlugin::default(),
+///     OverlayRemotePlugin { address: "127.0.0.1:7878" },
+/// ));
+/// ```
+pub struct OverlayRemotePlugin {
+    /// Address to listen for clients on, by default it is `127.0.0.1:7878`.
+    pub address: &'static str,
+}
+impl Default for OverlayRemotePlugin {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:7878" }
+    }
+}
+impl Plugin for OverlayRemotePlugin {
+    fn build(&self, app: &mut App) {
+        let address = self.address;
+        let listener = TcpListener::bind(address).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        });
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to stream debug overlay on {address}: {err}");
+                return;
+            }
+        };
+        let (tap, records) = mpsc::sync_channel(STREAM_CAPACITY);
+        let spawned = thread::Builder::new()
+            .name("debug overlay remote".to_owned())
+            .spawn(move || stream_records(&listener, &records));
+        if let Err(err) = spawned {
+            error!("Failed to stream debug overlay on {address}: {err}");
+            return;
+        }
+        info!("Streaming debug overlay on {address}");
+        app.world
+            .get_resource_or_insert_with(RecordTaps::default)
+            .0
+            .push(tap);
+    }
+}
+
+/// Send `records` to the clients connecting to `listener`, until the app
+/// exits.
+fn stream_records(listener: &TcpListener, records: &Receiver<MessageRecord>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+    loop {
+        for client in listener.incoming() {
+            let blocking = |client: &TcpStream| {
+                client.set_nonblocking(false)?;
+                client.set_write_timeout(Some(WRITE_TIMEOUT))
+            };
+            match client {
+                // Writes are blocking, so that messages aren't cut. Clients
+                // timing out are dropped, cut messages are never read.
+                Ok(client) if blocking(&client).is_ok() => clients.push(client),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => warn!("Failed to accept debug overlay client: {err}"),
+            }
+        }
+        let record = match records.recv_timeout(ACCEPT_INTERVAL) {
+            Ok(record) => record,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let frame = match frame(&record) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("Failed to encode debug overlay message: {err}");
+                continue;
+            }
+        };
+        clients.retain_mut(|client| client.write_all(&frame).is_ok());
+    }
+}
+
+/// Displays the messages streamed by the [`OverlayRemotePlugin`] of other
+/// processes, alongside the local ones.
+///
+/// The site of received messages is prefixed by `source`, so that their
+/// prefix reads as `[server src/main.rs:3:5]`. Messages show up as soon as
+/// they are received, ignoring their `time`. This is useful for client/server
+/// games, to read the debug output of both on one screen.
+///
+/// At most 16 processes are connected at once. Processes that stall in the
+/// middle of a message are disconnected. The messages are not authenticated,
+/// prefer a loopback address and forward the port over SSH to receive them
+/// from another device.
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin).
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{OverlayPlugin, OverlayRemoteSink};
+///
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     OverlayPlugin::default(),
+///     OverlayRemoteSink { address: "127.0.0.1:7879", source: "server" },
+/// ));
+/// ```
+pub struct OverlayRemoteSink {
+    /// Address to listen for other processes on, by default it is
+    /// `127.0.0.1:7879`.
+    pub address: &'static str,
+    /// Name prefixed to the site of received messages, by default `remote`.
+    pub source: &'static str,
+}
+impl Default for OverlayRemoteSink {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:7879", source: "remote" }
+    }
+}
+impl Plugin for OverlayRemoteSink {
+    fn build(&self, _app: &mut App) {
+        let Self { address, source } = *self;
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to receive debug overlay on {address}: {err}");
+                return;
+            }
+        };
+        let spawned = thread::Builder::new()
+            .name("debug overlay sink".to_owned())
+            .spawn(move || receive_clients(&listener, source));
+        match spawned {
+            Ok(_) => info!("Receiving debug overlay on {address}"),
+            Err(err) => error!("Failed to receive debug overlay on {address}: {err}"),
+        }
+    }
+}
+
+/// Display the messages sent by the processes connecting to `listener`.
+fn receive_clients(listener: &TcpListener, source: &'static str) {
+    // Each receiving thread holds a clone, until its source disconnects.
+    let sources = Arc::new(());
+    for client in listener.incoming() {
+        let client = match client {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to accept debug overlay source: {err}");
+                continue;
+            }
+        };
+        if Arc::strong_count(&sources) > MAX_SOURCES {
+            warn!("Refused debug overlay source: already {MAX_SOURCES} connected");
+            continue;
+        }
+        let connected = Arc::clone(&sources);
+        let spawned = thread::Builder::new()
+            .name("debug overlay source".to_owned())
+            .spawn(move || {
+                receive_records(client, source);
+                drop(connected);
+            });
+        if let Err(err) = spawned {
+            warn!("Failed to receive debug overlay source: {err}");
+        }
+    }
+}
+
+/// Display the messages read from `client`, until it disconnects.
+fn receive_records(mut client: TcpStream, source: &'static str) {
+    // Sites of the messages displayed by `client`, so that clearing all its
+    // messages leaves the ones of other sources.
+    let mut sites = HashSet::new();
+    let mut next = [0];
+    loop {
+        // Sources may stay idle for as long as they want, but not stall in
+        // the middle of a message.
+        let waited = client
+            .set_read_timeout(None)
+            .and_then(|()| client.peek(&mut next));
+        let started = waited.and_then(|_| client.set_read_timeout(Some(READ_TIMEOUT)));
+        if let Err(err) = started {
+            warn!("Disconnected debug overlay source: {err}");
+            return;
+        }
+        match read_frame(&mut client) {
+            Ok(Some(record)) => match &record.change {
+                RecordedChange::ClearAll => {
+                    for site in sites.drain() {
+                        let cleared = record.clone();
+                        let change = RecordedChange::Clear;
+                        MessageRecord { site, change, ..cleared }.send(Some(source));
+                    }
+                }
+                change => {
+                    if let RecordedChange::Print(_) = change {
+                        sites.insert(record.site.clone());
+                    } else {
+                        sites.remove(&record.site);
+                    }
+                    record.send(Some(source));
+                }
+            },
+            Ok(None) => return,
+            Err(err) => {
+                warn!("Disconnected debug overlay source: {err}");
+                return;
+            }
+        }
+    }
+}
+
+/// The next record written to `reader` by [`frame`], `None` once `reader`
+/// ends.
+fn read_frame(mut reader: impl Read) -> io::Result<Option<MessageRecord>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_LEN {
+        let error = format!("message of {length} bytes is too large");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+    let mut json = vec![0; length];
+    reader.read_exact(&mut json)?;
+    Ok(Some(serde_json::from_slice(&json)?))
+}
+
+/// `record` as JSON, prefixed by its length.
+fn frame(record: &MessageRecord) -> serde_json::Result<Vec<u8>> {
+    let json = serde_json::to_vec(record)?;
+    let mut frame = Vec::with_capacity(json.len() + 4);
+    frame.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&json);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> MessageRecord {
+        MessageRecord {
+            time: 1.5,
+            site: "src/main.rs:3:5".to_owned(),
+            change: RecordedChange::Print("hello".to_owned()),
+            color: None,
+            timeout: 7.0,
+            push: false,
+        }
+    }
+    #[test]
+    fn test_frame_length_prefix() {
+        let record = record();
+        let frame = frame(&record).unwrap();
+        let (length, json) = frame.split_at(4);
+        assert_eq!(
+            u32::from_be_bytes(length.try_into().unwrap()) as usize,
+            json.len()
+        );
+        let decoded: MessageRecord = serde_json::from_slice(json).unwrap();
+        assert_eq!(decoded, record);
+    }
+    #[test]
+    fn test_read_frames() {
+        let cleared = MessageRecord { change: RecordedChange::ClearAll, ..record() };
+        let mut stream = frame(&record()).unwrap();
+        stream.extend(frame(&cleared).unwrap());
+        let mut reader = &stream[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(record()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(cleared));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        let too_large = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+        assert!(read_frame(&too_large[..]).is_err());
+    }
+}
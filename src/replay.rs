@@ -0,0 +1,284 @@
+//! Record and replay of the messages sent to the overlay, see
+//! [`OverlayRecording`] and [`OverlayReplay`].
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::mpsc::{SyncSender, TrySendError};
+#[cfg(feature = "serde")]
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::prelude::{Color, Res, ResMut, Resource, Time};
+
+use crate::{command_channels, InvocationSiteKey, PrintOptions};
+
+/// A message received by the overlay, see [`OverlayRecording`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageRecord {
+    /// Game time in seconds since the first recorded message.
+    pub time: f64,
+    /// The invocation site of the message, or its `key:`, empty for
+    /// [`RecordedChange::ClearAll`].
+    pub site: String,
+    pub change: RecordedChange,
+    pub color: Option<Color>,
+    /// For how many seconds the message shows up.
+    pub timeout: f64,
+    /// Whether the message was printed with the `push` option.
+    pub push: bool,
+}
+/// What a [`MessageRecord`] did to the overlay.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedChange {
+    /// The message was printed, with this text including its color markup.
+    Print(String),
+    /// The message was removed with [`screen_clear!`](crate::screen_clear).
+    Clear,
+    /// All messages were removed with `screen_clear!()`.
+    ClearAll,
+}
+impl MessageRecord {
+    /// The name of `site` in a [`MessageRecord`].
+    pub(crate) fn site_name(site: &InvocationSiteKey) -> String {
+        match site {
+            InvocationSiteKey::Site { file, line, column } => format!("{file}:{line}:{column}"),
+            InvocationSiteKey::Named(name) => name.to_string(),
+        }
+    }
+    /// Display the message again, its site name prefixed by `source` if any.
+    ///
+    /// [`RecordedChange::ClearAll`] clears all messages, whatever their
+    /// source.
+    pub(crate) fn send(self, source: Option<&str>) {
+        let site = match source {
+            Some(source) => format!("{source} {}", self.site),
+            None => self.site,
+        };
+        let key = InvocationSiteKey::Named(Cow::Owned(site));
+        let channels = command_channels();
+        let text = match self.change {
+            RecordedChange::Print(text) => text,
+            RecordedChange::Clear => return channels.clear(key),
+            RecordedChange::ClearAll => return channels.clear_all(),
+        };
+        let mut options = PrintOptions::default().sec(self.timeout).push(self.push);
+        if let Some(color) = self.color {
+            options = options.col(color);
+        }
+        channels.print(key, options, || text);
+    }
+}
+
+/// Records the messages received by the overlay while this resource exists.
+///
+/// Insert it to start recording, the recorded messages can be replayed
+/// later with the [`OverlayReplay`] resource. With the `serde` feature, they
+/// can be saved to a file with [`OverlayRecording::write_to`]. Messages are
+/// not recorded in [`OverlayPlugin::headless`](crate::OverlayPlugin::headless)
+/// mode.
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{OverlayPlugin, OverlayRecording};
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, OverlayPlugin::default()))
+///     .init_resource::<OverlayRecording>();
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlayRecording {
+    /// Game time of the first recorded message.
+    start: Option<f64>,
+    records: Vec<MessageRecord>,
+}
+impl OverlayRecording {
+    /// Recorded messages, oldest first.
+    pub fn records(&self) -> &[MessageRecord] {
+        &self.records
+    }
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.records.clear();
+    }
+    /// Add `record`, received at `time` game time, to the recording.
+    pub(crate) fn push(&mut self, time: f64, record: MessageRecord) {
+        let start = *self.start.get_or_insert(time);
+        let time = time - start;
+        self.records.push(MessageRecord { time, ..record });
+    }
+    /// Write the recording to `writer`, one JSON object per line.
+    #[cfg(feature = "serde")]
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        for record in &self.records {
+            serde_json::to_writer(&mut writer, record)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+    /// Write the recording to the file at `path`, replacing it if it exists.
+    #[cfg(feature = "serde")]
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Channels receiving a [`MessageRecord`] of each message received by the
+/// overlay, with its game time, for other frontends.
+///
+/// The channels are bounded, records are dropped when a frontend doesn't
+/// keep up, rather than piling up in memory.
+#[derive(Resource, Default)]
+pub(crate) struct RecordTaps(pub(crate) Vec<SyncSender<MessageRecord>>);
+impl RecordTaps {
+    /// Send `record` to the channels, removing the disconnected ones.
+    pub(crate) fn send(&mut self, record: &MessageRecord) {
+        let sent = |tap: &mut SyncSender<_>| tap.try_send(record.clone());
+        self.0
+            .retain_mut(|tap| !matches!(sent(tap), Err(TrySendError::Disconnected(_))));
+    }
+}
+
+/// Displays recorded messages again, at the same pace as they were
+/// recorded, while this resource exists.
+///
+/// The replay starts when the resource is inserted, each message is
+/// displayed once as much game time elapsed since the start of the replay as
+/// when it was recorded. With the `serde` feature, a recording saved to a
+/// file can be replayed with [`OverlayReplay::read_from`].
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{OverlayRecording, OverlayReplay};
+///
+/// fn replay_recording(mut cmds: Commands, recording: Res<OverlayRecording>) {
+///     cmds.insert_resource(OverlayReplay::new(recording.records().to_vec()));
+///     cmds.remove_resource::<OverlayRecording>();
+/// }
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlayReplay {
+    /// Game time when the replay started.
+    start: Option<f64>,
+    records: VecDeque<MessageRecord>,
+}
+impl OverlayReplay {
+    /// A replay of `records`, they are sorted by time.
+    pub fn new(records: impl IntoIterator<Item = MessageRecord>) -> Self {
+        let mut records: Vec<_> = records.into_iter().collect();
+        records.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { start: None, records: records.into() }
+    }
+    /// Whether all messages were replayed.
+    pub fn is_finished(&self) -> bool {
+        self.records.is_empty()
+    }
+    /// A replay of the recording read from `reader`, as written by
+    /// [`OverlayRecording::write`].
+    #[cfg(feature = "serde")]
+    pub fn read(reader: impl BufRead) -> io::Result<Self> {
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self::new(records))
+    }
+    /// A replay of the recording in the file at `path`, as written by
+    /// [`OverlayRecording::write_to`].
+    #[cfg(feature = "serde")]
+    pub fn read_from(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Send the messages of the [`OverlayReplay`] due at the current time.
+pub(crate) fn replay_messages(replay: Option<ResMut<OverlayReplay>>, time: Res<Time>) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    if replay.is_finished() {
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    let elapsed = now - *replay.start.get_or_insert(now);
+    while replay
+        .records
+        .front()
+        .is_some_and(|record| record.time <= elapsed)
+    {
+        let record = replay
+            .records
+            .pop_front()
+            .expect("Checked by the loop condition");
+        record.send(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(time: f64, text: &str) -> MessageRecord {
+        MessageRecord {
+            time,
+            site: "src/main.rs:3:5".to_owned(),
+            change: RecordedChange::Print(text.to_owned()),
+            color: None,
+            timeout: 7.0,
+            push: false,
+        }
+    }
+    #[test]
+    fn test_recording_starts_at_first_message() {
+        let mut recording = OverlayRecording::default();
+        recording.push(10.0, record(0.0, "a"));
+        recording.push(12.5, record(0.0, "b"));
+        let times: Vec<_> = recording
+            .records()
+            .iter()
+            .map(|record| record.time)
+            .collect();
+        assert_eq!(times, [0.0, 2.5]);
+    }
+    #[test]
+    fn test_replay_sorted() {
+        let replay = OverlayReplay::new([record(2.0, "b"), record(1.0, "a")]);
+        let changes: Vec<_> = replay.records.iter().map(|r| r.change.clone()).collect();
+        assert_eq!(changes, [record(0.0, "a").change, record(0.0, "b").change]);
+    }
+    #[test]
+    fn test_taps_drop_records_when_full() {
+        let (tap, records) = std::sync::mpsc::sync_channel(1);
+        let mut taps = RecordTaps(vec![tap]);
+        taps.send(&record(0.0, "a"));
+        taps.send(&record(0.0, "b"));
+        assert_eq!(taps.0.len(), 1);
+        assert_eq!(records.try_iter().collect::<Vec<_>>(), [record(0.0, "a")]);
+
+        drop(records);
+        taps.send(&record(0.0, "c"));
+        assert!(taps.0.is_empty());
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_write_read() {
+        let mut recording = OverlayRecording::default();
+        recording.push(1.0, record(0.0, "hp: [red]3[/]"));
+        recording.push(
+            2.0,
+            MessageRecord { change: RecordedChange::Clear, ..record(0.0, "") },
+        );
+        let clear_all = RecordedChange::ClearAll;
+        recording.push(3.0, MessageRecord { change: clear_all, ..record(0.0, "") });
+        let mut output = Vec::new();
+        recording.write(&mut output).unwrap();
+        let replay = OverlayReplay::read(&output[..]).unwrap();
+        assert_eq!(Vec::from(replay.records), recording.records());
+    }
+}
@@ -0,0 +1,132 @@
+//! Capture of the messages displayed by the overlay, see [`OverlaySnapshot`].
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use bevy::prelude::Color;
+
+use crate::InvocationSiteKey;
+
+/// A message displayed by the overlay, see [`OverlaySnapshot`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SnapshotLine {
+    /// Overlay time in seconds when the message showed up.
+    pub time: f64,
+    pub site: InvocationSiteKey,
+    /// The message as displayed, prefix included, without color markup.
+    pub text: String,
+    pub color: Color,
+}
+
+/// The messages displayed by the overlay at some point in time.
+///
+/// Use `OverlaySnapshot::capture` to take a snapshot, or press
+/// `OverlayPlugin::snapshot_key` to write one to
+/// `OverlayPlugin::snapshot_path`. Attach it to bug reports rather than a
+/// screenshot, so that messages can be searched and copied.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OverlaySnapshot {
+    /// Overlay time in seconds when the snapshot was taken.
+    pub time: f64,
+    /// Displayed messages, in the order they showed up.
+    pub lines: Vec<SnapshotLine>,
+}
+impl OverlaySnapshot {
+    /// Write the snapshot to `writer` as text, one message per line.
+    pub fn write_text(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "overlay snapshot at {:.3}s", self.time)?;
+        for SnapshotLine { time, site, text, color } in &self.lines {
+            let color = hex(*color);
+            writeln!(writer, "[{time:.3}s] {color} {site} {}", text.trim_end())?;
+        }
+        writer.flush()
+    }
+    /// Write the snapshot to `writer` as a JSON object.
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "{{\"time\":{},\"lines\":[", self.time)?;
+        for (i, SnapshotLine { time, site, text, color }) in self.lines.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let (site, text) = (json_string(&site.to_string()), json_string(text.trim_end()));
+            let color = hex(*color);
+            write!(
+                writer,
+                "{separator}{{\"time\":{time},\"site\":{site},\"text\":{text},\"color\":\"{color}\"}}"
+            )?;
+        }
+        writeln!(writer, "]}}")?;
+        writer.flush()
+    }
+    /// Write the snapshot to the file at `path`, replacing it if it exists.
+    /// It is written as JSON if `path` has the `json` extension, as text
+    /// otherwise.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let writer = BufWriter::new(File::create(path)?);
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            self.write_json(writer)
+        } else {
+            self.write_text(writer)
+        }
+    }
+}
+
+/// `color` as `#rrggbbaa`.
+fn hex(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
+
+/// `text` as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> OverlaySnapshot {
+        let site = InvocationSiteKey::Site { file: "src/main.rs", line: 3, column: 5 };
+        let line = SnapshotLine {
+            time: 1.5,
+            site,
+            text: "hp: \"low\"\n10\n".to_owned(),
+            color: Color::RED,
+        };
+        OverlaySnapshot { time: 2.0, lines: vec![line] }
+    }
+    #[test]
+    fn test_write_text() {
+        let mut output = Vec::new();
+        snapshot().write_text(&mut output).unwrap();
+        let expected = "overlay snapshot at 2.000s\n\
+                        [1.500s] #ff0000ff [src/main.rs:3:5] hp: \"low\"\n10\n";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+    #[test]
+    fn test_write_json() {
+        let mut output = Vec::new();
+        snapshot().write_json(&mut output).unwrap();
+        let expected = r##"{"time":2,"lines":[{"time":1.5,"site":"[src/main.rs:3:5]","text":"hp: \"low\"\n10","color":"#ff0000ff"}]}"##;
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{expected}\n"));
+    }
+}
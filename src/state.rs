@@ -0,0 +1,96 @@
+//! Clearing the overlay on state transitions, see [`ClearOnStateChange`].
+use std::any::TypeId;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::{command_channels, InvocationSiteKey};
+
+/// Removes the messages printed with the `scope:` option of
+/// [`screen_print!`](crate::screen_print) from the screen, like
+/// [`screen_clear!`](crate::screen_clear), when the `S` state they are scoped
+/// to exits.
+///
+/// This prevents debug messages of a state, such as the main menu, from
+/// lingering in the next one. Other messages, such as a HUD, stay on screen.
+/// Messages scoped to the exited state and printed in the [`OnExit`],
+/// [`OnTransition`] and [`OnEnter`] schedules of the transition still show
+/// up, until the state exits again.
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin).
+///
+/// ```rust,no_run
+/// use bevy::prelude::*;
+/// use bevy_debug_text_overlay::{screen_print, ClearOnStateChange, OverlayPlugin};
+///
+/// #[derive(States, Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+/// enum GameState {
+///     #[default]
+///     Menu,
+///     InGame,
+/// }
+///
+/// fn menu_system() {
+///     screen_print!(scope: GameState::Menu, "hovered: Play");
+/// }
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, OverlayPlugin::default()))
+///     .init_state::<GameState>()
+///     .add_plugins(ClearOnStateChange::<GameState>::default())
+///     .add_systems(Update, menu_system.run_if(in_state(GameState::Menu)));
+/// ```
+pub struct ClearOnStateChange<S>(PhantomData<fn() -> S>);
+impl<S> Default for ClearOnStateChange<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<S: States> Plugin for ClearOnStateChange<S> {
+    fn build(&self, app: &mut App) {
+        let clear = clear_on_state_change::<S>.before(apply_state_transition::<S>);
+        app.init_resource::<ScopedSites>()
+            .add_systems(StateTransition, clear);
+    }
+}
+
+/// A value of a [`States`] type, the `scope:` option of
+/// [`screen_print!`](crate::screen_print).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct StateScope {
+    state: TypeId,
+    value: u64,
+}
+impl StateScope {
+    pub(crate) fn of<S: States>(value: &S) -> Self {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        Self { state: TypeId::of::<S>(), value: hasher.finish() }
+    }
+}
+
+/// Sites of the displayed messages printed with a `scope:` option, per
+/// state, only recorded with a [`ClearOnStateChange`] plugin.
+#[derive(Resource, Default)]
+pub(crate) struct ScopedSites(pub(crate) HashMap<StateScope, HashSet<InvocationSiteKey>>);
+
+/// Clear the messages scoped to the current state if a transition to another
+/// state is queued.
+fn clear_on_state_change<S: States>(
+    state: Option<Res<State<S>>>,
+    next: Option<Res<NextState<S>>>,
+    mut scoped: ResMut<ScopedSites>,
+) {
+    let (Some(state), Some(next)) = (state, next) else {
+        return;
+    };
+    if next.0.as_ref().is_some_and(|next| next != state.get()) {
+        let sites = scoped.0.remove(&StateScope::of(state.get()));
+        let channels = command_channels();
+        for site in sites.into_iter().flatten() {
+            channels.clear(site);
+        }
+    }
+}
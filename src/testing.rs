@@ -0,0 +1,55 @@
+//! Drive the overlay in an `App` without window nor renderer, for tests.
+//!
+//! Not part of the public API, it may change in any release.
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::OverlayPlugin;
+
+/// Messages are sent to a global queue, so that apps can't run in parallel.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// An `App` with the [`OverlayPlugin`] and a manually advanced [`Time`].
+///
+/// Only one `Harness` exists at a time, creating another waits for the
+/// previous one to be dropped.
+pub struct Harness {
+    pub app: App,
+    _serial: MutexGuard<'static, ()>,
+}
+impl Harness {
+    /// A harness ignoring the [`OverlayPlugin::env_var`], so that tests don't
+    /// depend on the environment they run in.
+    pub fn new(plugin: OverlayPlugin) -> Self {
+        Self::with_env_var(OverlayPlugin { env_var: None, ..plugin })
+    }
+    /// A harness reading the [`OverlayPlugin::env_var`] of `plugin`.
+    pub fn with_env_var(plugin: OverlayPlugin) -> Self {
+        let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut app = App::new();
+        app.init_resource::<Time>().add_plugins(plugin);
+        Self { app, _serial: serial }
+    }
+    /// Advance time by `secs`, and run two frames, so that spawned messages
+    /// are laid out.
+    pub fn frame(&mut self, secs: f32) {
+        let mut time = self.app.world.resource_mut::<Time>();
+        time.advance_by(Duration::from_secs_f32(secs));
+        self.app.update();
+        self.app.update();
+    }
+    /// Text of the visible messages, sorted.
+    pub fn visible(&mut self) -> Vec<String> {
+        let mut query = self.app.world.query::<(&Text, &Visibility)>();
+        let visible = |(text, vis): (&Text, &Visibility)| {
+            let text: String = text.sections.iter().map(|s| s.value.as_str()).collect();
+            let text = text.trim_end().to_owned();
+            (*vis == Visibility::Inherited).then_some(text)
+        };
+        let mut texts: Vec<_> = query.iter(&self.app.world).filter_map(visible).collect();
+        texts.sort();
+        texts
+    }
+}
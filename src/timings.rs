@@ -0,0 +1,179 @@
+//! Time spent in bevy systems, see [`SystemTimingsPlugin`].
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::mem;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use bevy::log::tracing_subscriber::{layer::Context, Layer};
+use bevy::log::BoxedSubscriber;
+use bevy::prelude::*;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::span::{Attributes, Id};
+use bevy::utils::tracing::Subscriber;
+use bevy::utils::{get_short_name, HashMap};
+
+use crate::screen_print;
+
+/// Time spent in each system since the last [`SystemTimingsPlugin`] update,
+/// by system name.
+static SYSTEM_TIMES: Mutex<BTreeMap<String, Duration>> = Mutex::new(BTreeMap::new());
+
+fn system_times() -> MutexGuard<'static, BTreeMap<String, Duration>> {
+    SYSTEM_TIMES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A system span, and when it was entered if the system is running.
+struct SystemSpan {
+    name: String,
+    entered: Option<Instant>,
+}
+
+/// Measures the time spent in bevy systems, for the [`SystemTimingsPlugin`].
+///
+/// Bevy only creates a span per system with its `trace` cargo feature, this
+/// layer measures the time spent in them. Add it to the bevy `LogPlugin`
+/// subscriber with [`timing_subscriber`].
+#[derive(Default)]
+pub struct SystemTimingLayer {
+    spans: Mutex<HashMap<Id, SystemSpan>>,
+}
+impl SystemTimingLayer {
+    fn spans(&self) -> MutexGuard<'_, HashMap<Id, SystemSpan>> {
+        self.spans.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Add a [`SystemTimingLayer`] to `subscriber`, to use as the bevy
+/// `LogPlugin`'s `update_subscriber`.
+///
+/// ```rust,no_run
+/// use bevy::{log::LogPlugin, prelude::*};
+/// use bevy_debug_text_overlay::{timing_subscriber, OverlayPlugin, SystemTimingsPlugin};
+///
+/// App::new().add_plugins((
+///     DefaultPlugins.set(LogPlugin {
+///         update_subscriber: Some(timing_subscriber),
+///         ..default()
+///     }),
+///     OverlayPlugin::default(),
+///     SystemTimingsPlugin::default(),
+/// ));
+/// ```
+pub fn timing_subscriber(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    use bevy::log::tracing_subscriber::layer::SubscriberExt;
+    Box::new(subscriber.with(SystemTimingLayer::default()))
+}
+
+/// Reads the `name` field of system spans.
+#[derive(Default)]
+struct SystemName(Option<String>);
+impl Visit for SystemName {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+impl<S: Subscriber> Layer<S> for SystemTimingLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "system" {
+            return;
+        }
+        let mut name = SystemName::default();
+        attrs.record(&mut name);
+        if let Some(name) = name.0 {
+            let span = SystemSpan { name, entered: None };
+            self.spans().insert(id.clone(), span);
+        }
+    }
+    fn on_enter(&self, id: &Id, _ctx: Context<'_, S>) {
+        if let Some(span) = self.spans().get_mut(id) {
+            span.entered = Some(Instant::now());
+        }
+    }
+    fn on_exit(&self, id: &Id, _ctx: Context<'_, S>) {
+        let mut spans = self.spans();
+        let Some(span) = spans.get_mut(id) else {
+            return;
+        };
+        let Some(entered) = span.entered.take() else {
+            return;
+        };
+        let elapsed = entered.elapsed();
+        let mut times = system_times();
+        match times.get_mut(&span.name) {
+            Some(time) => *time += elapsed,
+            None => {
+                times.insert(span.name.clone(), elapsed);
+            }
+        }
+    }
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.spans().remove(&id);
+    }
+}
+
+/// Displays the systems taking the most time per frame, as measured by the
+/// [`SystemTimingLayer`].
+///
+/// This requires the [`OverlayPlugin`](crate::OverlayPlugin), the
+/// [`SystemTimingLayer`] and bevy's `trace` cargo feature. Systems running
+/// after this plugin's system in the `Last` schedule are not measured.
+pub struct SystemTimingsPlugin {
+    /// How many systems to display, by default it is 5.
+    pub top: usize,
+    /// How often in seconds (of real time) to update the timings, they are
+    /// averaged over the frames in between. By default it is 1 second.
+    pub interval: f64,
+}
+impl Default for SystemTimingsPlugin {
+    fn default() -> Self {
+        Self { top: 5, interval: 1.0 }
+    }
+}
+impl Plugin for SystemTimingsPlugin {
+    fn build(&self, app: &mut App) {
+        let config = TimingsConfig { top: self.top, interval: self.interval };
+        app.insert_resource(config)
+            .add_systems(Last, show_system_timings);
+    }
+}
+
+#[derive(Resource)]
+struct TimingsConfig {
+    top: usize,
+    interval: f64,
+}
+
+fn show_system_timings(
+    config: Res<TimingsConfig>,
+    time: Option<Res<Time<Real>>>,
+    mut frames: Local<u32>,
+    mut elapsed: Local<Duration>,
+) {
+    let Some(time) = time else {
+        return;
+    };
+    *frames += 1;
+    *elapsed += time.delta();
+    if elapsed.as_secs_f64() < config.interval {
+        return;
+    }
+    *elapsed = Duration::ZERO;
+    let frames = mem::take(&mut *frames);
+    let mut times: Vec<_> = mem::take(&mut *system_times()).into_iter().collect();
+    times.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+    let mut text = String::new();
+    for (name, total) in times.iter().take(config.top) {
+        let millis = total.as_secs_f64() * 1000.0 / f64::from(frames);
+        let _ = write!(text, "\n{millis:>8.3}ms {}", get_short_name(name));
+    }
+    let timeout = 2.0 * config.interval;
+    screen_print!(sec: timeout, key: "system timings", "slowest systems:{text}");
+}
@@ -0,0 +1,91 @@
+//! A [`tracing_subscriber::Layer`] displaying tracing events in the overlay.
+use std::fmt::{self, Write};
+
+use bevy::log::tracing_subscriber::{layer::Context, Layer};
+use bevy::log::BoxedSubscriber;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::{self, Event, Subscriber};
+
+use crate::overlay::guard_logging;
+use crate::{command_channels, InvocationSiteKey, Level, PrintOptions};
+
+/// Display tracing events as `push` messages in the overlay, colored by level.
+///
+/// Add it to the bevy `LogPlugin` subscriber with [`overlay_subscriber`], it
+/// then only receives events that pass the `LogPlugin` filter. Use
+/// [`Layer::with_filter`] for finer control.
+#[derive(Default)]
+pub struct OverlayLayer;
+
+/// Add an [`OverlayLayer`] to `subscriber`, to use as the bevy `LogPlugin`'s
+/// `update_subscriber`.
+///
+/// ```rust,no_run
+/// use bevy::{log::LogPlugin, prelude::*};
+/// use bevy_debug_text_overlay::{overlay_subscriber, OverlayPlugin};
+///
+/// App::new().add_plugins((
+///     DefaultPlugins.set(LogPlugin {
+///         update_subscriber: Some(overlay_subscriber),
+///         ..default()
+///     }),
+///     OverlayPlugin::default(),
+/// ));
+/// ```
+pub fn overlay_subscriber(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    use bevy::log::tracing_subscriber::layer::SubscriberExt;
+    Box::new(subscriber.with(OverlayLayer))
+}
+
+/// Formats the `message` field first, followed by other fields as
+/// `name=value`.
+#[derive(Default)]
+struct EventText {
+    message: String,
+    fields: String,
+}
+impl Visit for EventText {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+impl<S: Subscriber> Layer<S> for OverlayLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Errors of the overlay itself are not displayed, it has its own way
+        // to report them.
+        if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
+        let level = match *metadata.level() {
+            tracing::Level::ERROR => Level::Error,
+            tracing::Level::WARN => Level::Warn,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::DEBUG => Level::Debug,
+            tracing::Level::TRACE => Level::Trace,
+        };
+        let key = InvocationSiteKey::Site {
+            file: metadata.file().unwrap_or(metadata.target()),
+            line: metadata.line().unwrap_or(0),
+            column: 0,
+        };
+        let options = PrintOptions::default()
+            .push(true)
+            .level(level)
+            .col(level.color())
+            .logged(true);
+        // Events mirrored by the overlay, or emitted while formatting this
+        // one, would cause infinite recursion.
+        guard_logging(|| {
+            command_channels().print(key, options, || {
+                let mut text = EventText::default();
+                event.record(&mut text);
+                text.message + &text.fields
+            });
+        });
+    }
+}
@@ -0,0 +1,1431 @@
+//! Tests of the overlay systems, driven by a minimal `App` with a manually
+//! advanced `Time`, see [`Harness`].
+#![cfg(feature = "debug")]
+use std::cell::Cell;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore};
+use bevy::input::gamepad::{
+    GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+};
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use bevy::window::{PrimaryWindow, WindowResized};
+use bevy_debug_text_overlay::testing::Harness;
+use bevy_debug_text_overlay::{
+    screen_assert, screen_clear, screen_counter, screen_group, screen_histogram, screen_print,
+    screen_print_if, screen_timer, screen_watch, Alignment, ClearOnStateChange,
+    DebugOverlayCategories, DebugOverlayLocation, DebugOverlayOpacity, DebugOverlayPaused,
+    DebugOverlayScale, DebugOverlaySites, EcsStats, GamepadBindings, InvocationSiteKey, Layout,
+    Level, MessagePrefix, OverlayCommands, OverlayConfig, OverlayFilter, OverlayHistory,
+    OverlayLayout, OverlayMessage, OverlayMessageExpired, OverlayMessageShown, OverlayPlugin,
+    OverlayRecording, OverlayReplay, OverlaySearch, OverlaySender, OverlaySet, OverlaySnapshot,
+    OverlayTheme, OverlayViewport, OverlayVisibility, RecordedChange, TimeSource,
+};
+
+#[test]
+fn refresh_replaces_text() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    for text in ["first", "second"] {
+        screen_print!(no_prefix, "{text}");
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["second"]);
+}
+
+#[test]
+fn refresh_twice_in_one_frame_keeps_last() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(no_prefix, key: "fresh", "first");
+    screen_print!(no_prefix, key: "fresh", "second");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["second"]);
+}
+
+#[test]
+fn clear_in_spawning_frame_hides_message() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(persist, no_prefix, key: "brief", "brief");
+    screen_clear!("brief");
+    harness.frame(0.1);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn push_adds_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    for text in ["first", "second"] {
+        screen_print!(push, no_prefix, "{text}");
+    }
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["first", "second"]);
+}
+
+#[test]
+fn messages_expire() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    OverlayCommands::default()
+        .sec(1.0)
+        .print_keyed("expire", "expiring");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["[expire] expiring"]);
+    harness.frame(1.0);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn clear_hides_message() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(persist, no_prefix, key: "recording", "recording ON");
+    harness.frame(100.0);
+    assert_eq!(harness.visible(), ["recording ON"]);
+    screen_clear!("recording");
+    harness.frame(0.1);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn clear_all_hides_every_message() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(persist, no_prefix, key: "status", "status");
+    screen_print!(push, no_prefix, "pushed");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["pushed", "status"]);
+    screen_print!(push, no_prefix, "fresh");
+    OverlayCommands::default().clear_all();
+    harness.frame(0.1);
+    assert!(harness.visible().is_empty());
+    screen_print!(push, no_prefix, "after");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["after"]);
+}
+
+#[test]
+fn pinned_messages_stay_until_unpinned() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let overlay = OverlayCommands::default();
+    screen_print!(pin, sec: 1.0, no_prefix, key: "fps", "fps");
+    screen_print!(sec: 1.0, no_prefix, key: "hp", "hp");
+    harness.frame(0.1);
+    overlay.pin("hp");
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["fps", "hp"]);
+    overlay.unpin("fps");
+    overlay.unpin("hp");
+    harness.frame(5.0);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn messages_are_edited_without_their_text() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let overlay = OverlayCommands::default();
+    screen_print!(sec: 1.0, no_prefix, key: "hp", "hp");
+    screen_print!(sec: 1.0, no_prefix, key: "mp", "mp");
+    harness.frame(0.1);
+    overlay.extend_timeout("hp", 10.0);
+    overlay.set_color("hp", Color::RED);
+    harness.frame(0.1);
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["hp"]);
+    let mut query = harness.app.world.query::<(&Text, &Visibility)>();
+    let (text, _) = query
+        .iter(&harness.app.world)
+        .find(|(_, vis)| **vis == Visibility::Inherited)
+        .unwrap();
+    assert_eq!(text.sections.last().unwrap().style.color, Color::RED);
+    // Faded out messages are not displayed again.
+    overlay.extend_timeout("mp", 10.0);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["hp"]);
+}
+
+#[test]
+fn messages_are_published_as_components() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(sec: 1.0, level: Level::Error, cat: "ai", key: "target", "lost [red]target[/]");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query::<&OverlayMessage>();
+    let published: Vec<_> = query.iter(&harness.app.world).cloned().collect();
+    let [message] = published.as_slice() else {
+        panic!("expected one message, got {published:?}");
+    };
+    assert_eq!(message.text, "[target] lost target");
+    assert_eq!(message.site, InvocationSiteKey::Named("target".into()));
+    assert_eq!(message.level, Level::Error);
+    assert_eq!(message.category.as_deref(), Some("ai"));
+    assert_eq!(message.expiration - message.created, 1.0);
+    assert!(!message.expired);
+    harness.frame(5.0);
+    let mut query = harness.app.world.query::<&OverlayMessage>();
+    assert!(query.single(&harness.app.world).expired);
+}
+
+#[test]
+fn unchanged_messages_stay_displayed_while_printed() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    for _ in 0..5 {
+        screen_print!(sec: 1.0, no_prefix, "steady");
+        harness.frame(0.4);
+        assert_eq!(harness.visible(), ["steady"]);
+    }
+    harness.frame(5.0);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn events_are_sent_when_messages_show_up_and_expire() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    fn texts<E: Event>(harness: &Harness, text: impl Fn(&E) -> &str) -> Vec<String> {
+        let events = harness.app.world.resource::<Events<E>>();
+        let mut reader = events.get_reader();
+        reader
+            .read(events)
+            .map(|event| text(event).to_owned())
+            .collect()
+    }
+    screen_print!(sec: 1.0, no_prefix, key: "fuel", level: Level::Warn, "low fuel");
+    harness.frame(0.1);
+    let shown = texts(&harness, |event: &OverlayMessageShown| &event.text);
+    assert_eq!(shown, ["low fuel"]);
+    screen_print!(sec: 1.0, no_prefix, key: "fuel", level: Level::Warn, "low fuel");
+    harness.frame(0.1);
+    assert!(texts(&harness, |event: &OverlayMessageShown| &event.text).is_empty());
+    harness.frame(5.0);
+    let expired = texts(&harness, |event: &OverlayMessageExpired| &event.text);
+    assert_eq!(expired, ["low fuel"]);
+}
+
+#[test]
+fn selected_diagnostics_are_displayed() {
+    let path = DiagnosticPath::const_new("render/gpu_time");
+    let plugin = OverlayPlugin { show_diagnostics: vec![path.clone()], ..default() };
+    let mut harness = Harness::new(plugin);
+    let mut store = DiagnosticsStore::default();
+    store.add(Diagnostic::new(path.clone()).with_suffix("ms"));
+    let measurement = DiagnosticMeasurement { time: Instant::now(), value: 4.0 };
+    store.get_mut(&path).unwrap().add_measurement(measurement);
+    harness.app.insert_resource(store);
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert!(visible
+        .iter()
+        .any(|text| text.ends_with("render/gpu_time: 4.00ms")));
+}
+
+#[derive(Component)]
+struct Enemy;
+
+#[test]
+fn ecs_stats_count_components() {
+    let stats = EcsStats::default().component::<Enemy>();
+    let mut harness = Harness::new(OverlayPlugin { ecs_stats: Some(stats), ..default() });
+    harness.app.world.spawn_batch([Enemy, Enemy, Enemy]);
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert!(visible.iter().any(|text| text.ends_with("Enemy: 3")));
+    assert!(visible.iter().any(|text| text.contains("archetypes: ")));
+}
+
+#[derive(States, Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+enum GameState {
+    #[default]
+    Menu,
+    InGame,
+}
+
+#[test]
+fn state_change_clears_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let entered = || screen_print!(push, no_prefix, "entered game");
+    harness
+        .app
+        .init_state::<GameState>()
+        .add_plugins(ClearOnStateChange::<GameState>::default())
+        .add_systems(OnEnter(GameState::InGame), entered);
+    screen_print!(persist, no_prefix, key: "menu", scope: GameState::Menu, "menu");
+    screen_print!(persist, no_prefix, key: "hud", "hud");
+    harness.frame(0.1);
+    let sorted = |harness: &mut Harness| {
+        let mut visible = harness.visible();
+        visible.sort();
+        visible
+    };
+    assert_eq!(sorted(&mut harness), ["hud", "menu"]);
+    harness
+        .app
+        .world
+        .insert_resource(NextState(Some(GameState::Menu)));
+    harness.frame(0.1);
+    assert_eq!(sorted(&mut harness), ["hud", "menu"]);
+    harness
+        .app
+        .world
+        .insert_resource(NextState(Some(GameState::InGame)));
+    harness.frame(0.1);
+    assert_eq!(sorted(&mut harness), ["entered game", "hud"]);
+}
+
+#[test]
+fn virtual_clock_messages_expire_with_game_time() {
+    let plugin = OverlayPlugin { time_source: TimeSource::Real, ..default() };
+    let mut harness = Harness::new(plugin);
+    // Real time doesn't advance, as if the game ran infinitely fast.
+    harness.app.init_resource::<Time<Real>>();
+    screen_print!(sec: 1.0, no_prefix, "real");
+    screen_print!(sec: 1.0, clock: TimeSource::Virtual, no_prefix, "virtual");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["real", "virtual"]);
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["real"]);
+}
+
+#[test]
+fn real_time_messages_expire_while_game_is_paused() {
+    let plugin = OverlayPlugin { time_source: TimeSource::Real, ..default() };
+    let mut harness = Harness::new(plugin);
+    harness.app.init_resource::<Time<Real>>();
+    screen_print!(sec: 1.0, no_prefix, "real");
+    screen_print!(sec: 1.0, clock: TimeSource::Virtual, no_prefix, "virtual");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["real", "virtual"]);
+    // The game is paused, only real time advances.
+    let mut real_time = harness.app.world.resource_mut::<Time<Real>>();
+    real_time.advance_by(Duration::from_secs(5));
+    harness.frame(0.0);
+    assert_eq!(harness.visible(), ["virtual"]);
+}
+
+#[test]
+fn watch_flashes_end_while_game_is_paused() {
+    let plugin = OverlayPlugin {
+        time_source: TimeSource::Real,
+        prefix: MessagePrefix::Hidden,
+        ..default()
+    };
+    let mut harness = Harness::new(plugin);
+    harness.app.init_resource::<Time<Real>>();
+    let flashing = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&BackgroundColor>();
+        query.iter(&harness.app.world).any(|bg| bg.0 != Color::NONE)
+    };
+    // The game is paused, only real time advances.
+    for hp in [10, 9] {
+        screen_watch!(hp);
+        harness.frame(0.0);
+    }
+    assert!(flashing(&mut harness));
+    let mut real_time = harness.app.world.resource_mut::<Time<Real>>();
+    real_time.advance_by(Duration::from_secs(1));
+    harness.frame(0.0);
+    assert!(!flashing(&mut harness));
+}
+
+#[test]
+fn post_update_messages_are_received_in_the_same_frame() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let late = || screen_print!(no_prefix, "late");
+    harness
+        .app
+        .add_systems(PostUpdate, late.before(OverlaySet::Update));
+    harness.app.update();
+    let mut texts = harness.app.world.query::<&Text>();
+    let late = |text: &Text| text.sections.iter().any(|s| s.value == "late\n");
+    assert!(texts.iter(&harness.app.world).any(late));
+}
+
+#[derive(Resource, Default)]
+struct LaidOut(Vec<bool>);
+
+#[test]
+fn systems_after_layout_see_placed_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let laid_out = |texts: Query<&Visibility, With<Text>>, mut laid_out: ResMut<LaidOut>| {
+        let visible = texts.iter().any(|vis| *vis == Visibility::Inherited);
+        laid_out.0.push(visible);
+    };
+    harness
+        .app
+        .init_resource::<LaidOut>()
+        .add_systems(PostUpdate, laid_out.after(OverlaySet::Layout));
+    screen_print!(no_prefix, "placed");
+    harness.frame(0.1);
+    assert_eq!(harness.app.world.resource::<LaidOut>().0, [false, true]);
+}
+
+#[test]
+fn icons_are_displayed_before_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(icon: "*", no_prefix, "glyph");
+    screen_print!(icon: Color::RED, no_prefix, "square");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["* glyph", "square"]);
+    let mut borders = harness.app.world.query::<(&Text, &BorderColor, &Style)>();
+    let (_, border, style) = borders
+        .iter(&harness.app.world)
+        .find(|(text, ..)| text.sections.iter().any(|s| s.value == "square\n"))
+        .unwrap();
+    assert_eq!(border.0, Color::RED);
+    assert_ne!(style.border.left, Val::ZERO);
+}
+
+#[test]
+fn search_hides_other_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(no_prefix, key: "hp", "hp: 3");
+    screen_print!(no_prefix, key: "stamina", "stamina: 20");
+    harness.frame(0.1);
+    harness.app.world.resource_mut::<OverlaySearch>().text = "STAM".to_owned();
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["stamina: 20"]);
+    harness
+        .app
+        .world
+        .resource_mut::<OverlaySearch>()
+        .text
+        .clear();
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["hp: 3", "stamina: 20"]);
+}
+
+#[test]
+fn env_var_sets_the_filter() {
+    std::env::set_var("DEBUG_OVERLAY_TEST", "warn,physics=trace,ai=off");
+    let plugin = OverlayPlugin { env_var: Some("DEBUG_OVERLAY_TEST"), ..default() };
+    let mut harness = Harness::with_env_var(plugin);
+    // The variable is only read when the plugin is built.
+    std::env::remove_var("DEBUG_OVERLAY_TEST");
+    screen_print!(no_prefix, "info");
+    screen_print!(no_prefix, level: Level::Warn, "warn");
+    screen_print!(no_prefix, cat: "physics", "physics info");
+    screen_print!(no_prefix, level: Level::Error, cat: "ai", "ai error");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["physics info", "warn"]);
+}
+
+#[test]
+fn per_frame_messages_stay_between_steps() {
+    use bevy::ecs::schedule::Stepping;
+
+    let plugin = OverlayPlugin { follow_stepping: true, ..default() };
+    let mut harness = Harness::new(plugin);
+    let mut stepping = Stepping::new();
+    stepping.add_schedule(Update).enable();
+    let print_frame = |mut frame: Local<u32>| {
+        *frame += 1;
+        screen_print!(frames: 1, no_prefix, "update {}", *frame);
+    };
+    harness
+        .app
+        .insert_resource(stepping)
+        .add_systems(Update, print_frame);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["STEPPING: frame 0"]);
+    harness
+        .app
+        .world
+        .resource_mut::<Stepping>()
+        .continue_frame();
+    harness.frame(0.1);
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["STEPPING: frame 1", "update 1"]);
+    harness.app.world.resource_mut::<Stepping>().disable();
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["update 3"]);
+}
+
+/// Counts how many times it is formatted.
+struct Formatted<'a>(&'a Cell<u32>);
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.set(self.0.get() + 1);
+        f.write_str("formatted")
+    }
+}
+
+#[test]
+fn filtered_out_messages_are_not_formatted() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    harness.app.world.resource_mut::<OverlayFilter>().min_level = Level::Warn;
+    let mut categories = harness.app.world.resource_mut::<DebugOverlayCategories>();
+    categories.disable("ai");
+    harness.frame(0.1);
+    let formatted = Cell::new(0);
+    screen_print!(no_prefix, "info {}", Formatted(&formatted));
+    screen_print!(no_prefix, level: Level::Error, cat: "ai", "ai {}", Formatted(&formatted));
+    assert_eq!(formatted.get(), 0);
+    screen_print!(no_prefix, level: Level::Warn, "warn {}", Formatted(&formatted));
+    assert_ne!(formatted.get(), 0);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["warn formatted"]);
+}
+
+#[test]
+fn max_visible_lines_limits_layout() {
+    let plugin = OverlayPlugin { max_visible_lines: Some(2), ..default() };
+    let mut harness = Harness::new(plugin);
+    for text in ["a", "b", "c"] {
+        screen_print!(push, no_prefix, "{text}");
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["b", "c"]);
+}
+
+#[test]
+fn high_priority_messages_are_not_culled() {
+    let plugin = OverlayPlugin { max_visible_lines: Some(2), ..default() };
+    let mut harness = Harness::new(plugin);
+    screen_print!(push, prio: 1, no_prefix, "a");
+    harness.frame(0.1);
+    for text in ["b", "c"] {
+        screen_print!(push, no_prefix, "{text}");
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["a", "c"]);
+}
+
+#[test]
+fn paused_messages_do_not_expire() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(sec: 1.0, no_prefix, "paused");
+    harness.frame(0.1);
+    harness
+        .app
+        .world
+        .resource_mut::<DebugOverlayPaused>()
+        .paused = true;
+    harness.frame(5.0);
+    assert_eq!(harness.visible(), ["paused"]);
+    harness
+        .app
+        .world
+        .resource_mut::<DebugOverlayPaused>()
+        .paused = false;
+    harness.frame(1.0);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn sites_muted_while_paused_are_cleared() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(persist, no_prefix, key: "noisy", "noisy");
+    screen_print!(persist, no_prefix, key: "calm", "calm");
+    harness.frame(0.1);
+    let mut paused = harness.app.world.resource_mut::<DebugOverlayPaused>();
+    paused.paused = true;
+    let noisy = InvocationSiteKey::Named("noisy".into());
+    let mut sites = harness.app.world.resource_mut::<DebugOverlaySites>();
+    sites.set_enabled(noisy.clone(), false);
+    harness.frame(0.1);
+    let mut paused = harness.app.world.resource_mut::<DebugOverlayPaused>();
+    paused.paused = false;
+    harness.frame(1.0);
+    assert_eq!(harness.visible(), ["calm"]);
+    let mut sites = harness.app.world.resource_mut::<DebugOverlaySites>();
+    sites.set_enabled(noisy, true);
+    screen_print!(persist, no_prefix, key: "noisy", "noisy again");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["calm", "noisy again"]);
+}
+
+#[test]
+fn group_by_file_adds_collapsible_header() {
+    let plugin = OverlayPlugin {
+        group_by_file: true,
+        collapse_key: Some(KeyCode::F1),
+        ..default()
+    };
+    let mut harness = Harness::new(plugin);
+    harness.app.init_resource::<ButtonInput<KeyCode>>();
+    for _ in 0..2 {
+        screen_print!(no_prefix, "first");
+        screen_print!(no_prefix, "second");
+        harness.frame(0.1);
+    }
+    let header = "- tests/overlay.rs (2)";
+    assert_eq!(harness.visible(), [header, "first", "second"]);
+
+    let mut keys = harness.app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.press(KeyCode::F1);
+    harness.app.update();
+    let mut keys = harness.app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.reset_all();
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["+ tests/overlay.rs (2)"]);
+}
+
+#[test]
+fn blinking_message_pulses_until_printed_without_blink() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let alpha = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let text = query.iter(&harness.app.world).next().unwrap();
+        text.sections.last().unwrap().style.color.a()
+    };
+    let mut alphas = Vec::new();
+    for _ in 0..10 {
+        screen_print!(blink: 1, key: "alarm", "alarm");
+        harness.frame(0.1);
+        alphas.push(alpha(&mut harness));
+    }
+    assert!(alphas.iter().any(|&alpha| alpha < 0.5), "{alphas:?}");
+    screen_print!(key: "alarm", "alarm");
+    harness.frame(0.1);
+    assert_eq!(alpha(&mut harness), 1.0);
+}
+
+#[test]
+fn scale_resizes_displayed_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!(persist, key: "default", "default");
+    screen_print!(persist, size: 20, key: "big", "big");
+    harness.frame(0.1);
+    let font_sizes = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let mut sizes: Vec<_> = query
+            .iter(&harness.app.world)
+            .map(|text| text.sections[0].style.font_size)
+            .collect();
+        sizes.sort_by(f32::total_cmp);
+        sizes
+    };
+    assert_eq!(font_sizes(&mut harness), [13.0, 20.0]);
+    harness.app.world.resource_mut::<DebugOverlayScale>().scale = 2.0;
+    harness.frame(0.1);
+    assert_eq!(font_sizes(&mut harness), [26.0, 40.0]);
+}
+
+#[test]
+fn color_markup_splits_sections() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let colors = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let text = query.iter(&harness.app.world).next().unwrap();
+        let sections = text.sections.iter();
+        sections
+            .map(|section| section.style.color)
+            .collect::<Vec<_>>()
+    };
+    for hp in [3, 4] {
+        screen_print!(no_prefix, key: "hp", "hp: [red]{hp}[/] / 20");
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["hp: 4 / 20"]);
+    assert_eq!(
+        colors(&mut harness),
+        [Color::YELLOW, Color::RED, Color::YELLOW]
+    );
+    screen_print!(no_prefix, key: "hp", "hp: 4 / 20");
+    harness.frame(0.1);
+    assert_eq!(colors(&mut harness), [Color::YELLOW]);
+}
+
+#[test]
+fn conditional_messages_clear_once_recovered() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let check = |harness: &mut Harness, hp: i32| {
+        screen_print_if!(hp < 10, no_prefix, "low hp: {hp}");
+        screen_assert!(hp >= 0, no_prefix, "negative hp");
+        screen_assert!(hp != 0);
+        harness.frame(0.1);
+        harness.visible()
+    };
+    assert!(check(&mut harness, 20).is_empty());
+    let visible = check(&mut harness, 0);
+    assert!(
+        visible[0].ends_with("assertion failed: hp != 0"),
+        "{visible:?}"
+    );
+    assert_eq!(check(&mut harness, -5), ["low hp: -5", "negative hp"]);
+    assert_eq!(check(&mut harness, 5), ["low hp: 5"]);
+    assert!(check(&mut harness, 20).is_empty());
+}
+
+#[test]
+fn watched_value_flashes_on_change() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    let watch = |harness: &mut Harness, hp: i32| {
+        screen_watch!(hp);
+        harness.frame(0.1);
+        let mut query = harness.app.world.query::<&BackgroundColor>();
+        let flashing = query.iter(&harness.app.world).any(|bg| bg.0 != Color::NONE);
+        (harness.visible(), flashing)
+    };
+    assert_eq!(watch(&mut harness, 10), (vec!["hp = 10".to_owned()], false));
+    assert_eq!(watch(&mut harness, 10), (vec!["hp = 10".to_owned()], false));
+    assert_eq!(watch(&mut harness, 9), (vec!["hp = 9".to_owned()], true));
+}
+
+#[test]
+fn diff_highlights_changed_lines() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    let sections = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let text = query.iter(&harness.app.world).next().unwrap();
+        let sections = text.sections.iter();
+        sections
+            .map(|section| (section.value.clone(), section.style.color))
+            .collect::<Vec<_>>()
+    };
+    for hp in [10, 9] {
+        screen_print!(diff, "hp: {hp}\nspeed: 2");
+        harness.frame(0.1);
+    }
+    let expected = [
+        ("hp: 9\n".to_owned(), Color::GREEN),
+        ("hp: 10\n".to_owned(), Color::RED),
+        ("speed: 2\n".to_owned(), Color::YELLOW),
+    ];
+    assert_eq!(sections(&mut harness), expected);
+    harness.frame(1.0);
+    let expected = [("hp: 9\nspeed: 2\n".to_owned(), Color::YELLOW)];
+    assert_eq!(sections(&mut harness), expected);
+}
+
+#[test]
+fn tail_displays_last_values_newest_first() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    for hit in [1, 2, 2, 3] {
+        screen_print!(tail: 3, no_prefix, "hit {hit}");
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["hit 3\nhit 2\nhit 2"]);
+}
+
+#[test]
+fn alignment_justifies_message_lines() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let justify = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let mut justify: Vec<_> = query.iter(&harness.app.world).map(|t| t.justify).collect();
+        justify.sort_by_key(|justify| *justify as u8);
+        justify
+    };
+    screen_print!(key: "a", "a\nbb");
+    screen_print!(align: Alignment::Right, key: "b", "ccc\nd");
+    harness.frame(0.1);
+    assert_eq!(
+        justify(&mut harness),
+        [JustifyText::Left, JustifyText::Right]
+    );
+    let mut location = harness.app.world.resource_mut::<DebugOverlayLocation>();
+    location.alignment = Alignment::Center;
+    harness.frame(0.1);
+    assert_eq!(
+        justify(&mut harness),
+        [JustifyText::Center, JustifyText::Right]
+    );
+}
+
+#[test]
+fn config_changes_restyle_messages() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let styles = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Text>();
+        let mut styles: Vec<_> = query
+            .iter(&harness.app.world)
+            .map(|text| {
+                (
+                    text.sections[0].style.color,
+                    text.sections[0].style.font_size,
+                )
+            })
+            .collect();
+        styles.sort_by(|a, b| a.1.total_cmp(&b.1));
+        styles
+    };
+    screen_print!("fallback");
+    screen_print!(col: Color::RED, size: 20, "explicit");
+    harness.frame(0.1);
+    assert_eq!(
+        styles(&mut harness),
+        [(Color::YELLOW, 13.0), (Color::RED, 20.0)]
+    );
+    let mut config = harness.app.world.resource_mut::<OverlayConfig>();
+    config.fallback_color = Color::WHITE;
+    config.font_size = 16.0;
+    harness.frame(0.1);
+    assert_eq!(
+        styles(&mut harness),
+        [(Color::WHITE, 16.0), (Color::RED, 20.0)]
+    );
+}
+
+#[test]
+fn muted_sites_are_hidden() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let print = |harness: &mut Harness, value: i32| {
+        screen_print!(no_prefix, key: "noisy", "noisy {value}");
+        screen_print!(no_prefix, key: "calm", "calm");
+        harness.frame(0.1);
+    };
+    print(&mut harness, 0);
+    let noisy = InvocationSiteKey::Named("noisy".into());
+    let sites = harness.app.world.resource::<DebugOverlaySites>();
+    assert_eq!(sites.iter().count(), 2);
+    assert!(sites.is_enabled(&noisy));
+    let mut sites = harness.app.world.resource_mut::<DebugOverlaySites>();
+    sites.set_enabled(noisy.clone(), false);
+    print(&mut harness, 1);
+    assert_eq!(harness.visible(), ["calm"]);
+    let mut sites = harness.app.world.resource_mut::<DebugOverlaySites>();
+    sites.toggle(noisy);
+    print(&mut harness, 2);
+    assert_eq!(harness.visible(), ["calm", "noisy 2"]);
+}
+
+#[test]
+fn senders_print_from_other_threads() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    let sender = OverlaySender::new("worker");
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let sender = sender.clone();
+            std::thread::spawn(move || sender.push(format!("job {i}")))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    sender.print("status");
+    harness.frame(0.1);
+    assert_eq!(
+        harness.visible(),
+        ["job 0", "job 1", "job 2", "job 3", "status"]
+    );
+    sender.clear();
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["job 0", "job 1", "job 2", "job 3"]);
+}
+
+#[test]
+fn frame_scoped_messages_expire_after_frames() {
+    let plugin = OverlayPlugin { fade_duration: 0.0, ..default() };
+    let mut harness = Harness::new(plugin);
+    screen_print!(frames: 3, no_prefix, "stepping");
+    // Each `frame` runs two updates.
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["stepping"]);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["stepping"]);
+    harness.frame(0.1);
+    assert!(harness.visible().is_empty());
+}
+
+#[test]
+fn timers_display_duration_statistics() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    for _ in 0..3 {
+        let _timer = screen_timer!("search");
+    }
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert_eq!(visible.len(), 1);
+    assert!(visible[0].starts_with("search: "), "{visible:?}");
+    assert!(visible[0].contains("(min "), "{visible:?}");
+}
+
+#[test]
+fn histograms_count_values_in_buckets() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    for frame_ms in [10, 10, 10, 30] {
+        screen_histogram!("frame_ms", frame_ms, buckets: 3);
+        harness.frame(0.1);
+    }
+    assert_eq!(harness.visible(), ["frame_ms 10.00 '_- 30.00"]);
+}
+
+#[test]
+fn stats_aggregate_numeric_series() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    for frame_ms in [16, 33, 14] {
+        screen_print!(stats, "frame_ms", frame_ms);
+        harness.frame(0.1);
+    }
+    let expected = "frame_ms: 14.00 (min 14.00, avg 21.00, max 33.00)";
+    assert_eq!(harness.visible(), [expected]);
+}
+
+#[test]
+fn counters_display_running_totals() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    let spawn = |count| {
+        // More increments than messages can be sent per frame.
+        for _ in 0..count {
+            screen_counter!("spawned");
+        }
+    };
+    spawn(5000);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["spawned: 5000"]);
+    spawn(2);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["spawned: 5002"]);
+}
+
+#[test]
+fn counters_reset_without_being_incremented() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    for _ in 0..3 {
+        screen_counter!("hits", reset: 0.05);
+    }
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["hits: 3"]);
+    std::thread::sleep(std::time::Duration::from_millis(60));
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["hits: 0"]);
+}
+
+fn print_from_system() {
+    screen_print!(sys, "in system");
+    let in_closure = || screen_print!(key: "closure", "in closure");
+    in_closure();
+}
+
+#[test]
+fn sys_prefix_shows_printing_function() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    print_from_system();
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert!(visible.contains(&"[print_from_system] in system".to_owned()));
+    assert!(visible.contains(&"[closure] in closure".to_owned()));
+
+    let plugin = OverlayPlugin { prefix: MessagePrefix::System, ..default() };
+    drop(harness);
+    let mut harness = Harness::new(plugin);
+    print_from_system();
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert!(visible.contains(&"[print_from_system] in closure".to_owned()));
+
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    drop(harness);
+    let mut harness = Harness::new(plugin);
+    print_from_system();
+    harness.frame(0.1);
+    let visible = harness.visible();
+    assert!(visible.contains(&"[print_from_system] in system".to_owned()));
+    assert!(visible.contains(&"in closure".to_owned()));
+}
+
+#[test]
+fn snapshots_capture_displayed_messages() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    screen_print!(col: Color::RED, "first");
+    harness.frame(0.1);
+    screen_print!(sec: 0.5, "second");
+    harness.frame(0.1);
+    let snapshot = OverlaySnapshot::capture(&mut harness.app.world);
+    let lines: Vec<_> = snapshot
+        .lines
+        .iter()
+        .map(|line| (&line.text[..], line.color))
+        .collect();
+    assert_eq!(
+        lines,
+        [("first\n", Color::RED), ("second\n", Color::YELLOW)]
+    );
+
+    harness.frame(1.0);
+    let snapshot = OverlaySnapshot::capture(&mut harness.app.world);
+    let texts: Vec<_> = snapshot.lines.iter().map(|line| &line.text[..]).collect();
+    assert_eq!(texts, ["first\n"]);
+}
+
+#[test]
+fn recordings_replay_messages_in_time() {
+    let plugin = || OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin());
+    harness.app.init_resource::<OverlayRecording>();
+    screen_print!("first");
+    harness.frame(0.1);
+    harness.frame(0.5);
+    screen_print!(key: "second", col: Color::RED, "second");
+    screen_clear!("second");
+    screen_print!("third");
+    harness.frame(0.1);
+    harness.frame(0.5);
+    screen_clear!();
+    harness.frame(0.1);
+    let records = harness
+        .app
+        .world
+        .resource::<OverlayRecording>()
+        .records()
+        .to_vec();
+    let changes: Vec<_> = records.iter().map(|record| &record.change).collect();
+    let print = |text: &str| RecordedChange::Print(text.to_owned());
+    let expected = [
+        print("first"),
+        print("second"),
+        RecordedChange::Clear,
+        print("third"),
+        RecordedChange::ClearAll,
+    ];
+    assert_eq!(changes, expected.iter().collect::<Vec<_>>());
+    assert!(records[0].site.starts_with("tests/overlay.rs:"));
+    assert_eq!(records[1].color, Some(Color::RED));
+    assert!((records[1].time - 0.6).abs() < 1e-6);
+    drop(harness);
+
+    let mut harness = Harness::new(plugin());
+    harness.app.insert_resource(OverlayReplay::new(records));
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["first"]);
+    harness.frame(0.3);
+    assert!(!harness.app.world.resource::<OverlayReplay>().is_finished());
+    harness.frame(0.4);
+    assert_eq!(harness.visible(), ["first", "third"]);
+    harness.frame(0.5);
+    assert!(harness.visible().is_empty());
+    assert!(harness.app.world.resource::<OverlayReplay>().is_finished());
+}
+
+#[test]
+fn gamepad_buttons_control_overlay() {
+    let plugin = OverlayPlugin {
+        gamepad: Some(GamepadBindings::default()),
+        ..default()
+    };
+    let mut harness = Harness::new(plugin);
+    harness.app.add_plugins(InputPlugin);
+    let gamepad = Gamepad::new(0);
+    let info = GamepadInfo { name: "dev kit".to_owned() };
+    let connected = GamepadConnectionEvent::new(gamepad, GamepadConnection::Connected(info));
+    harness.app.world.send_event(GamepadEvent::from(connected));
+    harness
+        .app
+        .world
+        .resource_mut::<DebugOverlayCategories>()
+        .hotkeys = vec!["physics".into(), "ai".into()];
+    let press = |harness: &mut Harness, button| {
+        for value in [1.0, 0.0] {
+            let event = GamepadButtonChangedEvent::new(gamepad, button, value);
+            harness.app.world.send_event(GamepadEvent::from(event));
+            harness.app.update();
+        }
+    };
+    screen_print!(no_prefix, cat: "physics", "physics");
+    screen_print!(no_prefix, cat: "ai", "ai");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["ai", "physics"]);
+
+    press(&mut harness, GamepadButtonType::DPadRight);
+    let categories = harness.app.world.resource::<DebugOverlayCategories>();
+    assert!(categories.is_enabled("physics"));
+    assert!(!categories.is_enabled("ai"));
+
+    press(&mut harness, GamepadButtonType::Select);
+    assert!(!harness.app.world.resource::<OverlayVisibility>().visible);
+}
+
+#[test]
+fn targeted_messages_stack_per_viewport() {
+    let plugin = OverlayPlugin { prefix: MessagePrefix::Hidden, ..default() };
+    let mut harness = Harness::new(plugin);
+    let mut spawn_camera = |name: &'static str| {
+        let viewport = OverlayViewport(name.into());
+        harness.app.world.spawn((Camera::default(), viewport)).id()
+    };
+    let cameras = [spawn_camera("player 1"), spawn_camera("player 2")];
+    for (player, health) in [("player 1", 10), ("player 2", 20)] {
+        screen_print!(target: player, "health: {health}");
+    }
+    screen_print!("shared");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["health: 10", "health: 20", "shared"]);
+
+    let mut query = harness.app.world.query::<(&Text, &Parent)>();
+    let mut roots = harness.app.world.query::<&TargetCamera>();
+    let world = &harness.app.world;
+    let mut targets: Vec<_> = query
+        .iter(world)
+        .map(|(text, parent)| {
+            let camera = roots.get(world, parent.get()).ok().map(|target| target.0);
+            (text.sections[0].value.trim_end().to_owned(), camera)
+        })
+        .collect();
+    targets.sort();
+    let expected = [
+        ("health: 10".to_owned(), Some(cameras[0])),
+        ("health: 20".to_owned(), Some(cameras[1])),
+        ("shared".to_owned(), None),
+    ];
+    assert_eq!(targets, expected);
+}
+
+#[test]
+fn viewport_roots_spawned_while_hidden_are_hidden() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    harness.frame(0.1);
+    let mut visibility = harness.app.world.resource_mut::<OverlayVisibility>();
+    visibility.visible = false;
+    harness.frame(0.1);
+    let viewport = OverlayViewport("player 1".into());
+    harness.app.world.spawn((Camera::default(), viewport));
+    screen_print!(target: "player 1", "health: 10");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query::<(&Text, &Parent)>();
+    let world = &harness.app.world;
+    let (_, parent) = query.single(world);
+    let root = world.get::<Visibility>(parent.get());
+    assert_eq!(root, Some(&Visibility::Hidden));
+}
+
+#[test]
+fn long_messages_wrap_at_max_width() {
+    let plugin = OverlayPlugin { max_width: Val::Percent(50.0), ..default() };
+    let mut harness = Harness::new(plugin);
+    harness.app.world.spawn((Window::default(), PrimaryWindow));
+    screen_print!("a long line that would run off the screen");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query_filtered::<&Style, With<Text>>();
+    let widths: Vec<_> = query
+        .iter(&harness.app.world)
+        .map(|style| style.max_width)
+        .collect();
+    assert_eq!(widths, [Val::Px(640.0)]);
+}
+
+#[test]
+fn window_resize_and_ui_scale_rewrap_messages() {
+    let plugin = OverlayPlugin { max_width: Val::Percent(50.0), ..default() };
+    let mut harness = Harness::new(plugin);
+    harness.app.add_event::<WindowResized>();
+    let window = harness
+        .app
+        .world
+        .spawn((Window::default(), PrimaryWindow))
+        .id();
+    screen_print!("a long line that would run off the screen");
+    harness.frame(0.1);
+    let max_width = |harness: &mut Harness| {
+        let mut query = harness.app.world.query_filtered::<&Style, With<Text>>();
+        let widths = query.iter(&harness.app.world).map(|style| style.max_width);
+        widths.collect::<Vec<_>>()
+    };
+    assert_eq!(max_width(&mut harness), [Val::Px(640.0)]);
+
+    let mut entity = harness.app.world.entity_mut(window);
+    entity
+        .get_mut::<Window>()
+        .unwrap()
+        .resolution
+        .set(800.0, 600.0);
+    harness
+        .app
+        .world
+        .send_event(WindowResized { window, width: 800.0, height: 600.0 });
+    harness.frame(0.1);
+    assert_eq!(max_width(&mut harness), [Val::Px(400.0)]);
+
+    harness.app.world.insert_resource(UiScale(2.0));
+    harness.frame(0.1);
+    assert_eq!(max_width(&mut harness), [Val::Px(200.0)]);
+}
+
+/// Places messages side by side, 100 pixels apart.
+#[derive(Default)]
+struct Row(Vec<(Entity, Vec2)>);
+impl OverlayLayout for Row {
+    fn insert(&mut self, entity: Entity, size: Vec2) -> Vec2 {
+        self.0.push((entity, size));
+        Vec2::new(100.0 * (self.0.len() - 1) as f32, 0.0)
+    }
+    fn remove(&mut self, entity: Entity) {
+        self.0.retain(|(placed, _)| *placed != entity);
+    }
+    fn size_of(&self, entity: Entity) -> Option<Vec2> {
+        let mut placed = self.0.iter();
+        placed
+            .find(|(placed, _)| *placed == entity)
+            .map(|(_, size)| *size)
+    }
+    fn size(&self) -> Vec2 {
+        Vec2::new(100.0 * self.0.len() as f32, 0.0)
+    }
+}
+
+#[test]
+fn custom_layouts_place_messages() {
+    let layout = Layout::Custom(Arc::new(|_| Box::<Row>::default()));
+    let mut harness = Harness::new(OverlayPlugin { layout, ..default() });
+    screen_print!(push, "first");
+    screen_print!(push, "second");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query_filtered::<&Style, With<Text>>();
+    let mut lefts: Vec<_> = query
+        .iter(&harness.app.world)
+        .map(|style| style.left)
+        .collect();
+    lefts.sort_by(|a, b| format!("{a:?}").cmp(&format!("{b:?}")));
+    assert_eq!(lefts, [Val::Px(0.0), Val::Px(100.0)]);
+}
+
+#[test]
+fn indented_messages_are_shifted() {
+    let mut harness = Harness::new(OverlayPlugin { indent_width: 10.0, ..default() });
+    screen_print!(push, "parent");
+    screen_print!(push, indent: 2, "child");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query::<(&Text, &Style)>();
+    let mut left_of = |text: &str| {
+        let mut placed = query.iter(&harness.app.world);
+        let (_, style) = placed
+            .find(|(ui_text, _)| ui_text.sections.iter().any(|s| s.value.contains(text)))
+            .unwrap();
+        style.left
+    };
+    assert_eq!(left_of("parent"), Val::Px(0.0));
+    assert_eq!(left_of("child"), Val::Px(20.0));
+}
+
+#[test]
+fn themes_color_messages_by_level_and_category() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let mut theme = OverlayTheme::high_contrast();
+    theme.categories.insert("ai".into(), Color::GREEN);
+    harness.app.insert_resource(theme);
+    harness.frame(0.1);
+    screen_print!(level: Level::Error, "crashed");
+    screen_print!(level: Level::Error, cat: "ai", "lost target");
+    screen_print!(level: Level::Error, col: Color::BLUE, "overridden");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query::<&Text>();
+    let mut color_of = |text: &str| {
+        let mut texts = query.iter(&harness.app.world);
+        let ui_text = texts
+            .find(|ui_text| ui_text.sections.iter().any(|s| s.value.contains(text)))
+            .unwrap();
+        ui_text.sections.last().unwrap().style.color
+    };
+    assert_eq!(color_of("crashed"), Color::RED);
+    assert_eq!(color_of("lost target"), Color::GREEN);
+    assert_eq!(color_of("overridden"), Color::BLUE);
+}
+
+#[test]
+fn overlay_and_message_opacity_multiply() {
+    let mut harness = Harness::new(OverlayPlugin::default());
+    screen_print!("bright");
+    screen_print!(alpha: 0.5, "faint");
+    harness.frame(0.1);
+    let alpha_of = |harness: &mut Harness, text: &str| {
+        let mut query = harness.app.world.query::<&Text>();
+        let mut texts = query.iter(&harness.app.world);
+        let ui_text = texts
+            .find(|ui_text| ui_text.sections.iter().any(|s| s.value.contains(text)))
+            .unwrap();
+        ui_text.sections.last().unwrap().style.color.a()
+    };
+    assert_eq!(alpha_of(&mut harness, "bright"), 1.0);
+    assert_eq!(alpha_of(&mut harness, "faint"), 0.5);
+    harness
+        .app
+        .world
+        .resource_mut::<DebugOverlayOpacity>()
+        .opacity = 0.5;
+    harness.frame(0.1);
+    assert_eq!(alpha_of(&mut harness, "bright"), 0.5);
+    assert_eq!(alpha_of(&mut harness, "faint"), 0.25);
+}
+
+#[test]
+fn grouped_messages_are_nested_under_their_header() {
+    let mut harness = Harness::new(OverlayPlugin { indent_width: 10.0, ..default() });
+    let length = screen_group!(col: Color::GREEN, "AI", {
+        screen_print!("target: player");
+        screen_group!("path", {
+            screen_print!(col: Color::RED, "length: 3");
+        });
+        3
+    });
+    screen_print!("outside: {length}");
+    harness.frame(0.1);
+    let mut query = harness.app.world.query::<(&Text, &Style)>();
+    let mut placed = |text: &str| {
+        let mut placed = query.iter(&harness.app.world);
+        let (ui_text, style) = placed
+            .find(|(ui_text, _)| ui_text.sections.iter().any(|s| s.value.contains(text)))
+            .unwrap();
+        (style.left, ui_text.sections.last().unwrap().style.color)
+    };
+    let white = OverlayPlugin::default().fallback_color;
+    assert_eq!(placed("AI"), (Val::Px(0.0), Color::GREEN));
+    assert_eq!(placed("target"), (Val::Px(10.0), Color::GREEN));
+    assert_eq!(placed("path"), (Val::Px(10.0), Color::GREEN));
+    assert_eq!(placed("length"), (Val::Px(20.0), Color::RED));
+    assert_eq!(placed("outside: 3"), (Val::Px(0.0), white));
+}
+
+#[test]
+fn truncated_messages_expand_while_key_held() {
+    let plugin = OverlayPlugin {
+        prefix: MessagePrefix::Hidden,
+        expand_key: Some(KeyCode::AltLeft),
+        ..default()
+    };
+    let mut harness = Harness::new(plugin);
+    harness.app.init_resource::<ButtonInput<KeyCode>>();
+    screen_print!(lines: 2, "a\nb\nc\nd");
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["a\nb\n\u{2026} 2 more lines"]);
+
+    let mut keys = harness.app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.press(KeyCode::AltLeft);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["a\nb\nc\nd"]);
+
+    let mut keys = harness.app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.release(KeyCode::AltLeft);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["a\nb\n\u{2026} 2 more lines"]);
+}
+
+#[test]
+fn identical_pushes_coalesce_with_count() {
+    let plugin = OverlayPlugin {
+        prefix: MessagePrefix::Hidden,
+        coalesce: true,
+        history: Some(16),
+        ..default()
+    };
+    let mut harness = Harness::new(plugin);
+    let retry = |times| {
+        for _ in 0..times {
+            screen_print!(push, "retrying");
+        }
+    };
+    retry(3);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["retrying \u{d7} 3"]);
+    retry(2);
+    harness.frame(0.1);
+    assert_eq!(harness.visible(), ["retrying \u{d7} 5"]);
+
+    screen_print!(push, coalesce: false, "retrying");
+    screen_print!(push, coalesce: false, "retrying");
+    harness.frame(0.1);
+    assert_eq!(
+        harness.visible(),
+        ["retrying", "retrying", "retrying \u{d7} 5"]
+    );
+    // Each push shows up in the history, even when coalesced.
+    let history = harness.app.world.resource::<OverlayHistory>();
+    assert_eq!(history.entries().len(), 7);
+}
+
+#[cfg(feature = "audio")]
+#[test]
+fn beeping_messages_play_a_tone_when_shown() {
+    use bevy::audio::Pitch;
+
+    let mut harness = Harness::new(OverlayPlugin::default());
+    harness.app.add_plugins(AssetPlugin::default());
+    harness.app.init_asset::<Pitch>();
+    let tones = |harness: &mut Harness| {
+        let mut query = harness.app.world.query::<&Handle<Pitch>>();
+        query.iter(&harness.app.world).count()
+    };
+    // The text changes, so that the print cache doesn't skip the second one.
+    for i in 0..2 {
+        screen_print!(beep, key: "alarm", "connection lost {i}");
+        harness.frame(0.1);
+    }
+    assert_eq!(tones(&mut harness), 1);
+}
+
+#[cfg(feature = "assets")]
+#[test]
+fn assets_failing_to_load_are_listed() {
+    use std::time::Duration;
+
+    use bevy::tasks::{IoTaskPool, TaskPool};
+    use bevy_debug_text_overlay::OverlayAssetsPlugin;
+
+    #[derive(Asset, TypePath)]
+    struct Map;
+
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut harness = Harness::new(OverlayPlugin::default());
+    let plugins = (
+        AssetPlugin::default(),
+        OverlayAssetsPlugin::<Map>::default(),
+    );
+    harness.app.add_plugins(plugins).init_asset::<Map>();
+    let map = harness
+        .app
+        .world
+        .resource::<AssetServer>()
+        .load::<Map>("missing.map");
+    harness.app.world.spawn(map);
+    let failed = |text: &String| text.contains("failed: missing.map: ");
+    // Loading happens in another thread.
+    for _ in 0..100 {
+        harness.frame(0.1);
+        if harness.visible().iter().any(failed) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!(
+        "missing.map is not listed as failed: {:?}",
+        harness.visible()
+    );
+}
+
+#[cfg(feature = "timings")]
+#[test]
+fn slowest_systems_are_displayed() {
+    use std::time::Duration;
+
+    use bevy::log::tracing_subscriber::{layer::SubscriberExt, Registry};
+    use bevy::utils::tracing::{self, info_span};
+    use bevy_debug_text_overlay::{SystemTimingLayer, SystemTimingsPlugin};
+
+    let plugin = SystemTimingsPlugin { top: 1, interval: 0.5 };
+    let mut harness = Harness::new(OverlayPlugin::default());
+    harness
+        .app
+        .add_plugins(plugin)
+        .init_resource::<Time<Real>>();
+    let subscriber = Registry::default().with(SystemTimingLayer::default());
+    tracing::subscriber::with_default(subscriber, || {
+        // Bevy's spans when built with the `trace` feature.
+        let slow = info_span!("system", name = "game::slow_system");
+        let fast = info_span!("system", name = "game::fast_system");
+        for _ in 0..2 {
+            slow.in_scope(|| std::thread::sleep(Duration::from_millis(20)));
+            fast.in_scope(|| {});
+        }
+    });
+    let advance_real_time = |harness: &mut Harness, secs| {
+        let mut real_time = harness.app.world.resource_mut::<Time<Real>>();
+        real_time.advance_by(Duration::from_secs(secs));
+    };
+    advance_real_time(&mut harness, 1);
+    harness.app.update();
+    // Messages printed in `Last` are only handled in the next frame.
+    advance_real_time(&mut harness, 0);
+    harness.frame(0.1);
+    let visible = harness.visible();
+    let timings = visible
+        .iter()
+        .find(|text| text.contains("slowest systems:"));
+    let timings = timings.expect("timings are displayed");
+    assert!(timings.contains("ms slow_system"), "{timings}");
+    assert!(!timings.contains("fast_system"), "{timings}");
+}
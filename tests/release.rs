@@ -0,0 +1,57 @@
+//! The macros without the `debug` cargo feature, run with
+//! `cargo test --no-default-features --test release`.
+#![cfg(not(feature = "debug"))]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use bevy::prelude::Color;
+use bevy_debug_text_overlay::{
+    screen_clear, screen_graph, screen_group, screen_print, screen_print_if, screen_watch,
+};
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations of each thread.
+struct CountingAllocator;
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn macros_neither_evaluate_nor_format_arguments() {
+    let evaluated = Cell::new(0);
+    let value = || {
+        evaluated.set(evaluated.get() + 1);
+        42
+    };
+    let name = String::from("player");
+    let before = ALLOCATIONS.with(Cell::get);
+    screen_print!("value: {}", value());
+    let timeout = || f64::from(value());
+    screen_print!(col: Color::RED, sec: timeout(), key: name, "{name}: {:?}", vec![value()]);
+    screen_print_if!(value() > 0, "positive: {}", value());
+    screen_graph!("value", value() as f32);
+    screen_print!(stats, "value", value());
+    screen_watch!(name);
+    // The block of a group is still run.
+    let grouped = screen_group!(key: name, "group", {
+        screen_print!("in group: {}", value());
+        1
+    });
+    assert_eq!(grouped, 1);
+    screen_clear!(name);
+    assert_eq!(ALLOCATIONS.with(Cell::get), before);
+    assert_eq!(evaluated.get(), 0);
+    // Arguments are not moved into the macros.
+    assert_eq!(name, "player");
+}